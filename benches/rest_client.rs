@@ -0,0 +1,103 @@
+// Overhead of a signed REST call, piece by piece, and order round trips against a local mock.
+//
+//     cargo bench --features bench --bench rest_client
+
+use bitsdk_rust::bit_rest_client::{BitRestClient, V1_LINEAR_ORDERS, bench};
+use bitsdk_rust::mock::MockExchange;
+use bitsdk_rust::models::{Market, OrderRequest, Side};
+use bitsdk_rust::trading::TradingApi;
+use criterion::{BenchmarkId, Criterion, Throughput, black_box, criterion_group, criterion_main};
+use futures_util::future::join_all;
+use serde_json::Value;
+
+fn order() -> OrderRequest {
+    OrderRequest::limit("BTC-USDT-PERPETUAL", Side::Buy, 1.5, 20000.0).with_label("bench-1")
+}
+
+const TIMESTAMP: i64 = 1_700_000_000_000;
+
+fn signed_params() -> Value {
+    let mut params = order().to_params(Market::Linear);
+    params["timestamp"] = Value::from(TIMESTAMP);
+    params
+}
+
+fn signing(c: &mut Criterion) {
+    let client = BitRestClient::new("bench-ak", "bench-sk", "http://127.0.0.1:1");
+    let params = signed_params();
+
+    c.bench_function("to_params", |b| {
+        b.iter(|| black_box(order()).to_params(Market::Linear))
+    });
+    c.bench_function("encode_object", |b| {
+        b.iter(|| bench::encode_object(black_box(&params)))
+    });
+    c.bench_function("sign_value", |b| {
+        b.iter(|| {
+            let mut params = black_box(&params).clone();
+            bench::sign_value(&client, V1_LINEAR_ORDERS, &mut params, TIMESTAMP);
+            params
+        })
+    });
+    c.bench_function("json_body", |b| {
+        b.iter(|| serde_json::to_vec(black_box(&params)).unwrap())
+    });
+    c.bench_function("auth_headers", |b| {
+        b.iter(|| bench::auth_headers(black_box(&client)))
+    });
+
+    // the whole body of a new order: through `Value` as the raw methods do, and typed
+    let req = order();
+    c.bench_function("signed_body_value", |b| {
+        b.iter(|| {
+            let mut params = black_box(&req).to_params(Market::Linear);
+            bench::sign_value(&client, V1_LINEAR_ORDERS, &mut params, TIMESTAMP);
+            serde_json::to_vec(&params).unwrap()
+        })
+    });
+    c.bench_function("signed_body_typed", |b| {
+        b.iter(|| {
+            let params = black_box(&req).params(Market::Linear);
+            bench::signed_body(
+                &client,
+                V1_LINEAR_ORDERS,
+                params.into_iter().flatten(),
+                TIMESTAMP,
+            )
+        })
+    });
+}
+
+fn round_trip(c: &mut Criterion) {
+    let rt = tokio::runtime::Runtime::new().unwrap();
+    let mock = rt.block_on(MockExchange::start("127.0.0.1:0")).unwrap();
+    let client = BitRestClient::new("bench-ak", "bench-sk", &mock.url());
+    let req = order();
+
+    let mut group = c.benchmark_group("new_order_round_trip");
+    for concurrency in [1usize, 8, 32] {
+        group.throughput(Throughput::Elements(concurrency as u64));
+        group.bench_with_input(
+            BenchmarkId::from_parameter(concurrency),
+            &concurrency,
+            |b, &n| {
+                b.iter(|| {
+                    rt.block_on(join_all(
+                        (0..n).map(|_| client.new_order(Market::Linear, &req)),
+                    ))
+                })
+            },
+        );
+    }
+    group.finish();
+
+    c.bench_function("linear_new_order_round_trip", |b| {
+        b.iter(|| {
+            let mut params = req.to_params(Market::Linear);
+            rt.block_on(client.linear_new_order(&mut params))
+        })
+    });
+}
+
+criterion_group!(benches, signing, round_trip);
+criterion_main!(benches);
@@ -0,0 +1,127 @@
+// WebSocket message decoding: through `serde_json::Value` as before, and straight from the
+// frame text with `WsFrame`, for a full depth snapshot and for linear and option tickers.
+//
+//     cargo bench --bench ws_decode
+
+use bitsdk_rust::bit_ws_client::WsEvent;
+use bitsdk_rust::ws_frame::{FrameDecoder, WsFrame};
+use criterion::{Criterion, Throughput, black_box, criterion_group, criterion_main};
+use flate2::Compression;
+use flate2::write::GzEncoder;
+use serde_json::Value;
+use std::io::Write;
+
+fn depth_message(levels: usize) -> String {
+    let side = |start: f64, step: f64| -> Vec<Value> {
+        (0..levels)
+            .map(|i| {
+                let price = start + step * i as f64;
+                serde_json::json!([format!("{:.2}", price), "1.25000000"])
+            })
+            .collect()
+    };
+    serde_json::json!({
+        "channel": "depth",
+        "timestamp": 1_700_000_000_123i64,
+        "module": "linear",
+        "data": {
+            "type": "snapshot",
+            "instrument_id": "BTC-USDT-PERPETUAL",
+            "sequence": 7,
+            "prev_sequence": 6,
+            "timestamp": 1_700_000_000_120i64,
+            "bids": side(19999.5, -0.5),
+            "asks": side(20000.0, 0.5),
+        },
+    })
+    .to_string()
+}
+
+fn ticker_message(instrument_id: &str) -> String {
+    let mut data = serde_json::json!({
+        "instrument_id": instrument_id,
+        "time": 1_700_000_000_120i64,
+        "best_bid": "19999.50000000",
+        "best_ask": "20000.00000000",
+        "best_bid_qty": "1.25000000",
+        "best_ask_qty": "0.75000000",
+        "last_price": "19999.50000000",
+        "mark_price": "19999.80000000",
+        "index_price": "20000.10000000",
+        "volume24h": "12345.00000000",
+        "funding_rate": "0.00010000",
+    });
+    let option = instrument_id.ends_with("-C") || instrument_id.ends_with("-P");
+    if option {
+        for (key, value) in [
+            ("mark_iv", "55.1"),
+            ("bid_iv", "54.8"),
+            ("ask_iv", "55.4"),
+            ("underlying_price", "20000.10000000"),
+            ("delta", "0.51"),
+            ("gamma", "0.00012"),
+            ("vega", "12.3"),
+            ("theta", "-45.6"),
+        ] {
+            data[key] = Value::String(value.to_string());
+        }
+    }
+    serde_json::json!({
+        "channel": "ticker",
+        "timestamp": 1_700_000_000_123i64,
+        "module": if option { "option" } else { "linear" },
+        "data": data,
+    })
+    .to_string()
+}
+
+fn tickers(c: &mut Criterion) {
+    for (name, instrument_id) in [
+        ("ticker_linear", "BTC-USDT-PERPETUAL"),
+        ("ticker_option", "BTC-27DEC24-60000-C"),
+    ] {
+        let text = ticker_message(instrument_id);
+        let mut group = c.benchmark_group(name);
+        group.throughput(Throughput::Bytes(text.len() as u64));
+        group.bench_function("value", |b| {
+            b.iter(|| {
+                let value: Value = serde_json::from_str(black_box(&text)).unwrap();
+                WsEvent::parse(&value)
+            })
+        });
+        group.bench_function("frame", |b| {
+            b.iter(|| WsFrame::parse(black_box(&text)).and_then(|f| WsEvent::from_frame(&f)))
+        });
+        group.finish();
+    }
+}
+
+fn decoding(c: &mut Criterion) {
+    let text = depth_message(200);
+    let mut gz = GzEncoder::new(vec![], Compression::default());
+    gz.write_all(text.as_bytes()).unwrap();
+    let gzipped = gz.finish().unwrap();
+
+    let mut group = c.benchmark_group("depth_200_levels");
+    group.throughput(Throughput::Bytes(text.len() as u64));
+    group.bench_function("value", |b| {
+        b.iter(|| {
+            let value: Value = serde_json::from_str(black_box(&text)).unwrap();
+            WsEvent::parse(&value)
+        })
+    });
+    group.bench_function("frame", |b| {
+        b.iter(|| WsFrame::parse(black_box(&text)).and_then(|f| WsEvent::from_frame(&f)))
+    });
+    let mut decoder = FrameDecoder::new();
+    group.bench_function("frame_gzip", |b| {
+        b.iter(|| {
+            let text = decoder.text(black_box(&gzipped)).unwrap();
+            WsFrame::parse(text).and_then(|f| WsEvent::from_frame(&f))
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, decoding, tickers);
+criterion_main!(benches);
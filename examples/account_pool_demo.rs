@@ -0,0 +1,41 @@
+use bitsdk_rust::account_pool::AccountPool;
+use bitsdk_rust::models::Market;
+use std::env;
+
+// cargo run --example account_pool_demo -- examples/accounts.toml [--cancel-all]
+#[tokio::main]
+async fn main() {
+    let path = env::args()
+        .nth(1)
+        .unwrap_or("examples/accounts.toml".to_string());
+    let cancel_all = env::args().any(|a| a == "--cancel-all");
+
+    let pool = AccountPool::load(&path).unwrap();
+
+    for (name, rsp) in pool.um_accounts().await {
+        match rsp {
+            Ok(acct) => println!("{}: margin balance {}", name, acct.total_margin_balance),
+            Err(e) => println!("{}: {}", name, e),
+        }
+    }
+
+    for (name, rsp) in pool.positions("USDT").await {
+        match rsp {
+            Ok(positions) => {
+                for p in positions {
+                    println!(
+                        "{}: {} qty={} pnl={}",
+                        name, p.instrument_id, p.qty, p.position_pnl
+                    )
+                }
+            }
+            Err(e) => println!("{}: {}", name, e),
+        }
+    }
+
+    if cancel_all {
+        for (name, rsp) in pool.cancel_all(Market::Linear, "USDT").await {
+            println!("{}: cancel all {:?}", name, rsp);
+        }
+    }
+}
@@ -0,0 +1,26 @@
+use bitsdk_rust::account_config::AccountConfig;
+use std::env;
+
+// Include the sibling file
+#[path = "common/mod.rs"]
+mod common;
+
+// cargo run --example account_sync_demo -- examples/account.toml [--apply]
+#[tokio::main]
+async fn main() {
+    let bit_cli = common::build_rest_client();
+
+    let path = env::args()
+        .nth(1)
+        .unwrap_or("examples/account.toml".to_string());
+    let apply = env::args().any(|a| a == "--apply");
+
+    let config = AccountConfig::load(&path).unwrap();
+    let plan = config.plan(&bit_cli).await.unwrap();
+    print!("{}", plan);
+
+    if apply && !plan.is_empty() {
+        plan.apply(&bit_cli).await.unwrap();
+        println!("applied {} changes", plan.changes.len());
+    }
+}
@@ -0,0 +1,51 @@
+use bitsdk_rust::algo::{self, AlgoConfig, AlgoKind, ParentOrder};
+use bitsdk_rust::bit_ws_client::{BitWsClient, WsSubscription};
+use bitsdk_rust::matching::FeeSchedule;
+use bitsdk_rust::models::{Market, Side};
+use bitsdk_rust::paper::PaperExchange;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+const INSTRUMENT: &str = "BTC-USDT-PERPETUAL";
+
+// TWAP on the paper exchange, pass `Arc::new(common::build_rest_client())` to run it live
+#[tokio::main]
+async fn main() {
+    let paper = Arc::new(PaperExchange::new(
+        FeeSchedule::default(),
+        HashMap::from([("USDT".to_string(), 100000.0)]),
+    ));
+    let _feed = paper.connect("wss://ws.bit.com", &[INSTRUMENT]);
+    let ticker = algo::ticker_feed(
+        BitWsClient::new("wss://ws.bit.com")
+            .subscribe(WsSubscription::public(&["ticker"], &[INSTRUMENT]))
+            .spawn(),
+        INSTRUMENT,
+    );
+
+    // let the book arrive
+    tokio::time::sleep(Duration::from_secs(3)).await;
+
+    let parent =
+        ParentOrder::new(Market::Linear, INSTRUMENT, Side::Buy, 0.6).with_steps(0.1, 0.01, 0.01);
+    let kind = AlgoKind::Twap {
+        duration: Duration::from_secs(60),
+        slices: 6,
+    };
+    let handle = algo::spawn(paper, parent, kind, ticker, AlgoConfig::default());
+
+    let mut events = handle.subscribe();
+    tokio::spawn(async move {
+        while let Ok(event) = events.recv().await {
+            println!("Algo event: {:?}", event);
+        }
+    });
+
+    tokio::time::sleep(Duration::from_secs(20)).await;
+    handle.pause();
+    tokio::time::sleep(Duration::from_secs(5)).await;
+    handle.resume();
+
+    println!("Report: {:?}", handle.join().await.unwrap());
+}
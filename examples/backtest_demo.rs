@@ -0,0 +1,50 @@
+use bitsdk_rust::backtest::{BacktestConfig, Backtester, Strategy, StrategyContext};
+use bitsdk_rust::bit_ws_client::WsEvent;
+use bitsdk_rust::models::{CancelRequest, Market, OrderRequest, Side};
+use bitsdk_rust::record;
+use std::env;
+
+const INSTRUMENT: &str = "BTC-USDT-PERPETUAL";
+
+/// Quotes one lot on each side of the mid and requotes every 10 seconds.
+struct SimpleMaker {
+    last_quote_ms: i64,
+}
+
+impl Strategy for SimpleMaker {
+    fn on_event(&mut self, ctx: &mut StrategyContext, event: &WsEvent) {
+        if !matches!(event, WsEvent::Depth(_)) || ctx.now_ms() - self.last_quote_ms < 10_000 {
+            return;
+        }
+        let Some(mid) = ctx.book(INSTRUMENT).and_then(|b| b.mid()) else {
+            return;
+        };
+        self.last_quote_ms = ctx.now_ms();
+        ctx.cancel_order(Market::Linear, CancelRequest::all("USDT"));
+        ctx.new_order(
+            Market::Linear,
+            OrderRequest::limit(INSTRUMENT, Side::Buy, 0.01, (mid * 0.999).round()).post_only(),
+        );
+        ctx.new_order(
+            Market::Linear,
+            OrderRequest::limit(INSTRUMENT, Side::Sell, 0.01, (mid * 1.001).round()).post_only(),
+        );
+    }
+}
+
+// cargo run --example backtest_demo -- recording.jsonl [more.jsonl ...]
+fn main() {
+    let recordings = env::args()
+        .skip(1)
+        .map(|path| record::read_jsonl(path).unwrap())
+        .collect();
+    let frames = record::merge(recordings);
+
+    let backtester = Backtester::new(BacktestConfig::default());
+    let report = backtester.run(&mut SimpleMaker { last_quote_ms: 0 }, frames);
+
+    println!("{:#?}", report.stats);
+    for (ts, equity) in &report.equity_curve {
+        println!("{},{}", ts, equity);
+    }
+}
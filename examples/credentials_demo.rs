@@ -0,0 +1,37 @@
+use bitsdk_rust::bit_rest_client::BitRestClient;
+use bitsdk_rust::credentials::{CredentialProvider, EnvProvider, KeyFileProvider, SecretString};
+use std::env;
+use std::sync::Arc;
+use std::time::Duration;
+
+// Encrypt BITCOM_AK/BITCOM_SK into a key file, then run a client off that file and pick up
+// key changes every 10s:
+//   BITCOM_KEYFILE_PASSPHRASE=... cargo run --example credentials_demo -- bitcom.key --write
+//   BITCOM_KEYFILE_PASSPHRASE=... cargo run --example credentials_demo -- bitcom.key
+#[tokio::main]
+async fn main() {
+    let api_host = env::var("BITCOM_REST_HOST").unwrap();
+    let path = env::args().nth(1).unwrap_or("bitcom.key".to_string());
+    let passphrase = SecretString::new(env::var("BITCOM_KEYFILE_PASSPHRASE").unwrap());
+
+    if env::args().any(|a| a == "--write") {
+        let credentials = EnvProvider::default().load().unwrap();
+        KeyFileProvider::write(&path, &credentials, &passphrase).unwrap();
+        println!("wrote {:?} to {}", credentials, path);
+        return;
+    }
+
+    let provider: Arc<dyn CredentialProvider> = Arc::new(KeyFileProvider::new(&path, passphrase));
+    let client = Arc::new(BitRestClient::from_provider(provider.as_ref(), &api_host).unwrap());
+    client.spawn_credential_refresh(provider, Duration::from_secs(10));
+
+    loop {
+        let rsp = client.get_um_account(&mut serde_json::json!({})).await;
+        println!(
+            "access-key:{} account: {:?}",
+            client.access_key(),
+            rsp.map(|r| r["code"].clone())
+        );
+        tokio::time::sleep(Duration::from_secs(5)).await;
+    }
+}
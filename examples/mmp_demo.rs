@@ -0,0 +1,49 @@
+use bitsdk_rust::mmp::{self, MmpConfigFile, MmpSupervisor, ResetPolicy};
+use bitsdk_rust::models::Market;
+use std::env;
+use std::sync::Arc;
+use std::time::Duration;
+
+// Include the sibling file
+#[path = "common/mod.rs"]
+mod common;
+
+#[tokio::main]
+async fn main() {
+    let bit_cli = Arc::new(common::build_rest_client());
+
+    // optional: cargo run --example mmp_demo -- examples/mmp.toml
+    if let Some(path) = env::args().nth(1) {
+        let file = MmpConfigFile::load(&path).unwrap();
+        for market in [Market::Spot, Market::Linear] {
+            let changes = mmp::plan_mmp_configs(&bit_cli, market, file.configs(market))
+                .await
+                .unwrap();
+            for change in &changes {
+                println!("{:?} {:?} -> {:?}", market, change.current, change.desired);
+            }
+            mmp::apply_mmp_configs(&bit_cli, market, &changes)
+                .await
+                .unwrap();
+        }
+    }
+
+    let supervisor = Arc::new(MmpSupervisor::new(
+        bit_cli,
+        Market::Linear,
+        vec!["BTC-USDT".to_string()],
+        ResetPolicy::CoolDown {
+            cool_down: Duration::from_secs(5),
+            max_resets_per_hour: 3,
+        },
+    ));
+
+    let mut events = supervisor.subscribe();
+    tokio::spawn(async move {
+        while let Ok(event) = events.recv().await {
+            println!("MMP event: {:?}", event);
+        }
+    });
+
+    supervisor.run_polling(Duration::from_secs(1)).await;
+}
@@ -0,0 +1,49 @@
+use bitsdk_rust::matching::FeeSchedule;
+use bitsdk_rust::models::{CancelRequest, Market, OrderRequest, Side};
+use bitsdk_rust::paper::PaperExchange;
+use bitsdk_rust::trading::TradingApi;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+// Same code runs live by passing `common::build_rest_client()` instead of the paper exchange
+async fn run_strategy<T: TradingApi>(api: &T) {
+    let order = api
+        .new_order(
+            Market::Linear,
+            &OrderRequest::limit("BTC-USDT-PERPETUAL", Side::Buy, 0.1, 200000.0),
+        )
+        .await
+        .unwrap();
+    println!("New order: {:?}", order);
+
+    tokio::time::sleep(Duration::from_secs(2)).await;
+    println!("Positions: {:?}", api.positions("USDT").await.unwrap());
+    println!("UM account: {:?}", api.um_account().await.unwrap());
+
+    let cancelled = api
+        .cancel_order(Market::Linear, &CancelRequest::all("USDT"))
+        .await
+        .unwrap();
+    println!("Cancelled: {}", cancelled);
+}
+
+#[tokio::main]
+async fn main() {
+    let paper = Arc::new(PaperExchange::new(
+        FeeSchedule::default(),
+        HashMap::from([("USDT".to_string(), 100000.0)]),
+    ));
+    let _feed = paper.connect("wss://ws.bit.com", &["BTC-USDT-PERPETUAL"]);
+
+    let mut events = paper.subscribe();
+    tokio::spawn(async move {
+        while let Ok(event) = events.recv().await {
+            println!("Private event: {:?}", event);
+        }
+    });
+
+    // let the book arrive
+    tokio::time::sleep(Duration::from_secs(3)).await;
+    run_strategy(paper.as_ref()).await;
+}
@@ -1,3 +1,5 @@
+use bitsdk_rust::bit_ws_client::WsEvent;
+use bitsdk_rust::ws_frame::{FrameDecoder, WsFrame};
 use futures_util::{SinkExt, StreamExt};
 use tokio_tungstenite::{connect_async, tungstenite::Message};
 
@@ -35,8 +37,21 @@ async fn main() {
 
     write.send(Message::Text(json.into())).await.unwrap();
 
-    while let Some(Ok(Message::Text(text))) = read.next().await {
-        println!("Received: {}", text);
+    // text or binary frames, binary ones possibly compressed
+    let mut decoder = FrameDecoder::new();
+    while let Some(Ok(msg)) = read.next().await {
+        let text = match &msg {
+            Message::Text(text) => text.as_str(),
+            Message::Binary(payload) => match decoder.text(payload) {
+                Some(text) => text,
+                None => continue,
+            },
+            _ => continue,
+        };
+        match WsFrame::parse(text).and_then(|frame| WsEvent::from_frame(&frame)) {
+            Some(event) => println!("Received: {:?}", event),
+            None => println!("Received: {}", text),
+        }
     }
 }
 
@@ -1,7 +1,7 @@
 use std::thread;
 use std::time::Duration;
 // Include the sibling file
-#[path = "common.rs"]
+#[path = "common/mod.rs"]
 mod common;
 
 #[tokio::main]
@@ -46,7 +46,7 @@ async fn main() {
         ]
     });
 
-    println!("New batch req: {}", batch_new_req.to_string());
+    println!("New batch req: {}", batch_new_req);
     match bit_cli.linear_batch_new_orders( &mut batch_new_req).await {
         Ok(response) => println!("Response: {:?}", response),
         Err(e) => eprintln!("Error: {}", e),
@@ -64,7 +64,7 @@ async fn main() {
 
     // cancel orders
     thread::sleep(Duration::from_secs(1));
-    println!("Cancel orders: {}", batch_new_req.to_string());
+    println!("Cancel orders: {}", batch_new_req);
     match bit_cli.linear_cancel_order( &mut serde_json::json!({
         "currency": "USDT"
     })).await {
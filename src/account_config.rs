@@ -0,0 +1,362 @@
+// Account configuration as code: declare the desired spot/linear/option/UM settings in a file,
+// diff them against the live account with plan(), then push the differences with apply().
+
+use crate::bit_rest_client::BitRestClient;
+use crate::error::{BitError, BitResult, parse_data};
+use crate::mmp::{self, MmpConfig, MmpConfigChange};
+use crate::models::Market;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::Path;
+
+/// Desired account settings. Every field is optional, anything left out is not managed.
+///
+/// ```toml
+/// um_account_mode = "um"
+///
+/// [spot]
+/// cod = true
+///
+/// [linear]
+/// cod = true
+/// position_mode = "one_way"
+/// leverage = { "BTC-USDT" = 10.0, "ETH-USDT" = 5.0 }
+///
+/// [[linear.mmp]]
+/// pair = "BTC-USDT"
+/// window_ms = 5000
+/// frozen_ms = 1000
+/// qty_limit = 10.0
+/// delta_limit = 10.0
+///
+/// [[option.mmp]]
+/// pair = "BTC"
+/// window_ms = 5000
+/// frozen_ms = 1000
+/// qty_limit = 100.0
+/// delta_limit = 5.0
+/// ```
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct AccountConfig {
+    #[serde(default)]
+    pub um_account_mode: Option<String>,
+    #[serde(default)]
+    pub spot: Option<SpotAccountConfig>,
+    #[serde(default)]
+    pub linear: Option<LinearAccountConfig>,
+    #[serde(default)]
+    pub option: Option<OptionAccountConfig>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SpotAccountConfig {
+    #[serde(default)]
+    pub cod: Option<bool>,
+    #[serde(default)]
+    pub mmp: Vec<MmpConfig>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct LinearAccountConfig {
+    #[serde(default)]
+    pub cod: Option<bool>,
+    #[serde(default)]
+    pub position_mode: Option<String>,
+    /// Leverage ratio keyed by pair, e.g. "BTC-USDT". The exchange sets leverage per pair,
+    /// so instrument ids such as "BTC-USDT-PERPETUAL" are rejected.
+    #[serde(default)]
+    pub leverage: BTreeMap<String, f64>,
+    #[serde(default)]
+    pub mmp: Vec<MmpConfig>,
+}
+
+/// Option MMP is per currency, `pair = "BTC"`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct OptionAccountConfig {
+    #[serde(default)]
+    pub mmp: Vec<MmpConfig>,
+}
+
+#[derive(Debug, Clone)]
+pub enum AccountChange {
+    UmAccountMode {
+        current: Option<String>,
+        desired: String,
+    },
+    Cod {
+        market: Market,
+        current: Option<bool>,
+        desired: bool,
+    },
+    PositionMode {
+        current: Option<String>,
+        desired: String,
+    },
+    Leverage {
+        pair: String,
+        current: Option<f64>,
+        desired: f64,
+    },
+    Mmp {
+        market: Market,
+        change: MmpConfigChange,
+    },
+}
+
+impl fmt::Display for AccountChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AccountChange::UmAccountMode { current, desired } => {
+                write!(f, "um account mode: {:?} -> {}", current, desired)
+            }
+            AccountChange::Cod {
+                market,
+                current,
+                desired,
+            } => write!(f, "{:?} cod: {:?} -> {}", market, current, desired),
+            AccountChange::PositionMode { current, desired } => {
+                write!(f, "linear position mode: {:?} -> {}", current, desired)
+            }
+            AccountChange::Leverage {
+                pair,
+                current,
+                desired,
+            } => write!(f, "linear leverage {}: {:?} -> {}", pair, current, desired),
+            AccountChange::Mmp { market, change } => write!(
+                f,
+                "{:?} mmp {}: {:?} -> {:?}",
+                market, change.desired.pair, change.current, change.desired
+            ),
+        }
+    }
+}
+
+/// The ordered list of changes needed to bring the account in line with an `AccountConfig`.
+#[derive(Debug, Clone, Default)]
+pub struct AccountPlan {
+    pub changes: Vec<AccountChange>,
+}
+
+impl AccountPlan {
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    /// Push every change. Stops at the first failure, the remaining changes are not attempted.
+    pub async fn apply(&self, client: &BitRestClient) -> BitResult<()> {
+        for change in &self.changes {
+            apply_change(client, change).await?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for AccountPlan {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.changes.is_empty() {
+            return writeln!(f, "no changes");
+        }
+        for change in &self.changes {
+            writeln!(f, "~ {}", change)?;
+        }
+        Ok(())
+    }
+}
+
+impl AccountConfig {
+    /// Load from a `.toml`, `.yaml` or `.yml` file.
+    pub fn load(path: impl AsRef<Path>) -> BitResult<Self> {
+        let path = path.as_ref();
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| BitError::Config(format!("{}: {}", path.display(), e)))?;
+        let config: AccountConfig = match path.extension().and_then(|e| e.to_str()) {
+            Some("toml") => toml::from_str(&text).map_err(|e| BitError::Config(e.to_string()))?,
+            Some("yaml") | Some("yml") => {
+                serde_yaml::from_str(&text).map_err(|e| BitError::Config(e.to_string()))?
+            }
+            _ => {
+                return Err(BitError::Config(format!(
+                    "{}: unsupported config format",
+                    path.display()
+                )));
+            }
+        };
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Check what can be checked without the exchange: leverage keys are pairs and ratios
+    /// are positive.
+    pub fn validate(&self) -> BitResult<()> {
+        let Some(linear) = &self.linear else {
+            return Ok(());
+        };
+        for (pair, ratio) in &linear.leverage {
+            let parts: Vec<&str> = pair.split('-').collect();
+            if parts.len() != 2 || parts.iter().any(|p| p.is_empty()) {
+                return Err(BitError::Config(format!(
+                    "linear leverage is set per pair such as \"BTC-USDT\", not \"{}\"",
+                    pair
+                )));
+            }
+            if !(ratio.is_finite() && *ratio > 0.0) {
+                return Err(BitError::Config(format!(
+                    "linear leverage of {} must be positive, got {}",
+                    pair, ratio
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// Diff the declared settings against the live account. Read-only.
+    pub async fn plan(&self, client: &BitRestClient) -> BitResult<AccountPlan> {
+        self.validate()?;
+        let mut changes = Vec::new();
+
+        if let Some(desired) = &self.um_account_mode {
+            let live: Value = parse_data(client.get_um_account_mode().await?)?;
+            let current = live["account_mode"].as_str().map(str::to_string);
+            if current.as_deref() != Some(desired.as_str()) {
+                changes.push(AccountChange::UmAccountMode {
+                    current,
+                    desired: desired.clone(),
+                });
+            }
+        }
+
+        if let Some(spot) = &self.spot {
+            if let Some(desired) = spot.cod {
+                let live: Value = parse_data(
+                    client
+                        .spot_get_account_configs(&mut serde_json::json!({}))
+                        .await?,
+                )?;
+                let current = live["cod"].as_bool();
+                if current != Some(desired) {
+                    changes.push(AccountChange::Cod {
+                        market: Market::Spot,
+                        current,
+                        desired,
+                    });
+                }
+            }
+            for change in mmp::plan_mmp_configs(client, Market::Spot, &spot.mmp).await? {
+                changes.push(AccountChange::Mmp {
+                    market: Market::Spot,
+                    change,
+                });
+            }
+        }
+
+        if let Some(linear) = &self.linear {
+            if linear.cod.is_some() || linear.position_mode.is_some() {
+                let live: Value = parse_data(
+                    client
+                        .linear_get_account_configs(&mut serde_json::json!({}))
+                        .await?,
+                )?;
+                if let Some(desired) = linear.cod {
+                    let current = live["cod"].as_bool();
+                    if current != Some(desired) {
+                        changes.push(AccountChange::Cod {
+                            market: Market::Linear,
+                            current,
+                            desired,
+                        });
+                    }
+                }
+                if let Some(desired) = &linear.position_mode {
+                    let current = live["position_mode"].as_str().map(str::to_string);
+                    if current.as_deref() != Some(desired.as_str()) {
+                        changes.push(AccountChange::PositionMode {
+                            current,
+                            desired: desired.clone(),
+                        });
+                    }
+                }
+            }
+            for (pair, desired) in &linear.leverage {
+                let live: Value = parse_data(
+                    client
+                        .linear_get_leverage_ratio(&mut serde_json::json!({ "pair": pair }))
+                        .await?,
+                )?;
+                let current = match &live["leverage_ratio"] {
+                    Value::String(s) => s.parse::<f64>().ok(),
+                    v => v.as_f64(),
+                };
+                if current.is_none_or(|c| (c - desired).abs() > 1e-9) {
+                    changes.push(AccountChange::Leverage {
+                        pair: pair.clone(),
+                        current,
+                        desired: *desired,
+                    });
+                }
+            }
+            for change in mmp::plan_mmp_configs(client, Market::Linear, &linear.mmp).await? {
+                changes.push(AccountChange::Mmp {
+                    market: Market::Linear,
+                    change,
+                });
+            }
+        }
+
+        if let Some(option) = &self.option {
+            for change in mmp::plan_mmp_configs(client, Market::Option, &option.mmp).await? {
+                changes.push(AccountChange::Mmp {
+                    market: Market::Option,
+                    change,
+                });
+            }
+        }
+
+        Ok(AccountPlan { changes })
+    }
+
+    /// `plan()` followed by `AccountPlan::apply()`. Returns the plan that was applied.
+    pub async fn apply(&self, client: &BitRestClient) -> BitResult<AccountPlan> {
+        let plan = self.plan(client).await?;
+        plan.apply(client).await?;
+        Ok(plan)
+    }
+}
+
+async fn apply_change(client: &BitRestClient, change: &AccountChange) -> BitResult<()> {
+    let rsp = match change {
+        AccountChange::UmAccountMode { desired, .. } => {
+            client
+                .update_um_account_mode(&mut serde_json::json!({ "account_mode": desired }))
+                .await?
+        }
+        AccountChange::Cod {
+            market, desired, ..
+        } => {
+            let mut params = serde_json::json!({ "cod": desired });
+            match market {
+                Market::Spot => client.spot_enable_cod(&mut params).await?,
+                Market::Linear => client.linear_enable_cod(&mut params).await?,
+                Market::Option => client.option_enable_cod(&mut params).await?,
+            }
+        }
+        AccountChange::PositionMode { desired, .. } => {
+            client
+                .linear_update_position_mode(&mut serde_json::json!({ "position_mode": desired }))
+                .await?
+        }
+        AccountChange::Leverage { pair, desired, .. } => {
+            client
+                .linear_update_leverage_ratio(&mut serde_json::json!({
+                    "pair": pair,
+                    "leverage_ratio": desired.to_string(),
+                }))
+                .await?
+        }
+        AccountChange::Mmp { market, change } => {
+            return mmp::update_mmp_config(client, *market, &change.desired).await;
+        }
+    };
+    parse_data::<Value>(rsp).map(|_| ())
+}
@@ -0,0 +1,165 @@
+// Many named accounts (e.g. sub-accounts) behind one connection pool, with fan-out helpers
+// that run the same call for every account concurrently.
+
+use crate::bit_rest_client::BitRestClient;
+use crate::credentials::{Credentials, SecretString};
+use crate::error::{BitError, BitResult};
+use crate::models::{CancelRequest, Market, Order, Position, UmAccount};
+use crate::rate_limit::{RateLimitConfig, RateLimiter};
+use crate::trading::TradingApi;
+use futures_util::future::join_all;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::path::Path;
+use std::sync::Arc;
+
+/// ```toml
+/// rest_host = "https://api.bit.com"
+/// # budget shared by every account in the pool, e.g. a per-IP limit
+/// shared_rate_limit = { rate_per_sec = 50.0, burst = 50.0 }
+///
+/// [[accounts]]
+/// name = "sub1"
+/// access_key = "..."
+/// secret_key = "..."
+/// rate_limit = { rate_per_sec = 10.0, burst = 10.0 }
+/// ```
+#[derive(Debug, Clone, Deserialize)]
+pub struct AccountPoolConfig {
+    pub rest_host: String,
+    #[serde(default)]
+    pub shared_rate_limit: Option<RateLimitConfig>,
+    pub accounts: Vec<AccountEntry>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct AccountEntry {
+    pub name: String,
+    pub access_key: String,
+    pub secret_key: SecretString,
+    #[serde(default)]
+    pub rate_limit: Option<RateLimitConfig>,
+}
+
+// the message of a config error without its "config error: " prefix, to add context
+fn config_message(e: BitError) -> String {
+    match e {
+        BitError::Config(msg) => msg,
+        e => e.to_string(),
+    }
+}
+
+pub struct AccountPool {
+    clients: BTreeMap<String, Arc<BitRestClient>>,
+}
+
+impl AccountPool {
+    pub fn load(path: impl AsRef<Path>) -> BitResult<Self> {
+        let path = path.as_ref();
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| BitError::Config(format!("{}: {}", path.display(), e)))?;
+        let config: AccountPoolConfig =
+            toml::from_str(&text).map_err(|e| BitError::Config(e.to_string()))?;
+        Self::from_config(&config)
+    }
+
+    pub fn from_config(config: &AccountPoolConfig) -> BitResult<Self> {
+        let http = reqwest::Client::new();
+        let shared = match config.shared_rate_limit {
+            Some(limit) => Some(Arc::new(RateLimiter::new(limit).map_err(|e| {
+                BitError::Config(format!("shared_rate_limit: {}", config_message(e)))
+            })?)),
+            None => None,
+        };
+
+        let mut clients = BTreeMap::new();
+        for entry in &config.accounts {
+            let credentials = Credentials::new(&entry.access_key, entry.secret_key.clone());
+            let mut client =
+                BitRestClient::try_with_client(credentials, &config.rest_host, http.clone())
+                    .map_err(|e| {
+                        BitError::Config(format!("{}: {}", entry.name, config_message(e)))
+                    })?;
+            if let Some(shared) = &shared {
+                client = client.add_rate_limiter(shared.clone());
+            }
+            if let Some(limit) = entry.rate_limit {
+                let limiter = RateLimiter::new(limit).map_err(|e| {
+                    BitError::Config(format!("{}: rate_limit: {}", entry.name, config_message(e)))
+                })?;
+                client = client.add_rate_limiter(Arc::new(limiter));
+            }
+            if clients
+                .insert(entry.name.clone(), Arc::new(client))
+                .is_some()
+            {
+                return Err(BitError::Config(format!(
+                    "duplicate account {}",
+                    entry.name
+                )));
+            }
+        }
+        Ok(Self { clients })
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Arc<BitRestClient>> {
+        self.clients.get(name)
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.clients.keys().map(String::as_str)
+    }
+
+    pub fn len(&self) -> usize {
+        self.clients.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.clients.is_empty()
+    }
+
+    /// Run `f` for every account concurrently. Results are keyed by account name; one
+    /// account failing does not affect the others.
+    pub async fn for_each<T, F, Fut>(&self, f: F) -> BTreeMap<String, BitResult<T>>
+    where
+        F: Fn(Arc<BitRestClient>) -> Fut,
+        Fut: Future<Output = BitResult<T>>,
+    {
+        let futures = self.clients.iter().map(|(name, client)| {
+            let fut = f(client.clone());
+            async move { (name.clone(), fut.await) }
+        });
+        join_all(futures).await.into_iter().collect()
+    }
+
+    pub async fn um_accounts(&self) -> BTreeMap<String, BitResult<UmAccount>> {
+        self.for_each(|c| async move { c.um_account().await }).await
+    }
+
+    pub async fn positions(&self, currency: &str) -> BTreeMap<String, BitResult<Vec<Position>>> {
+        self.for_each(|c| async move { c.positions(currency).await })
+            .await
+    }
+
+    pub async fn open_orders(
+        &self,
+        market: Market,
+        currency: &str,
+    ) -> BTreeMap<String, BitResult<Vec<Order>>> {
+        self.for_each(|c| async move { c.open_orders(market, currency, None).await })
+            .await
+    }
+
+    /// Cancel every order in `currency` on every account. Returns the count per account.
+    pub async fn cancel_all(
+        &self,
+        market: Market,
+        currency: &str,
+    ) -> BTreeMap<String, BitResult<i64>> {
+        let req = CancelRequest::all(currency);
+        let req = &req;
+        self.for_each(|c| async move { c.cancel_order(market, req).await })
+            .await
+    }
+}
@@ -0,0 +1,694 @@
+// Execution algorithms: work a parent order through a series of child limit orders.
+//
+// TWAP spreads the quantity evenly over a time window, VWAP follows a recorded intraday volume
+// profile and an iceberg shows a randomized slice of the remaining quantity at a time. Every
+// poll interval the running algo compares what should have been filled by now with what has
+// been, and places, reprices or cancels its single working child accordingly. Children join
+// the touch on their own side and are capped at the parent's limit price; after the schedule
+// ends the remainder crosses the spread. Children are rounded to the tick and lot size, and
+// a remainder below the min size is dropped.
+//
+// Runs against any `TradingApi`, so the same algo can be tried on the paper exchange first.
+
+use crate::bit_ws_client::WsEvent;
+use crate::error::{BitError, BitResult};
+use crate::models::{
+    AmendRequest, CancelRequest, Market, OrderRequest, Side, Ticker, settle_currency,
+};
+use crate::record::RecordedFrame;
+use crate::trading::TradingApi;
+use crate::util::now_ms;
+use rand::rngs::SmallRng;
+use rand::{Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{broadcast, mpsc, watch};
+use tokio::task::JoinHandle;
+
+const DAY_MS: i64 = 24 * 3600 * 1000;
+const EPS: f64 = 1e-12;
+/// Consecutive failed exchange calls before the algo gives up.
+const MAX_ERRORS: u32 = 5;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParentOrder {
+    pub market: Market,
+    pub instrument_id: String,
+    pub side: Side,
+    pub qty: f64,
+    /// Children never buy above / sell below this price.
+    pub limit_price: Option<f64>,
+    /// Set on every child order.
+    pub label: Option<String>,
+    /// Tick and lot size of the instrument, children are rounded to them. 0 leaves them as is.
+    pub price_step: f64,
+    pub size_step: f64,
+    /// Smallest child the exchange accepts, a remainder below it is left unfilled.
+    pub min_size: f64,
+}
+
+impl ParentOrder {
+    pub fn new(market: Market, instrument_id: &str, side: Side, qty: f64) -> Self {
+        Self {
+            market,
+            instrument_id: instrument_id.to_string(),
+            side,
+            qty,
+            limit_price: None,
+            label: None,
+            price_step: 0.0,
+            size_step: 0.0,
+            min_size: 0.0,
+        }
+    }
+
+    pub fn with_limit_price(mut self, price: f64) -> Self {
+        self.limit_price = Some(price);
+        self
+    }
+
+    pub fn with_label(mut self, label: &str) -> Self {
+        self.label = Some(label.to_string());
+        self
+    }
+
+    pub fn with_steps(mut self, price_step: f64, size_step: f64, min_size: f64) -> Self {
+        self.price_step = price_step;
+        self.size_step = size_step;
+        self.min_size = min_size;
+        self
+    }
+}
+
+/// `value` in whole `step`s, rounded down or up. A step of 0 leaves it as is.
+fn to_step(value: f64, step: f64, up: bool) -> f64 {
+    if step <= 0.0 {
+        return value;
+    }
+    // absorb float noise such as 0.3 / 0.1 = 2.9999999999999996
+    let steps = value / step;
+    let steps = if up {
+        (steps - 1e-9).ceil()
+    } else {
+        (steps + 1e-9).floor()
+    };
+    (steps * step * 1e9).round() / 1e9
+}
+
+/// Share of a day's volume per equal time-of-day bucket (UTC), summing to 1.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct VolumeProfile {
+    pub weights: Vec<f64>,
+}
+
+impl VolumeProfile {
+    /// Flat profile, VWAP then behaves like TWAP.
+    pub fn uniform(buckets: usize) -> Self {
+        let buckets = buckets.max(1);
+        Self {
+            weights: vec![1.0 / buckets as f64; buckets],
+        }
+    }
+
+    /// Traded volume of `instrument_id` in a recording, folded onto one day.
+    pub fn from_frames(frames: &[RecordedFrame], instrument_id: &str, buckets: usize) -> Self {
+        let buckets = buckets.max(1);
+        let bucket_ms = DAY_MS / buckets as i64;
+        let mut volume = vec![0.0; buckets];
+        for frame in frames {
+            let Some(WsEvent::Trades(trades)) = frame.event() else {
+                continue;
+            };
+            for t in trades.iter().filter(|t| t.instrument_id == instrument_id) {
+                let time = if t.created_at > 0 {
+                    t.created_at
+                } else {
+                    frame.recv_ms
+                };
+                let bucket = (time.rem_euclid(DAY_MS) / bucket_ms) as usize;
+                volume[bucket.min(buckets - 1)] += t.qty;
+            }
+        }
+        let total: f64 = volume.iter().sum();
+        if total <= 0.0 {
+            return Self::uniform(buckets);
+        }
+        Self {
+            weights: volume.into_iter().map(|v| v / total).collect(),
+        }
+    }
+
+    /// Volume expected in `[from_ms, to_ms)`, as a share of a day.
+    fn volume_between(&self, from_ms: i64, to_ms: i64) -> f64 {
+        let bucket_ms = DAY_MS / self.weights.len() as i64;
+        let mut t = from_ms;
+        let mut volume = 0.0;
+        while t < to_ms {
+            let bucket = (t.rem_euclid(DAY_MS) / bucket_ms) as usize;
+            let bucket_end = t - t.rem_euclid(bucket_ms) + bucket_ms;
+            let end = bucket_end.min(to_ms);
+            let weight = self.weights.get(bucket).copied().unwrap_or(0.0);
+            volume += weight * (end - t) as f64 / bucket_ms as f64;
+            t = end;
+        }
+        volume
+    }
+
+    /// Share of the volume of `[start_ms, end_ms)` expected by `now_ms`.
+    fn fraction(&self, start_ms: i64, end_ms: i64, now_ms: i64) -> f64 {
+        let total = self.volume_between(start_ms, end_ms);
+        if total <= 0.0 {
+            return linear_fraction(start_ms, end_ms, now_ms);
+        }
+        (self.volume_between(start_ms, now_ms.min(end_ms)) / total).clamp(0.0, 1.0)
+    }
+}
+
+fn linear_fraction(start_ms: i64, end_ms: i64, now_ms: i64) -> f64 {
+    if end_ms <= start_ms {
+        return 1.0;
+    }
+    ((now_ms - start_ms) as f64 / (end_ms - start_ms) as f64).clamp(0.0, 1.0)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum AlgoKind {
+    /// `slices` equal slices over `duration`, each released at the start of its interval.
+    Twap { duration: Duration, slices: u32 },
+    /// Release quantity in proportion to the profile's volume over `duration`.
+    Vwap {
+        duration: Duration,
+        profile: VolumeProfile,
+    },
+    /// Show `display_qty`, varied by up to `variance` (0.2 = ±20%) per child, until done.
+    Iceberg { display_qty: f64, variance: f64 },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct AlgoConfig {
+    pub poll_interval: Duration,
+    /// Seed for the iceberg display size, random when unset.
+    pub seed: Option<u64>,
+}
+
+impl Default for AlgoConfig {
+    fn default() -> Self {
+        Self {
+            poll_interval: Duration::from_secs(1),
+            seed: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AlgoControl {
+    Run,
+    Pause,
+    Cancel,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum AlgoEvent {
+    ChildPlaced {
+        order_id: String,
+        qty: f64,
+        price: f64,
+    },
+    ChildAmended {
+        order_id: String,
+        price: f64,
+    },
+    ChildCancelled {
+        order_id: String,
+    },
+    Progress {
+        filled_qty: f64,
+        avg_price: f64,
+    },
+    Paused,
+    Resumed,
+    /// A failed exchange call. The algo retries on the next poll.
+    Error(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AlgoStatus {
+    Completed,
+    Cancelled,
+    Failed,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct AlgoReport {
+    pub status: AlgoStatus,
+    pub filled_qty: f64,
+    pub avg_price: f64,
+    pub child_orders: usize,
+}
+
+/// Control and observe a running algo.
+pub struct AlgoHandle {
+    control: watch::Sender<AlgoControl>,
+    events: broadcast::Sender<AlgoEvent>,
+    task: JoinHandle<AlgoReport>,
+}
+
+impl AlgoHandle {
+    /// Pull the working child and stop placing new ones until `resume`.
+    pub fn pause(&self) {
+        let _ = self.control.send(AlgoControl::Pause);
+    }
+
+    pub fn resume(&self) {
+        let _ = self.control.send(AlgoControl::Run);
+    }
+
+    /// Pull the working child and finish with `AlgoStatus::Cancelled`.
+    pub fn cancel(&self) {
+        let _ = self.control.send(AlgoControl::Cancel);
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<AlgoEvent> {
+        self.events.subscribe()
+    }
+
+    pub async fn join(self) -> BitResult<AlgoReport> {
+        self.task
+            .await
+            .map_err(|e| BitError::Config(format!("algo task: {}", e)))
+    }
+}
+
+/// Keep the latest ticker of `instrument_id` from a WebSocket event stream.
+pub fn ticker_feed(
+    mut events: mpsc::Receiver<WsEvent>,
+    instrument_id: &str,
+) -> watch::Receiver<Ticker> {
+    let (tx, rx) = watch::channel(Ticker::default());
+    let instrument_id = instrument_id.to_string();
+    tokio::spawn(async move {
+        while let Some(event) = events.recv().await {
+            let ticker = match event {
+                WsEvent::Ticker(t) => t,
+                WsEvent::OptionTicker(t) => t.ticker,
+                _ => continue,
+            };
+            if ticker.instrument_id == instrument_id && tx.send(ticker).is_err() {
+                return;
+            }
+        }
+    });
+    rx
+}
+
+/// Start working `parent` with `kind`, pricing children off `ticker`.
+pub fn spawn<T: TradingApi + 'static>(
+    api: Arc<T>,
+    parent: ParentOrder,
+    kind: AlgoKind,
+    ticker: watch::Receiver<Ticker>,
+    config: AlgoConfig,
+) -> AlgoHandle {
+    let (control, control_rx) = watch::channel(AlgoControl::Run);
+    let (events, _) = broadcast::channel(1024);
+    let rng = match config.seed {
+        Some(seed) => SmallRng::seed_from_u64(seed),
+        None => SmallRng::from_entropy(),
+    };
+    let mut algo = Algo {
+        api,
+        parent,
+        kind,
+        ticker,
+        control: control_rx,
+        events: events.clone(),
+        poll_interval: config.poll_interval,
+        rng,
+        start_ms: now_ms(),
+        child: None,
+        child_ids: HashSet::new(),
+        seen_trades: HashSet::new(),
+        filled_qty: 0.0,
+        filled_notional: 0.0,
+        errors: 0,
+    };
+    let task = tokio::spawn(async move { algo.run().await });
+    AlgoHandle {
+        control,
+        events,
+        task,
+    }
+}
+
+struct Child {
+    order_id: String,
+    price: f64,
+    /// Quantity this child is meant to fill from when it was placed.
+    qty: f64,
+    filled_at_placement: f64,
+    /// A cancel was sent and not confirmed, retried before anything else happens.
+    cancelling: bool,
+}
+
+impl Child {
+    /// Quantity still to fill, given the parent's total `filled_qty`.
+    fn left(&self, filled_qty: f64) -> f64 {
+        self.qty - (filled_qty - self.filled_at_placement)
+    }
+}
+
+struct Algo<T> {
+    api: Arc<T>,
+    parent: ParentOrder,
+    kind: AlgoKind,
+    ticker: watch::Receiver<Ticker>,
+    control: watch::Receiver<AlgoControl>,
+    events: broadcast::Sender<AlgoEvent>,
+    poll_interval: Duration,
+    rng: SmallRng,
+    start_ms: i64,
+    child: Option<Child>,
+    child_ids: HashSet<String>,
+    seen_trades: HashSet<String>,
+    filled_qty: f64,
+    filled_notional: f64,
+    errors: u32,
+}
+
+impl<T: TradingApi> Algo<T> {
+    fn emit(&self, event: AlgoEvent) {
+        // no subscribers is not an error
+        let _ = self.events.send(event);
+    }
+
+    fn report(&self, status: AlgoStatus) -> AlgoReport {
+        AlgoReport {
+            status,
+            filled_qty: self.filled_qty,
+            avg_price: self.avg_price(),
+            child_orders: self.child_ids.len(),
+        }
+    }
+
+    fn avg_price(&self) -> f64 {
+        if self.filled_qty > 0.0 {
+            self.filled_notional / self.filled_qty
+        } else {
+            0.0
+        }
+    }
+
+    fn remaining(&self) -> f64 {
+        (self.parent.qty - self.filled_qty).max(0.0)
+    }
+
+    fn end_ms(&self) -> Option<i64> {
+        match &self.kind {
+            AlgoKind::Twap { duration, .. } | AlgoKind::Vwap { duration, .. } => {
+                Some(self.start_ms + duration.as_millis() as i64)
+            }
+            AlgoKind::Iceberg { .. } => None,
+        }
+    }
+
+    /// Cumulative quantity that should be done by `now`.
+    fn target_qty(&self, now: i64) -> f64 {
+        let fraction = match &self.kind {
+            AlgoKind::Twap { slices, .. } => {
+                let end = self.end_ms().unwrap_or(now);
+                let slices = (*slices).max(1) as f64;
+                // a slice is released when its interval starts
+                (((linear_fraction(self.start_ms, end, now) * slices).floor() + 1.0) / slices)
+                    .min(1.0)
+            }
+            AlgoKind::Vwap { profile, .. } => {
+                let end = self.end_ms().unwrap_or(now);
+                profile.fraction(self.start_ms, end, now)
+            }
+            AlgoKind::Iceberg { .. } => 1.0,
+        };
+        self.parent.qty * fraction
+    }
+
+    /// Join our side of the touch, or cross once the schedule is over. Capped by the limit.
+    fn child_price(&self, now: i64) -> Option<f64> {
+        let ticker = self.ticker.borrow();
+        let late = self.end_ms().is_some_and(|end| now >= end);
+        let price = match (self.parent.side, late) {
+            (Side::Buy, false) | (Side::Sell, true) => ticker.best_bid,
+            (Side::Sell, false) | (Side::Buy, true) => ticker.best_ask,
+        };
+        if price <= 0.0 {
+            return None;
+        }
+        let price = match (self.parent.side, self.parent.limit_price) {
+            (Side::Buy, Some(limit)) => price.min(limit),
+            (Side::Sell, Some(limit)) => price.max(limit),
+            (_, None) => price,
+        };
+        // round away from the touch, so the limit still holds
+        let price = to_step(
+            price,
+            self.parent.price_step,
+            self.parent.side == Side::Sell,
+        );
+        (price > 0.0).then_some(price)
+    }
+
+    /// `qty` rounded down to the lot size, 0 when that is below the min size.
+    fn child_qty(&self, qty: f64) -> f64 {
+        let qty = to_step(qty, self.parent.size_step, false);
+        if qty < self.parent.min_size.max(EPS) {
+            0.0
+        } else {
+            qty
+        }
+    }
+
+    fn display_qty(&mut self, working: f64) -> f64 {
+        match self.kind {
+            AlgoKind::Iceberg {
+                display_qty,
+                variance,
+            } => {
+                let variance = variance.clamp(0.0, 1.0);
+                let factor = if variance > 0.0 {
+                    self.rng.gen_range(1.0 - variance..=1.0 + variance)
+                } else {
+                    1.0
+                };
+                working.min(display_qty * factor)
+            }
+            _ => working,
+        }
+    }
+
+    /// Count consecutive failures, `run` gives up after `MAX_ERRORS`.
+    fn check<R>(&mut self, result: BitResult<R>) -> Option<R> {
+        match result {
+            Ok(r) => {
+                self.errors = 0;
+                Some(r)
+            }
+            Err(e) => {
+                self.errors += 1;
+                self.emit(AlgoEvent::Error(e.to_string()));
+                None
+            }
+        }
+    }
+
+    async fn run(&mut self) -> AlgoReport {
+        let mut interval = tokio::time::interval(self.poll_interval);
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        loop {
+            interval.tick().await;
+
+            let control = *self.control.borrow_and_update();
+            match control {
+                AlgoControl::Cancel => {
+                    if !self.pull_child().await {
+                        if self.errors >= MAX_ERRORS {
+                            return self.report(AlgoStatus::Failed);
+                        }
+                        continue;
+                    }
+                    self.sync_fills().await;
+                    return self.report(AlgoStatus::Cancelled);
+                }
+                AlgoControl::Pause => {
+                    if !self.pull_child().await {
+                        if self.errors >= MAX_ERRORS {
+                            return self.report(AlgoStatus::Failed);
+                        }
+                        continue;
+                    }
+                    self.emit(AlgoEvent::Paused);
+                    let resumed = self
+                        .control
+                        .wait_for(|c| *c != AlgoControl::Pause)
+                        .await
+                        .is_ok();
+                    if !resumed {
+                        return self.report(AlgoStatus::Cancelled);
+                    }
+                    self.emit(AlgoEvent::Resumed);
+                    continue;
+                }
+                AlgoControl::Run => {}
+            }
+
+            self.sync_fills().await;
+            let left = self.remaining();
+            if self.child_qty(left) <= EPS {
+                // every child is filled, or only dust below the min size is left to pull
+                if left > EPS && !self.cancel_child().await {
+                    if self.errors >= MAX_ERRORS {
+                        return self.report(AlgoStatus::Failed);
+                    }
+                    continue;
+                }
+                self.child = None;
+                return self.report(AlgoStatus::Completed);
+            }
+            self.step().await;
+            if self.errors >= MAX_ERRORS {
+                self.cancel_child().await;
+                return self.report(AlgoStatus::Failed);
+            }
+        }
+    }
+
+    /// Place, reprice or pull the working child.
+    async fn step(&mut self) {
+        let now = now_ms();
+        let working = (self.target_qty(now) - self.filled_qty).min(self.remaining());
+        let Some(price) = self.child_price(now) else {
+            return;
+        };
+
+        if let Some(child) = &self.child {
+            let child_left = child.left(self.filled_qty);
+            if child_left <= EPS {
+                // filled, the next poll places the next child
+                self.child = None;
+            } else if child.cancelling {
+                // it may still be live, placing another child could overfill
+                self.cancel_child().await;
+            } else if working <= EPS || child_left > working + EPS {
+                // more than the schedule allows now, start over with a smaller child
+                self.cancel_child().await;
+            } else if (child.price - price).abs() > EPS {
+                let req = AmendRequest {
+                    instrument_id: self.parent.instrument_id.clone(),
+                    order_id: Some(child.order_id.clone()),
+                    price: Some(price),
+                    ..Default::default()
+                };
+                let order_id = child.order_id.clone();
+                let result = self.api.amend_order(self.parent.market, &req).await;
+                if self.check(result).is_some() {
+                    if let Some(child) = &mut self.child {
+                        child.price = price;
+                    }
+                    self.emit(AlgoEvent::ChildAmended { order_id, price });
+                }
+            }
+            return;
+        }
+
+        if working <= EPS {
+            return;
+        }
+        let display = self.display_qty(working).max(self.parent.min_size);
+        let qty = self.child_qty(display.min(working));
+        if qty <= EPS {
+            // the schedule has not released a whole lot yet
+            return;
+        }
+        let mut req = OrderRequest::limit(&self.parent.instrument_id, self.parent.side, qty, price);
+        req.label = self.parent.label.clone();
+        let result = self.api.new_order(self.parent.market, &req).await;
+        if let Some(order) = self.check(result) {
+            self.child_ids.insert(order.order_id.clone());
+            self.emit(AlgoEvent::ChildPlaced {
+                order_id: order.order_id.clone(),
+                qty,
+                price,
+            });
+            self.child = Some(Child {
+                order_id: order.order_id,
+                price,
+                qty,
+                filled_at_placement: self.filled_qty,
+                cancelling: false,
+            });
+        }
+    }
+
+    /// Catch up on fills, then pull the working child unless it is already filled.
+    async fn pull_child(&mut self) -> bool {
+        self.sync_fills().await;
+        if self
+            .child
+            .as_ref()
+            .is_some_and(|c| c.left(self.filled_qty) <= EPS)
+        {
+            self.child = None;
+            return true;
+        }
+        self.cancel_child().await
+    }
+
+    /// Pull the working child. Returns false when the cancel failed: the child stays tracked,
+    /// since it may still be live, and `step` retries the cancel instead of placing another.
+    async fn cancel_child(&mut self) -> bool {
+        let Some(child) = &mut self.child else {
+            return true;
+        };
+        child.cancelling = true;
+        let order_id = child.order_id.clone();
+        let req = CancelRequest::by_order_id(&self.parent.instrument_id, &order_id);
+        let result = self.api.cancel_order(self.parent.market, &req).await;
+        if self.check(result).is_none() {
+            return false;
+        }
+        self.child = None;
+        self.emit(AlgoEvent::ChildCancelled { order_id });
+        true
+    }
+
+    /// Add up our fills from the user trades of the instrument since the start.
+    async fn sync_fills(&mut self) {
+        let currency = settle_currency(&self.parent.instrument_id).to_string();
+        let result = self
+            .api
+            .user_trades_since(
+                self.parent.market,
+                &currency,
+                Some(&self.parent.instrument_id),
+                self.start_ms,
+            )
+            .await;
+        let Some(trades) = self.check(result) else {
+            return;
+        };
+        let mut changed = false;
+        for t in trades {
+            if self.child_ids.contains(&t.order_id) && self.seen_trades.insert(t.trade_id) {
+                self.filled_qty += t.qty;
+                self.filled_notional += t.qty * t.price;
+                changed = true;
+            }
+        }
+        if changed {
+            self.emit(AlgoEvent::Progress {
+                filled_qty: self.filled_qty,
+                avg_price: self.avg_price(),
+            });
+        }
+    }
+}
@@ -0,0 +1,334 @@
+// Deterministic backtester: replays recorded market data on a simulated clock, routes the
+// strategy's orders through the local matching engine with configurable latency, and reports
+// fills, an equity curve and summary statistics.
+//
+// Strategies see the same `WsEvent`, `OrderRequest`, `AmendRequest` and `CancelRequest` types
+// as the live WebSocket and `TradingApi` paths.
+
+use crate::bit_ws_client::WsEvent;
+use crate::error::BitError;
+use crate::matching::{FeeSchedule, MatchingEngine};
+use crate::models::{AmendRequest, CancelRequest, Market, Order, OrderRequest, Position, Trade};
+use crate::order_book::OrderBook;
+use crate::record::RecordedFrame;
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, BinaryHeap, HashMap};
+
+pub trait Strategy {
+    fn on_start(&mut self, _ctx: &mut StrategyContext) {}
+
+    /// Market data and private events (orders, fills, positions) in simulated time order.
+    fn on_event(&mut self, ctx: &mut StrategyContext, event: &WsEvent);
+
+    /// An order action was rejected by the matching engine.
+    fn on_order_error(&mut self, _ctx: &mut StrategyContext, _error: &BitError) {}
+
+    fn on_finish(&mut self, _ctx: &mut StrategyContext) {}
+}
+
+#[derive(Debug, Clone)]
+pub enum OrderAction {
+    New(Market, OrderRequest),
+    Amend(Market, AmendRequest),
+    Cancel(Market, CancelRequest),
+}
+
+/// The strategy's view of the world: what it has been told so far, plus the order actions it
+/// issues during a callback. Actions reach the matching engine after `order_latency_ms`.
+pub struct StrategyContext {
+    now_ms: i64,
+    books: HashMap<String, OrderBook>,
+    open_orders: BTreeMap<String, Order>,
+    positions: BTreeMap<String, Position>,
+    actions: Vec<OrderAction>,
+}
+
+impl StrategyContext {
+    fn new() -> Self {
+        Self {
+            now_ms: 0,
+            books: HashMap::new(),
+            open_orders: BTreeMap::new(),
+            positions: BTreeMap::new(),
+            actions: Vec::new(),
+        }
+    }
+
+    pub fn now_ms(&self) -> i64 {
+        self.now_ms
+    }
+
+    /// None before the first snapshot, and after a sequence gap until the next one.
+    pub fn book(&self, instrument_id: &str) -> Option<&OrderBook> {
+        self.books.get(instrument_id).filter(|b| b.is_valid())
+    }
+
+    /// Open orders as last reported through order events.
+    pub fn open_orders(&self) -> impl Iterator<Item = &Order> {
+        self.open_orders.values()
+    }
+
+    pub fn position(&self, instrument_id: &str) -> Option<&Position> {
+        self.positions.get(instrument_id)
+    }
+
+    pub fn new_order(&mut self, market: Market, req: OrderRequest) {
+        self.actions.push(OrderAction::New(market, req));
+    }
+
+    pub fn amend_order(&mut self, market: Market, req: AmendRequest) {
+        self.actions.push(OrderAction::Amend(market, req));
+    }
+
+    pub fn cancel_order(&mut self, market: Market, req: CancelRequest) {
+        self.actions.push(OrderAction::Cancel(market, req));
+    }
+
+    fn observe(&mut self, event: &WsEvent) {
+        match event {
+            WsEvent::Depth(depth) => self
+                .books
+                .entry(depth.instrument_id.clone())
+                .or_insert_with(|| OrderBook::new(&depth.instrument_id))
+                .apply(depth),
+            WsEvent::Orders(orders) => {
+                for order in orders {
+                    if order.is_open() {
+                        self.open_orders
+                            .insert(order.order_id.clone(), order.clone());
+                    } else {
+                        self.open_orders.remove(&order.order_id);
+                    }
+                }
+            }
+            WsEvent::Positions(positions) => {
+                for pos in positions {
+                    self.positions
+                        .insert(pos.instrument_id.clone(), pos.clone());
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct BacktestConfig {
+    pub fees: FeeSchedule,
+    pub balances: HashMap<String, f64>,
+    /// Strategy to matching engine.
+    pub order_latency_ms: i64,
+    /// Matching engine to strategy, for acks and fills.
+    pub ack_latency_ms: i64,
+    /// Recorded receive time to strategy, on top of what the recording already contains.
+    pub market_data_latency_ms: i64,
+    pub queue_position: bool,
+    pub leverage: f64,
+    /// Equity curve sampling interval.
+    pub sample_interval_ms: i64,
+}
+
+impl Default for BacktestConfig {
+    fn default() -> Self {
+        Self {
+            fees: FeeSchedule::default(),
+            balances: HashMap::from([("USDT".to_string(), 100_000.0)]),
+            order_latency_ms: 5,
+            ack_latency_ms: 5,
+            market_data_latency_ms: 0,
+            queue_position: true,
+            leverage: 10.0,
+            sample_interval_ms: 60_000,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct BacktestStats {
+    pub start_equity: f64,
+    pub end_equity: f64,
+    pub pnl: f64,
+    pub fees: f64,
+    pub volume: f64,
+    pub num_trades: usize,
+    pub num_rejects: usize,
+    pub max_drawdown: f64,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct BacktestReport {
+    pub fills: Vec<Trade>,
+    /// `(time_ms, total_margin_balance)` samples.
+    pub equity_curve: Vec<(i64, f64)>,
+    pub stats: BacktestStats,
+}
+
+enum Scheduled {
+    MarketData(WsEvent),
+    ToEngine(OrderAction),
+    ToStrategy(WsEvent),
+    Rejected(BitError),
+}
+
+pub struct Backtester {
+    config: BacktestConfig,
+}
+
+struct Run {
+    engine: MatchingEngine,
+    ctx: StrategyContext,
+    queue: BinaryHeap<Reverse<(i64, u64)>>,
+    items: HashMap<u64, Scheduled>,
+    seq: u64,
+    report: BacktestReport,
+    next_sample_ms: Option<i64>,
+}
+
+impl Run {
+    fn schedule(&mut self, at_ms: i64, item: Scheduled) {
+        self.seq += 1;
+        self.queue.push(Reverse((at_ms, self.seq)));
+        self.items.insert(self.seq, item);
+    }
+}
+
+impl Backtester {
+    pub fn new(config: BacktestConfig) -> Self {
+        Self { config }
+    }
+
+    /// Replay `frames` (ordered by `recv_ms`) through `strategy`.
+    pub fn run<S, I>(&self, strategy: &mut S, frames: I) -> BacktestReport
+    where
+        S: Strategy,
+        I: IntoIterator<Item = RecordedFrame>,
+    {
+        let engine = MatchingEngine::new(self.config.fees, self.config.balances.clone())
+            .with_leverage(self.config.leverage)
+            .with_queue_position(self.config.queue_position);
+        let mut run = Run {
+            engine,
+            ctx: StrategyContext::new(),
+            queue: BinaryHeap::new(),
+            items: HashMap::new(),
+            seq: 0,
+            report: BacktestReport::default(),
+            next_sample_ms: None,
+        };
+
+        let mut frames = frames.into_iter().peekable();
+        let start_ms = frames.peek().map_or(0, |f| f.recv_ms);
+        run.ctx.now_ms = start_ms;
+        run.engine.set_time(start_ms);
+        run.report.stats.start_equity = run.engine.um_account().total_margin_balance;
+        strategy.on_start(&mut run.ctx);
+        self.dispatch_actions(&mut run);
+
+        for frame in frames {
+            self.drain(&mut run, strategy, Some(frame.recv_ms));
+            let Some(event) = frame.event() else {
+                continue;
+            };
+            run.engine.set_time(frame.recv_ms);
+            for private in run.engine.on_market_event(&event) {
+                run.schedule(
+                    frame.recv_ms + self.config.ack_latency_ms,
+                    Scheduled::ToStrategy(private),
+                );
+            }
+            run.schedule(
+                frame.recv_ms + self.config.market_data_latency_ms,
+                Scheduled::MarketData(event),
+            );
+            self.drain(&mut run, strategy, Some(frame.recv_ms));
+        }
+        self.drain(&mut run, strategy, None);
+
+        strategy.on_finish(&mut run.ctx);
+        self.finish(run)
+    }
+
+    /// Process everything scheduled up to `until_ms` inclusive, or everything if `None`.
+    fn drain<S: Strategy>(&self, run: &mut Run, strategy: &mut S, until_ms: Option<i64>) {
+        while let Some(Reverse((at_ms, seq))) = run.queue.peek().copied() {
+            if until_ms.is_some_and(|t| at_ms > t) {
+                break;
+            }
+            run.queue.pop();
+            let item = run.items.remove(&seq).unwrap();
+            self.sample_equity(run, at_ms);
+            run.ctx.now_ms = at_ms;
+            run.engine.set_time(at_ms);
+            match item {
+                Scheduled::MarketData(event) | Scheduled::ToStrategy(event) => {
+                    if let WsEvent::UserTrades(trades) = &event {
+                        run.report.fills.extend(trades.iter().cloned());
+                    }
+                    run.ctx.observe(&event);
+                    strategy.on_event(&mut run.ctx, &event);
+                }
+                Scheduled::Rejected(error) => {
+                    run.report.stats.num_rejects += 1;
+                    strategy.on_order_error(&mut run.ctx, &error);
+                }
+                Scheduled::ToEngine(action) => {
+                    let ack_ms = at_ms + self.config.ack_latency_ms;
+                    let result = match &action {
+                        OrderAction::New(market, req) => {
+                            run.engine.new_order(*market, req).map(|(_, e)| e)
+                        }
+                        OrderAction::Amend(_, req) => run.engine.amend_order(req).map(|(_, e)| e),
+                        OrderAction::Cancel(market, req) => Ok(run.engine.cancel(*market, req).1),
+                    };
+                    match result {
+                        Ok(events) => {
+                            for event in events {
+                                run.schedule(ack_ms, Scheduled::ToStrategy(event));
+                            }
+                        }
+                        Err(error) => run.schedule(ack_ms, Scheduled::Rejected(error)),
+                    }
+                }
+            }
+            self.dispatch_actions(run);
+        }
+    }
+
+    fn dispatch_actions(&self, run: &mut Run) {
+        let at_ms = run.ctx.now_ms + self.config.order_latency_ms;
+        for action in std::mem::take(&mut run.ctx.actions) {
+            run.schedule(at_ms, Scheduled::ToEngine(action));
+        }
+    }
+
+    fn sample_equity(&self, run: &mut Run, now_ms: i64) {
+        let next = *run.next_sample_ms.get_or_insert(now_ms);
+        if now_ms >= next {
+            let equity = run.engine.um_account().total_margin_balance;
+            run.report.equity_curve.push((now_ms, equity));
+            run.next_sample_ms = Some(now_ms + self.config.sample_interval_ms.max(1));
+        }
+    }
+
+    fn finish(&self, mut run: Run) -> BacktestReport {
+        let end_equity = run.engine.um_account().total_margin_balance;
+        run.report
+            .equity_curve
+            .push((run.engine.now_ms(), end_equity));
+
+        let stats = &mut run.report.stats;
+        stats.end_equity = end_equity;
+        stats.pnl = end_equity - stats.start_equity;
+        stats.num_trades = run.report.fills.len();
+        for fill in &run.report.fills {
+            stats.fees += fill.fee;
+            stats.volume += fill.price * fill.qty;
+        }
+        let mut peak = f64::MIN;
+        for &(_, equity) in &run.report.equity_curve {
+            peak = peak.max(equity);
+            stats.max_drawdown = stats.max_drawdown.max(peak - equity);
+        }
+        run.report
+    }
+}
@@ -0,0 +1,1194 @@
+// bitcli: every BitRestClient endpoint from the command line.
+//
+//     bitcli linear orders new --instrument BTC-USDT-PERPETUAL --side buy --qty 1 --price 20000
+//     bitcli spot trades --since 1d -o csv
+//     bitcli um account
+//     bitcli wallet deposits --currency USDT --since 7d
+//     bitcli pnl --from 2024-01-01 --to 2024-01-31 --basis fifo --out jan.md
+//     bitcli export --source um --from 2024-01-01 --to 2024-02-01 --out jan.csv
+//     bitcli mmp reset --pair BTC-USDT
+//
+// Credentials come from a profile in ~/.config/bitcli/config.toml (or --config / BITCLI_CONFIG),
+// or from the BITCOM_REST_HOST, BITCOM_AK and BITCOM_SK env vars used by the examples.
+
+mod output;
+
+use bitsdk_rust::bit_rest_client::BitRestClient;
+use bitsdk_rust::block_trade::{
+    self, BlockTradeLimits, BlockTradeQuery, BlockTradeRequest, BlockTradeRole,
+};
+use bitsdk_rust::credentials::{
+    CommandProvider, CredentialProvider, Credentials, KeyFileProvider, SecretString,
+};
+use bitsdk_rust::ledger::{self, LedgerQuery, LedgerSource};
+use bitsdk_rust::models::{AmendRequest, CancelRequest, Market, OrderRequest, Side, TimeInForce};
+use bitsdk_rust::pnl::{self, CostBasis, PnlReportBuilder};
+use bitsdk_rust::util::now_ms;
+use bitsdk_rust::wallet::{
+    HistoryQuery, SubAccountTransferRequest, TransferRequest, WalletAccount, WithdrawRequest,
+};
+use chrono::NaiveDate;
+use clap::{Parser, Subcommand, ValueEnum};
+use output::OutputFormat;
+use reqwest::Method;
+use serde::Deserialize;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::env;
+use std::path::PathBuf;
+use std::process;
+
+#[derive(Parser)]
+#[command(name = "bitcli", about = "bit.com command line client")]
+struct Cli {
+    /// Profile name in the config file
+    #[arg(long, global = true)]
+    profile: Option<String>,
+    /// Config file, defaults to ~/.config/bitcli/config.toml
+    #[arg(long, global = true, env = "BITCLI_CONFIG")]
+    config: Option<PathBuf>,
+    #[arg(short, long, global = true, value_enum, default_value_t = OutputFormat::Table)]
+    output: OutputFormat,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Spot endpoints
+    Spot {
+        #[command(subcommand)]
+        cmd: MarketCmd,
+    },
+    /// Linear (USDT-margined) endpoints
+    Linear {
+        #[command(subcommand)]
+        cmd: MarketCmd,
+    },
+    /// Option (coin-margined) endpoints, --currency BTC or ETH
+    Option {
+        #[command(subcommand)]
+        cmd: MarketCmd,
+    },
+    /// Unified margin account endpoints
+    Um {
+        #[command(subcommand)]
+        cmd: UmCmd,
+    },
+    /// Market-maker protection, linear unless --market spot/option
+    Mmp {
+        #[arg(long, value_enum, default_value_t = MarketArg::Linear)]
+        market: MarketArg,
+        #[command(subcommand)]
+        cmd: MmpCmd,
+    },
+    /// Deposits, withdrawals and transfers
+    Wallet {
+        #[command(subcommand)]
+        cmd: WalletCmd,
+    },
+    /// Export the spot or UM transaction log to .csv or .parquet
+    Export {
+        #[arg(long, value_enum)]
+        source: WalletArg,
+        /// First day, YYYY-MM-DD (UTC)
+        #[arg(long)]
+        from: NaiveDate,
+        /// Day after the last one, YYYY-MM-DD (UTC)
+        #[arg(long)]
+        to: NaiveDate,
+        #[arg(long)]
+        currency: Option<String>,
+        #[arg(long)]
+        out: PathBuf,
+    },
+    /// Daily PnL and fees per instrument from spot and linear fills and UM funding
+    Pnl {
+        /// First day, YYYY-MM-DD (UTC)
+        #[arg(long)]
+        from: NaiveDate,
+        /// Last day, YYYY-MM-DD (UTC)
+        #[arg(long)]
+        to: NaiveDate,
+        #[arg(long, default_value = "USDT")]
+        currency: String,
+        #[arg(long, value_enum, default_value_t = BasisArg::Fifo)]
+        basis: BasisArg,
+        /// USD rate of a non-stablecoin currency, e.g. --usd-rate BTC=65000
+        #[arg(long)]
+        usd_rate: Vec<String>,
+        /// Full report as .csv or .md, otherwise only the daily USD totals are printed
+        #[arg(long)]
+        out: Option<PathBuf>,
+    },
+    /// Fetch a WebSocket auth token
+    WsAuth,
+    /// Call any private endpoint, e.g. `bitcli raw get /linear/v1/orders '{"currency":"USDT"}'`
+    Raw {
+        method: String,
+        path: String,
+        params: Option<String>,
+    },
+}
+
+#[derive(Subcommand)]
+enum MarketCmd {
+    Orders {
+        #[command(subcommand)]
+        cmd: OrdersCmd,
+    },
+    /// Our own fills
+    Trades {
+        #[arg(long)]
+        instrument: Option<String>,
+        #[arg(long, default_value = "USDT")]
+        currency: String,
+        /// Look-back window such as 30m, 12h or 1d
+        #[arg(long)]
+        since: Option<String>,
+    },
+    /// Linear and option, options include greeks
+    Positions {
+        #[arg(long, default_value = "USDT")]
+        currency: String,
+        #[arg(long)]
+        instrument: Option<String>,
+    },
+    /// Spot only
+    Accounts,
+    /// Spot only, transaction logs
+    Txlogs {
+        #[arg(long)]
+        currency: Option<String>,
+        #[arg(long)]
+        since: Option<String>,
+    },
+    Configs,
+    /// Cancel-on-disconnect
+    Cod {
+        #[arg(value_enum)]
+        state: OnOff,
+    },
+    Mmp {
+        #[command(subcommand)]
+        cmd: MmpCmd,
+    },
+    /// Linear only, show or set the leverage ratio of a pair
+    Leverage {
+        #[arg(long)]
+        pair: String,
+        #[arg(long)]
+        set: Option<f64>,
+    },
+    /// Linear only, set the position mode
+    PositionMode {
+        mode: String,
+    },
+    /// Linear only, block trades
+    BlockTrades {
+        #[command(subcommand)]
+        cmd: BlockTradeCmd,
+    },
+    /// Option only, listed instruments
+    Instruments {
+        #[arg(long, default_value = "BTC")]
+        currency: String,
+    },
+    /// Option only, mark price, IV and greeks
+    Tickers {
+        #[arg(long)]
+        instrument: String,
+    },
+    /// Option only, expired positions delivered
+    Deliveries {
+        #[arg(long, default_value = "BTC")]
+        currency: String,
+        #[arg(long)]
+        since: Option<String>,
+    },
+    /// Option only, settlement history
+    Settlements {
+        #[arg(long, default_value = "BTC")]
+        currency: String,
+        #[arg(long)]
+        since: Option<String>,
+    },
+}
+
+#[derive(Subcommand)]
+enum OrdersCmd {
+    New {
+        #[arg(long)]
+        instrument: String,
+        #[arg(long, value_enum)]
+        side: SideArg,
+        #[arg(long)]
+        qty: f64,
+        /// Omit for a market order
+        #[arg(long)]
+        price: Option<f64>,
+        #[arg(long, value_enum)]
+        tif: Option<TifArg>,
+        #[arg(long)]
+        post_only: bool,
+        #[arg(long)]
+        reduce_only: bool,
+        #[arg(long)]
+        label: Option<String>,
+    },
+    Amend {
+        #[arg(long)]
+        instrument: String,
+        #[arg(long)]
+        order_id: Option<String>,
+        #[arg(long)]
+        label: Option<String>,
+        #[arg(long)]
+        price: Option<f64>,
+        #[arg(long)]
+        qty: Option<f64>,
+    },
+    /// Without --order-id, --label or --instrument every order in the currency is cancelled
+    Cancel {
+        #[arg(long, default_value = "USDT")]
+        currency: String,
+        #[arg(long)]
+        instrument: Option<String>,
+        #[arg(long)]
+        order_id: Option<String>,
+        #[arg(long)]
+        label: Option<String>,
+        /// Cancel everything without asking
+        #[arg(long)]
+        yes: bool,
+    },
+    /// Order history
+    List {
+        #[arg(long, default_value = "USDT")]
+        currency: String,
+        #[arg(long)]
+        instrument: Option<String>,
+        #[arg(long)]
+        since: Option<String>,
+    },
+    Open {
+        #[arg(long, default_value = "USDT")]
+        currency: String,
+        #[arg(long)]
+        instrument: Option<String>,
+    },
+    /// Body is read from a JSON file, same shape as the batchorders endpoint
+    BatchNew {
+        file: PathBuf,
+    },
+    BatchAmend {
+        file: PathBuf,
+    },
+}
+
+#[derive(Subcommand)]
+enum BlockTradeCmd {
+    /// Book a block trade, legs are read from a JSON file:
+    /// `[{"instrument_id": "BTC-USDT-PERPETUAL", "side": "buy", "price": 20000, "qty": 50}]`
+    New {
+        #[arg(long)]
+        label: String,
+        #[arg(long)]
+        counterparty: String,
+        #[arg(long, value_enum, default_value_t = RoleArg::Taker)]
+        role: RoleArg,
+        legs: PathBuf,
+    },
+    /// Our own block trades
+    List {
+        #[arg(long, default_value = "USDT")]
+        currency: String,
+        #[arg(long)]
+        since: Option<String>,
+    },
+    /// Block trades of every user, all pages
+    Platform {
+        #[arg(long, default_value = "USDT")]
+        currency: String,
+        #[arg(long)]
+        instrument: Option<String>,
+        #[arg(long)]
+        since: Option<String>,
+    },
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum RoleArg {
+    Taker,
+    Maker,
+}
+
+#[derive(Subcommand)]
+enum MmpCmd {
+    State {
+        #[arg(long)]
+        pair: String,
+    },
+    Reset {
+        #[arg(long)]
+        pair: String,
+    },
+    Update {
+        #[arg(long)]
+        pair: String,
+        #[arg(long)]
+        window_ms: i64,
+        #[arg(long)]
+        frozen_ms: i64,
+        #[arg(long)]
+        qty_limit: f64,
+        #[arg(long)]
+        delta_limit: f64,
+    },
+}
+
+#[derive(Subcommand)]
+enum UmCmd {
+    Account,
+    /// Show the account mode, or switch it with --set
+    Mode {
+        #[arg(long)]
+        set: Option<String>,
+    },
+    Txlogs {
+        #[arg(long, default_value = "USDT")]
+        currency: String,
+        #[arg(long)]
+        since: Option<String>,
+    },
+}
+
+#[derive(Subcommand)]
+enum WalletCmd {
+    DepositAddress {
+        #[arg(long)]
+        currency: String,
+        #[arg(long)]
+        chain: String,
+    },
+    Deposits {
+        #[arg(long)]
+        currency: Option<String>,
+        #[arg(long)]
+        since: Option<String>,
+    },
+    Withdraw {
+        #[arg(long)]
+        currency: String,
+        #[arg(long)]
+        chain: String,
+        #[arg(long)]
+        address: String,
+        #[arg(long)]
+        amount: f64,
+        #[arg(long)]
+        memo: Option<String>,
+        #[arg(long)]
+        client_id: Option<String>,
+        /// Send without asking
+        #[arg(long)]
+        yes: bool,
+    },
+    Withdrawals {
+        #[arg(long)]
+        currency: Option<String>,
+        #[arg(long)]
+        since: Option<String>,
+    },
+    WithdrawFee {
+        #[arg(long)]
+        currency: String,
+        #[arg(long)]
+        chain: String,
+    },
+    /// Between our own spot and UM wallets
+    Transfer {
+        #[arg(long)]
+        currency: String,
+        #[arg(long)]
+        amount: f64,
+        #[arg(long, value_enum)]
+        from: WalletArg,
+        #[arg(long, value_enum)]
+        to: WalletArg,
+    },
+    /// Between the master account and a sub-account, by user id
+    SubTransfer {
+        #[arg(long)]
+        currency: String,
+        #[arg(long)]
+        amount: f64,
+        #[arg(long)]
+        from_uid: String,
+        #[arg(long)]
+        to_uid: String,
+    },
+    Transfers {
+        #[arg(long)]
+        currency: Option<String>,
+        #[arg(long)]
+        since: Option<String>,
+    },
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum WalletArg {
+    Spot,
+    Um,
+}
+
+impl From<WalletArg> for WalletAccount {
+    fn from(arg: WalletArg) -> Self {
+        match arg {
+            WalletArg::Spot => WalletAccount::Spot,
+            WalletArg::Um => WalletAccount::Um,
+        }
+    }
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum BasisArg {
+    Fifo,
+    Average,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum MarketArg {
+    Spot,
+    Linear,
+    Option,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum SideArg {
+    Buy,
+    Sell,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum TifArg {
+    Gtc,
+    Ioc,
+    Fok,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum OnOff {
+    On,
+    Off,
+}
+
+#[derive(Deserialize)]
+struct ConfigFile {
+    #[serde(default)]
+    profiles: HashMap<String, Profile>,
+}
+
+/// The secret comes from `secret_key`, `secret_command` (prints the secret) or an encrypted
+/// `keyfile` whose passphrase is in BITCLI_KEYFILE_PASSPHRASE.
+#[derive(Deserialize)]
+struct Profile {
+    rest_host: String,
+    #[serde(default)]
+    access_key: String,
+    secret_key: Option<SecretString>,
+    secret_command: Option<Vec<String>>,
+    keyfile: Option<PathBuf>,
+}
+
+impl Profile {
+    fn credential_provider(&self) -> Result<Box<dyn CredentialProvider>, String> {
+        if let Some(path) = &self.keyfile {
+            let provider = KeyFileProvider::from_env(path, "BITCLI_KEYFILE_PASSPHRASE")
+                .map_err(|e| e.to_string())?;
+            return Ok(Box::new(provider));
+        }
+        if let Some([program, args @ ..]) = self.secret_command.as_deref() {
+            let args: Vec<&str> = args.iter().map(String::as_str).collect();
+            return Ok(Box::new(CommandProvider::new(
+                &self.access_key,
+                program,
+                &args,
+            )));
+        }
+        match &self.secret_key {
+            Some(sk) => Ok(Box::new(Credentials::new(&self.access_key, sk.clone()))),
+            None => Err("profile needs one of secret_key, secret_command or keyfile".to_string()),
+        }
+    }
+}
+
+#[tokio::main]
+async fn main() {
+    let cli = Cli::parse();
+    let client = build_client(&cli).unwrap_or_else(|e| fail(&e));
+    let rsp = run(&client, cli.command).await.unwrap_or_else(|e| fail(&e));
+    println!("{}", output::render(&rsp, cli.output));
+    if rsp["code"].as_i64().is_some_and(|c| c != 0) {
+        process::exit(2);
+    }
+}
+
+fn fail(msg: &str) -> ! {
+    eprintln!("bitcli: {}", msg);
+    process::exit(1);
+}
+
+/// An explicit --profile wins, then the BITCOM_* env vars, then the "default" profile.
+fn build_client(cli: &Cli) -> Result<BitRestClient, String> {
+    if cli.profile.is_none()
+        && let (Ok(host), Ok(ak), Ok(sk)) = (
+            env::var("BITCOM_REST_HOST"),
+            env::var("BITCOM_AK"),
+            env::var("BITCOM_SK"),
+        )
+    {
+        return Ok(BitRestClient::new(&ak, &sk, &host));
+    }
+
+    let path = match &cli.config {
+        Some(path) => path.clone(),
+        None => PathBuf::from(env::var("HOME").map_err(|_| "HOME is not set")?)
+            .join(".config/bitcli/config.toml"),
+    };
+    let text = std::fs::read_to_string(&path)
+        .map_err(|e| format!("{}: {} (or set BITCOM_REST_HOST/AK/SK)", path.display(), e))?;
+    let config: ConfigFile = toml::from_str(&text).map_err(|e| e.to_string())?;
+    let name = cli.profile.as_deref().unwrap_or("default");
+    let profile = config.profiles.get(name).ok_or(format!(
+        "no profile [profiles.{}] in {}",
+        name,
+        path.display()
+    ))?;
+    BitRestClient::from_provider(profile.credential_provider()?.as_ref(), &profile.rest_host)
+        .map_err(|e| e.to_string())
+}
+
+/// "30m", "12h", "1d", "2w" -> start time in ms.
+fn since_ms(since: &str) -> Result<i64, String> {
+    let (num, unit_ms) = [
+        ("s", 1_000),
+        ("m", 60_000),
+        ("h", 3_600_000),
+        ("d", 86_400_000),
+        ("w", 604_800_000),
+    ]
+    .into_iter()
+    .find_map(|(unit, ms)| Some((since.strip_suffix(unit)?, ms)))
+    .ok_or(format!("bad --since unit in {}", since))?;
+    let n: i64 = num.parse().map_err(|_| format!("bad --since {}", since))?;
+    Ok(now_ms() - n * unit_ms)
+}
+
+/// Ask before an irreversible request, unless `yes`.
+fn confirm(action: &str, yes: bool) -> Result<(), String> {
+    if yes {
+        return Ok(());
+    }
+    eprint!("{}? [y/N] ", action);
+    let mut answer = String::new();
+    std::io::stdin()
+        .read_line(&mut answer)
+        .map_err(|e| e.to_string())?;
+    match answer.trim() {
+        "y" | "Y" | "yes" => Ok(()),
+        _ => Err("aborted, pass --yes to skip the prompt".to_string()),
+    }
+}
+
+fn filter_params(
+    market: Market,
+    currency: Option<&str>,
+    instrument: Option<&str>,
+    since: Option<&str>,
+) -> Result<Value, String> {
+    let mut params = serde_json::json!({});
+    if let Some(currency) = currency
+        && market != Market::Spot
+    {
+        params["currency"] = Value::String(currency.to_string());
+    }
+    if let Some(instrument) = instrument {
+        let key = match market {
+            Market::Spot => "pair",
+            Market::Linear | Market::Option => "instrument_id",
+        };
+        params[key] = Value::String(instrument.to_string());
+    }
+    if let Some(since) = since {
+        params["start_time"] = Value::from(since_ms(since)?);
+    }
+    Ok(params)
+}
+
+fn read_json(path: &PathBuf) -> Result<Value, String> {
+    let text = std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    serde_json::from_str(&text).map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+async fn run(client: &BitRestClient, command: Command) -> Result<Value, String> {
+    let rsp = match command {
+        Command::Spot { cmd } => return run_market(client, Market::Spot, cmd).await,
+        Command::Linear { cmd } => return run_market(client, Market::Linear, cmd).await,
+        Command::Option { cmd } => return run_market(client, Market::Option, cmd).await,
+        Command::Um { cmd } => match cmd {
+            UmCmd::Account => client.get_um_account(&mut serde_json::json!({})).await,
+            UmCmd::Mode { set: None } => client.get_um_account_mode().await,
+            UmCmd::Mode { set: Some(mode) } => {
+                client
+                    .update_um_account_mode(&mut serde_json::json!({ "account_mode": mode }))
+                    .await
+            }
+            UmCmd::Txlogs { currency, since } => {
+                let mut params =
+                    filter_params(Market::Linear, Some(&currency), None, since.as_deref())?;
+                client.get_um_txlogs(&mut params).await
+            }
+        },
+        Command::Mmp { market, cmd } => {
+            let market = match market {
+                MarketArg::Spot => Market::Spot,
+                MarketArg::Linear => Market::Linear,
+                MarketArg::Option => Market::Option,
+            };
+            return run_mmp(client, market, cmd).await;
+        }
+        Command::Wallet { cmd } => return run_wallet(client, cmd).await,
+        Command::Export {
+            source,
+            from,
+            to,
+            currency,
+            out,
+        } => return run_export(client, source, from, to, currency, out).await,
+        Command::Pnl {
+            from,
+            to,
+            currency,
+            basis,
+            usd_rate,
+            out,
+        } => {
+            let basis = match basis {
+                BasisArg::Fifo => CostBasis::Fifo,
+                BasisArg::Average => CostBasis::Average,
+            };
+            let mut builder = PnlReportBuilder::new(basis, from, to);
+            for pair in &usd_rate {
+                let (ccy, rate) = pair
+                    .split_once('=')
+                    .and_then(|(c, r)| Some((c, r.parse().ok()?)))
+                    .ok_or(format!("bad --usd-rate {}, expected CCY=RATE", pair))?;
+                builder = builder.usd_rate(ccy, rate);
+            }
+            return run_pnl(client, builder, &currency, out).await;
+        }
+        Command::WsAuth => client.ws_auth().await,
+        Command::Raw {
+            method,
+            path,
+            params,
+        } => {
+            let method =
+                Method::from_bytes(method.to_uppercase().as_bytes()).map_err(|e| e.to_string())?;
+            let mut params = match params {
+                Some(p) => serde_json::from_str(&p).map_err(|e| e.to_string())?,
+                None => serde_json::json!({}),
+            };
+            client.call_private_api(&path, method, &mut params).await
+        }
+    };
+    rsp.map_err(|e| e.to_string())
+}
+
+async fn run_market(
+    client: &BitRestClient,
+    market: Market,
+    cmd: MarketCmd,
+) -> Result<Value, String> {
+    let spot = market == Market::Spot;
+    let rsp = match cmd {
+        MarketCmd::Orders { cmd } => return run_orders(client, market, cmd).await,
+        MarketCmd::Trades {
+            instrument,
+            currency,
+            since,
+        } => {
+            let mut params = filter_params(
+                market,
+                Some(&currency),
+                instrument.as_deref(),
+                since.as_deref(),
+            )?;
+            match market {
+                Market::Spot => client.spot_get_user_trades(&mut params).await,
+                Market::Linear => client.linear_get_user_trades(&mut params).await,
+                Market::Option => client.option_get_user_trades(&mut params).await,
+            }
+        }
+        MarketCmd::Positions { .. } if spot => return Err("no positions on spot".into()),
+        MarketCmd::Positions {
+            currency,
+            instrument,
+        } => {
+            let mut params = filter_params(market, Some(&currency), instrument.as_deref(), None)?;
+            if market == Market::Option {
+                client.option_get_positions(&mut params).await
+            } else {
+                client.linear_get_positions(&mut params).await
+            }
+        }
+        MarketCmd::Accounts if spot => client.spot_get_class_accounts().await,
+        MarketCmd::Accounts => return Err("use `bitcli um account`".into()),
+        MarketCmd::Txlogs { currency, since } if spot => {
+            let mut params = filter_params(market, None, None, since.as_deref())?;
+            if let Some(currency) = currency {
+                params["currency"] = Value::String(currency);
+            }
+            client.spot_get_class_txlogs(&mut params).await
+        }
+        MarketCmd::Txlogs { .. } => return Err("use `bitcli um txlogs`".into()),
+        MarketCmd::Configs if spot => {
+            client
+                .spot_get_account_configs(&mut serde_json::json!({}))
+                .await
+        }
+        MarketCmd::Configs if market == Market::Linear => {
+            client
+                .linear_get_account_configs(&mut serde_json::json!({}))
+                .await
+        }
+        MarketCmd::Configs => return Err("spot and linear only".into()),
+        MarketCmd::Cod { state } => {
+            let mut params = serde_json::json!({ "cod": matches!(state, OnOff::On) });
+            match market {
+                Market::Spot => client.spot_enable_cod(&mut params).await,
+                Market::Linear => client.linear_enable_cod(&mut params).await,
+                Market::Option => client.option_enable_cod(&mut params).await,
+            }
+        }
+        MarketCmd::Mmp { cmd } => return run_mmp(client, market, cmd).await,
+        MarketCmd::Leverage { .. }
+        | MarketCmd::PositionMode { .. }
+        | MarketCmd::BlockTrades { .. }
+            if market != Market::Linear =>
+        {
+            return Err("linear only".into());
+        }
+        MarketCmd::Instruments { .. }
+        | MarketCmd::Tickers { .. }
+        | MarketCmd::Deliveries { .. }
+        | MarketCmd::Settlements { .. }
+            if market != Market::Option =>
+        {
+            return Err("option only".into());
+        }
+        MarketCmd::BlockTrades { cmd } => return run_block_trades(client, cmd).await,
+        MarketCmd::Instruments { currency } => {
+            let params = serde_json::json!({ "currency": currency, "category": "option" });
+            client.option_get_instruments(&params).await
+        }
+        MarketCmd::Tickers { instrument } => {
+            let params = serde_json::json!({ "instrument_id": instrument });
+            client.option_get_tickers(&params).await
+        }
+        MarketCmd::Deliveries { currency, since } => {
+            let mut params = filter_params(market, Some(&currency), None, since.as_deref())?;
+            client.option_get_deliveries(&mut params).await
+        }
+        MarketCmd::Settlements { currency, since } => {
+            let mut params = filter_params(market, Some(&currency), None, since.as_deref())?;
+            client.option_get_settlements(&mut params).await
+        }
+        MarketCmd::Leverage { pair, set: None } => {
+            client
+                .linear_get_leverage_ratio(&mut serde_json::json!({ "pair": pair }))
+                .await
+        }
+        MarketCmd::Leverage {
+            pair,
+            set: Some(ratio),
+        } => {
+            client
+                .linear_update_leverage_ratio(&mut serde_json::json!({
+                    "pair": pair,
+                    "leverage_ratio": ratio.to_string(),
+                }))
+                .await
+        }
+        MarketCmd::PositionMode { mode } => {
+            client
+                .linear_update_position_mode(&mut serde_json::json!({ "position_mode": mode }))
+                .await
+        }
+    };
+    rsp.map_err(|e| e.to_string())
+}
+
+async fn run_orders(
+    client: &BitRestClient,
+    market: Market,
+    cmd: OrdersCmd,
+) -> Result<Value, String> {
+    let rsp = match cmd {
+        OrdersCmd::New {
+            instrument,
+            side,
+            qty,
+            price,
+            tif,
+            post_only,
+            reduce_only,
+            label,
+        } => {
+            let side = match side {
+                SideArg::Buy => Side::Buy,
+                SideArg::Sell => Side::Sell,
+            };
+            let mut req = match price {
+                Some(price) => OrderRequest::limit(&instrument, side, qty, price),
+                None => OrderRequest::market(&instrument, side, qty),
+            };
+            req.time_in_force = tif.map(|t| match t {
+                TifArg::Gtc => TimeInForce::Gtc,
+                TifArg::Ioc => TimeInForce::Ioc,
+                TifArg::Fok => TimeInForce::Fok,
+            });
+            req.post_only = post_only;
+            req.reduce_only = reduce_only;
+            req.label = label;
+            let mut params = req.to_params(market);
+            match market {
+                Market::Spot => client.spot_new_order(&mut params).await,
+                Market::Linear => client.linear_new_order(&mut params).await,
+                Market::Option => client.option_new_order(&mut params).await,
+            }
+        }
+        OrdersCmd::Amend {
+            instrument,
+            order_id,
+            label,
+            price,
+            qty,
+        } => {
+            let mut params = AmendRequest {
+                instrument_id: instrument,
+                order_id,
+                label,
+                price,
+                qty,
+            }
+            .to_params(market);
+            match market {
+                Market::Spot => client.spot_amend_order(&mut params).await,
+                Market::Linear => client.linear_amend_order(&mut params).await,
+                Market::Option => client.option_amend_order(&mut params).await,
+            }
+        }
+        OrdersCmd::Cancel {
+            currency,
+            instrument,
+            order_id,
+            label,
+            yes,
+        } => {
+            if order_id.is_none() && label.is_none() && instrument.is_none() {
+                confirm(
+                    &format!("cancel every open {:?} order in {}", market, currency),
+                    yes,
+                )?;
+            }
+            let mut params = CancelRequest {
+                currency,
+                instrument_id: instrument,
+                order_id,
+                label,
+            }
+            .to_params(market);
+            match market {
+                Market::Spot => client.spot_cancel_order(&mut params).await,
+                Market::Linear => client.linear_cancel_order(&mut params).await,
+                Market::Option => client.option_cancel_order(&mut params).await,
+            }
+        }
+        OrdersCmd::List {
+            currency,
+            instrument,
+            since,
+        } => {
+            let mut params = filter_params(
+                market,
+                Some(&currency),
+                instrument.as_deref(),
+                since.as_deref(),
+            )?;
+            match market {
+                Market::Spot => client.spot_get_orders(&mut params).await,
+                Market::Linear => client.linear_get_orders(&mut params).await,
+                Market::Option => client.option_get_orders(&mut params).await,
+            }
+        }
+        OrdersCmd::Open {
+            currency,
+            instrument,
+        } => {
+            let mut params = filter_params(market, Some(&currency), instrument.as_deref(), None)?;
+            match market {
+                Market::Spot => client.spot_get_open_orders(&mut params).await,
+                Market::Linear => client.linear_get_open_orders(&mut params).await,
+                Market::Option => client.option_get_open_orders(&mut params).await,
+            }
+        }
+        OrdersCmd::BatchNew { file } => {
+            let mut params = read_json(&file)?;
+            match market {
+                Market::Spot => client.spot_batch_new_orders(&mut params).await,
+                Market::Linear => client.linear_batch_new_orders(&mut params).await,
+                Market::Option => client.option_batch_new_orders(&mut params).await,
+            }
+        }
+        OrdersCmd::BatchAmend { file } => {
+            let mut params = read_json(&file)?;
+            match market {
+                Market::Spot => client.spot_batch_amend_orders(&mut params).await,
+                Market::Linear => client.linear_batch_amend_orders(&mut params).await,
+                Market::Option => client.option_batch_amend_orders(&mut params).await,
+            }
+        }
+    };
+    rsp.map_err(|e| e.to_string())
+}
+
+async fn run_block_trades(client: &BitRestClient, cmd: BlockTradeCmd) -> Result<Value, String> {
+    let since = |s: Option<String>| s.as_deref().map(since_ms).transpose();
+    let rsp = match cmd {
+        BlockTradeCmd::New {
+            label,
+            counterparty,
+            role,
+            legs,
+        } => {
+            let role = match role {
+                RoleArg::Taker => BlockTradeRole::Taker,
+                RoleArg::Maker => BlockTradeRole::Maker,
+            };
+            let mut req = BlockTradeRequest::new(&label, &counterparty, role);
+            req.legs = serde_json::from_value(read_json(&legs)?).map_err(|e| e.to_string())?;
+            req.validate(&BlockTradeLimits::default())
+                .map_err(|e| e.to_string())?;
+            client.linear_new_block_trade(&mut req.to_params()).await
+        }
+        BlockTradeCmd::List { currency, since: s } => {
+            let mut query = BlockTradeQuery::new(&currency);
+            query.start_time = since(s)?;
+            client.linear_get_block_trades(&mut query.to_params()).await
+        }
+        BlockTradeCmd::Platform {
+            currency,
+            instrument,
+            since: s,
+        } => {
+            let mut query = BlockTradeQuery::new(&currency);
+            query.instrument_id = instrument;
+            query.start_time = since(s)?;
+            let trades = block_trade::platform_block_trades(client, &query)
+                .await
+                .map_err(|e| e.to_string())?;
+            // same envelope as the raw endpoints so every output format works
+            return Ok(serde_json::json!({ "code": 0, "data": trades }));
+        }
+    };
+    rsp.map_err(|e| e.to_string())
+}
+
+fn history_params(currency: Option<String>, since: Option<&str>) -> Result<Value, String> {
+    let query = HistoryQuery {
+        currency,
+        start_time: since.map(since_ms).transpose()?,
+        ..Default::default()
+    };
+    Ok(query.to_params())
+}
+
+async fn run_pnl(
+    client: &BitRestClient,
+    builder: PnlReportBuilder,
+    currency: &str,
+    out: Option<PathBuf>,
+) -> Result<Value, String> {
+    let report = pnl::daily_report(client, builder, currency)
+        .await
+        .map_err(|e| e.to_string())?;
+    if let Some(path) = &out {
+        let written = match path.extension().and_then(|e| e.to_str()) {
+            Some("csv") => std::fs::File::create(path).and_then(|f| report.write_csv(f)),
+            Some("md") => std::fs::write(path, report.to_markdown()),
+            _ => return Err(format!("{}: expected a .csv or .md file", path.display())),
+        };
+        written.map_err(|e| format!("{}: {}", path.display(), e))?;
+    }
+    let totals: Vec<Value> = report
+        .daily_usd()
+        .iter()
+        .map(|(date, t)| {
+            serde_json::json!({
+                "date": date.to_string(),
+                "realized": t.realized,
+                "unrealized_change": t.unrealized_change,
+                "funding": t.funding,
+                "fees": t.fees,
+                "net": t.net(),
+            })
+        })
+        .collect();
+    Ok(serde_json::json!({ "code": 0, "data": totals }))
+}
+
+async fn run_export(
+    client: &BitRestClient,
+    source: WalletArg,
+    from: NaiveDate,
+    to: NaiveDate,
+    currency: Option<String>,
+    out: PathBuf,
+) -> Result<Value, String> {
+    let day_ms = |d: NaiveDate| {
+        d.and_time(chrono::NaiveTime::MIN)
+            .and_utc()
+            .timestamp_millis()
+    };
+    let source = match source {
+        WalletArg::Spot => LedgerSource::Spot,
+        WalletArg::Um => LedgerSource::Um,
+    };
+    let mut query = LedgerQuery::new(source, day_ms(from), day_ms(to));
+    query.currency = currency;
+    let records = ledger::fetch_ledger(client, &query)
+        .await
+        .map_err(|e| e.to_string())?;
+    ledger::write_file(&out, &records).map_err(|e| e.to_string())?;
+    Ok(serde_json::json!({
+        "code": 0,
+        "data": { "file": out.display().to_string(), "records": records.len() },
+    }))
+}
+
+async fn run_wallet(client: &BitRestClient, cmd: WalletCmd) -> Result<Value, String> {
+    let rsp = match cmd {
+        WalletCmd::DepositAddress { currency, chain } => {
+            let mut params = serde_json::json!({ "currency": currency, "chain": chain });
+            client.get_deposit_address(&mut params).await
+        }
+        WalletCmd::Deposits { currency, since } => {
+            let mut params = history_params(currency, since.as_deref())?;
+            client.get_deposits(&mut params).await
+        }
+        WalletCmd::Withdraw {
+            currency,
+            chain,
+            address,
+            amount,
+            memo,
+            client_id,
+            yes,
+        } => {
+            let mut req = WithdrawRequest::new(&currency, &chain, &address, amount);
+            req.memo = memo;
+            req.client_id = client_id;
+            req.validate().map_err(|e| e.to_string())?;
+            confirm(
+                &format!(
+                    "withdraw {} {} to {} on {}",
+                    amount, currency, address, chain
+                ),
+                yes,
+            )?;
+            client.withdraw(&mut req.to_params()).await
+        }
+        WalletCmd::Withdrawals { currency, since } => {
+            let mut params = history_params(currency, since.as_deref())?;
+            client.get_withdrawals(&mut params).await
+        }
+        WalletCmd::WithdrawFee { currency, chain } => {
+            let mut params = serde_json::json!({ "currency": currency, "chain": chain });
+            client.get_withdraw_fee(&mut params).await
+        }
+        WalletCmd::Transfer {
+            currency,
+            amount,
+            from,
+            to,
+        } => {
+            let req = TransferRequest::new(&currency, amount, from.into(), to.into());
+            req.validate().map_err(|e| e.to_string())?;
+            client.transfer(&mut req.to_params()).await
+        }
+        WalletCmd::SubTransfer {
+            currency,
+            amount,
+            from_uid,
+            to_uid,
+        } => {
+            let req = SubAccountTransferRequest::new(&currency, amount, &from_uid, &to_uid);
+            req.validate().map_err(|e| e.to_string())?;
+            client.subaccount_transfer(&mut req.to_params()).await
+        }
+        WalletCmd::Transfers { currency, since } => {
+            let mut params = history_params(currency, since.as_deref())?;
+            client.get_transfers(&mut params).await
+        }
+    };
+    rsp.map_err(|e| e.to_string())
+}
+
+async fn run_mmp(client: &BitRestClient, market: Market, cmd: MmpCmd) -> Result<Value, String> {
+    // option MMP is per currency, --pair BTC
+    let key = match market {
+        Market::Spot | Market::Linear => "pair",
+        Market::Option => "currency",
+    };
+    let rsp = match cmd {
+        MmpCmd::State { pair } => {
+            let mut params = serde_json::json!({ key: pair });
+            match market {
+                Market::Spot => client.spot_get_mmp_state(&mut params).await,
+                Market::Linear => client.linear_get_mmp_state(&mut params).await,
+                Market::Option => client.option_get_mmp_state(&mut params).await,
+            }
+        }
+        MmpCmd::Reset { pair } => {
+            let mut params = serde_json::json!({ key: pair });
+            match market {
+                Market::Spot => client.spot_reset_mmp(&mut params).await,
+                Market::Linear => client.linear_reset_mmp(&mut params).await,
+                Market::Option => client.option_reset_mmp(&mut params).await,
+            }
+        }
+        MmpCmd::Update {
+            pair,
+            window_ms,
+            frozen_ms,
+            qty_limit,
+            delta_limit,
+        } => {
+            let mut params = serde_json::json!({
+                key: pair,
+                "window_ms": window_ms,
+                "frozen_ms": frozen_ms,
+                "qty_limit": qty_limit.to_string(),
+                "delta_limit": delta_limit.to_string(),
+            });
+            match market {
+                Market::Spot => client.spot_update_mmp_config(&mut params).await,
+                Market::Linear => client.linear_update_mmp_config(&mut params).await,
+                Market::Option => client.option_update_mmp_config(&mut params).await,
+            }
+        }
+    };
+    rsp.map_err(|e| e.to_string())
+}
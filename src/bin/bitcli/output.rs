@@ -0,0 +1,118 @@
+// Render API responses as a table, JSON or CSV.
+
+use clap::ValueEnum;
+use serde_json::Value;
+use std::collections::BTreeSet;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    Table,
+    Json,
+    Csv,
+}
+
+pub fn render(rsp: &Value, format: OutputFormat) -> String {
+    if format == OutputFormat::Json {
+        return serde_json::to_string_pretty(rsp).unwrap();
+    }
+    // show the payload, fall back to the whole response on errors
+    let data = match &rsp["data"] {
+        Value::Null => rsp,
+        data => data,
+    };
+    let (headers, rows) = to_rows(data);
+    match format {
+        OutputFormat::Csv => render_csv(&headers, &rows),
+        _ => render_table(&headers, &rows),
+    }
+}
+
+/// A list of objects becomes one row per object, a single object becomes key/value rows.
+fn to_rows(data: &Value) -> (Vec<String>, Vec<Vec<String>>) {
+    let list = match data {
+        Value::Array(items) => items.clone(),
+        Value::Object(map) => match map.values().find(|v| v.is_array()) {
+            // paged responses wrap the list, e.g. {"orders": [...], "next_cursor": ...}
+            Some(Value::Array(items)) if map.len() <= 3 => items.clone(),
+            _ => {
+                let rows = map.iter().map(|(k, v)| vec![k.clone(), cell(v)]).collect();
+                return (vec!["key".to_string(), "value".to_string()], rows);
+            }
+        },
+        v => return (vec!["value".to_string()], vec![vec![cell(v)]]),
+    };
+
+    let mut headers = Vec::new();
+    let mut seen = BTreeSet::new();
+    for item in &list {
+        if let Value::Object(map) = item {
+            for k in map.keys() {
+                if seen.insert(k.clone()) {
+                    headers.push(k.clone());
+                }
+            }
+        }
+    }
+    if headers.is_empty() {
+        let rows = list.iter().map(|v| vec![cell(v)]).collect();
+        return (vec!["value".to_string()], rows);
+    }
+    let rows = list
+        .iter()
+        .map(|item| headers.iter().map(|h| cell(&item[h])).collect())
+        .collect();
+    (headers, rows)
+}
+
+fn cell(value: &Value) -> String {
+    match value {
+        Value::Null => String::new(),
+        Value::String(s) => s.clone(),
+        v => v.to_string(),
+    }
+}
+
+fn render_table(headers: &[String], rows: &[Vec<String>]) -> String {
+    let mut widths: Vec<usize> = headers.iter().map(|h| h.len()).collect();
+    for row in rows {
+        for (i, c) in row.iter().enumerate() {
+            widths[i] = widths[i].max(c.chars().count());
+        }
+    }
+    let line = |cells: &[String]| {
+        cells
+            .iter()
+            .enumerate()
+            .map(|(i, c)| format!("{:<width$}", c, width = widths[i]))
+            .collect::<Vec<_>>()
+            .join("  ")
+            .trim_end()
+            .to_string()
+    };
+    let mut out = vec![line(headers)];
+    out.push(
+        widths
+            .iter()
+            .map(|w| "-".repeat(*w))
+            .collect::<Vec<_>>()
+            .join("  "),
+    );
+    out.extend(rows.iter().map(|r| line(r)));
+    out.join("\n")
+}
+
+fn render_csv(headers: &[String], rows: &[Vec<String>]) -> String {
+    let escape = |c: &String| {
+        if c.contains([',', '"', '\n']) {
+            format!("\"{}\"", c.replace('"', "\"\""))
+        } else {
+            c.clone()
+        }
+    };
+    let mut out = vec![headers.iter().map(escape).collect::<Vec<_>>().join(",")];
+    out.extend(
+        rows.iter()
+            .map(|r| r.iter().map(escape).collect::<Vec<_>>().join(",")),
+    );
+    out.join("\n")
+}
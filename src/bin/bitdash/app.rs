@@ -0,0 +1,141 @@
+// Dashboard state, updated from REST snapshots and WebSocket events.
+
+use bitsdk_rust::bit_ws_client::WsEvent;
+use bitsdk_rust::models::{Order, Position, Ticker, Trade, UmAccount};
+use bitsdk_rust::order_book::OrderBook;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+
+const MAX_FILLS: usize = 100;
+
+pub enum Confirm {
+    CancelOne(Order),
+    CancelAll,
+}
+
+pub struct App {
+    pub um_account: UmAccount,
+    pub orders: BTreeMap<String, Order>,
+    pub positions: BTreeMap<String, Position>,
+    pub fills: VecDeque<Trade>,
+    pub tickers: BTreeMap<String, Ticker>,
+    pub books: HashMap<String, OrderBook>,
+    pub watchlist: Vec<String>,
+    pub ladder_index: usize,
+    pub selected_order: usize,
+    pub confirm: Option<Confirm>,
+    pub status: String,
+    pub private_connected: bool,
+    pub public_connected: bool,
+}
+
+impl App {
+    pub fn new(watchlist: Vec<String>) -> Self {
+        Self {
+            um_account: UmAccount::default(),
+            orders: BTreeMap::new(),
+            positions: BTreeMap::new(),
+            fills: VecDeque::new(),
+            tickers: BTreeMap::new(),
+            books: HashMap::new(),
+            watchlist,
+            ladder_index: 0,
+            selected_order: 0,
+            confirm: None,
+            status: "loading".to_string(),
+            private_connected: false,
+            public_connected: false,
+        }
+    }
+
+    pub fn on_private(&mut self, event: WsEvent) {
+        match event {
+            WsEvent::UmAccount(account) => self.um_account = account,
+            WsEvent::Orders(orders) => self.set_orders(orders),
+            WsEvent::Positions(positions) => self.set_positions(positions),
+            WsEvent::UserTrades(trades) => self.add_fills(trades),
+            WsEvent::Connected => self.private_connected = true,
+            WsEvent::Disconnected { reason } => {
+                self.private_connected = false;
+                self.status = format!("private stream disconnected: {}", reason);
+            }
+            _ => {}
+        }
+    }
+
+    pub fn on_public(&mut self, event: WsEvent) {
+        match event {
+            WsEvent::Ticker(ticker) => {
+                if let Some(pos) = self.positions.get_mut(&ticker.instrument_id)
+                    && ticker.mark_price > 0.0
+                {
+                    pos.mark_price = ticker.mark_price;
+                    pos.position_pnl = (ticker.mark_price - pos.avg_price) * pos.qty;
+                }
+                self.tickers.insert(ticker.instrument_id.clone(), ticker);
+            }
+            WsEvent::Depth(depth) => self
+                .books
+                .entry(depth.instrument_id.clone())
+                .or_insert_with(|| OrderBook::new(&depth.instrument_id))
+                .apply(&depth),
+            WsEvent::Connected => self.public_connected = true,
+            WsEvent::Disconnected { reason } => {
+                self.public_connected = false;
+                self.status = format!("market data disconnected: {}", reason);
+            }
+            _ => {}
+        }
+    }
+
+    pub fn set_orders(&mut self, orders: Vec<Order>) {
+        for order in orders {
+            if order.is_open() {
+                self.orders.insert(order.order_id.clone(), order);
+            } else {
+                self.orders.remove(&order.order_id);
+            }
+        }
+        self.selected_order = self.selected_order.min(self.orders.len().saturating_sub(1));
+    }
+
+    pub fn set_positions(&mut self, positions: Vec<Position>) {
+        for pos in positions {
+            if pos.qty == 0.0 {
+                self.positions.remove(&pos.instrument_id);
+            } else {
+                self.positions.insert(pos.instrument_id.clone(), pos);
+            }
+        }
+    }
+
+    pub fn add_fills(&mut self, trades: Vec<Trade>) {
+        for trade in trades {
+            self.fills.push_front(trade);
+        }
+        self.fills.truncate(MAX_FILLS);
+    }
+
+    pub fn selected(&self) -> Option<&Order> {
+        self.orders.values().nth(self.selected_order)
+    }
+
+    pub fn select_next(&mut self) {
+        if self.selected_order + 1 < self.orders.len() {
+            self.selected_order += 1;
+        }
+    }
+
+    pub fn select_prev(&mut self) {
+        self.selected_order = self.selected_order.saturating_sub(1);
+    }
+
+    pub fn next_ladder(&mut self) {
+        if !self.watchlist.is_empty() {
+            self.ladder_index = (self.ladder_index + 1) % self.watchlist.len();
+        }
+    }
+
+    pub fn ladder_instrument(&self) -> Option<&str> {
+        self.watchlist.get(self.ladder_index).map(String::as_str)
+    }
+}
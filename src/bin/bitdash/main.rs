@@ -0,0 +1,171 @@
+// bitdash: terminal dashboard for the UM account, positions, open orders, fills and a
+// watchlist of tickers and order books.
+//
+//     BITCOM_REST_HOST=... BITCOM_WS_HOST=... BITCOM_AK=... BITCOM_SK=... \
+//         bitdash BTC-USDT-PERPETUAL ETH-USDT-PERPETUAL
+
+mod app;
+mod ui;
+
+use app::{App, Confirm};
+use bitsdk_rust::bit_rest_client::BitRestClient;
+use bitsdk_rust::bit_ws_client::{BitWsClient, WsSubscription};
+use bitsdk_rust::error::BitResult;
+use bitsdk_rust::models::{CancelRequest, Market, settle_currency};
+use bitsdk_rust::trading::TradingApi;
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use std::env;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::mpsc;
+
+const CURRENCY: &str = "USDT";
+
+#[tokio::main]
+async fn main() {
+    let api_host = env::var("BITCOM_REST_HOST").expect("BITCOM_REST_HOST");
+    let ws_host = env::var("BITCOM_WS_HOST").expect("BITCOM_WS_HOST");
+    let access_key = env::var("BITCOM_AK").expect("BITCOM_AK");
+    let private_key = env::var("BITCOM_SK").expect("BITCOM_SK");
+    let client = Arc::new(BitRestClient::new(&access_key, &private_key, &api_host));
+
+    let mut watchlist: Vec<String> = env::args().skip(1).collect();
+    if watchlist.is_empty() {
+        watchlist.push("BTC-USDT-PERPETUAL".to_string());
+    }
+    let watch_refs: Vec<&str> = watchlist.iter().map(String::as_str).collect();
+
+    let mut private = BitWsClient::new(&ws_host)
+        .with_auth(client.clone())
+        .subscribe(WsSubscription::private(&[
+            "um_account",
+            "order",
+            "user_trade",
+            "position",
+        ]))
+        .spawn();
+    let mut public = BitWsClient::new(&ws_host)
+        .subscribe(WsSubscription::public(&["ticker", "depth"], &watch_refs))
+        .spawn();
+
+    // crossterm input is blocking, read it on its own thread
+    let (key_tx, mut keys) = mpsc::channel(64);
+    std::thread::spawn(move || {
+        while let Ok(event) = event::read() {
+            if key_tx.blocking_send(event).is_err() {
+                return;
+            }
+        }
+    });
+
+    let mut app = App::new(watchlist.clone());
+    if let Err(e) = refresh(&client, &mut app).await {
+        app.status = format!("snapshot failed: {}", e);
+    }
+
+    let mut terminal = ratatui::init();
+    let mut redraw = tokio::time::interval(Duration::from_millis(200));
+    loop {
+        tokio::select! {
+            Some(event) = private.recv() => app.on_private(event),
+            Some(event) = public.recv() => app.on_public(event),
+            Some(event) = keys.recv() => {
+                if !on_input(&client, &mut app, event).await {
+                    break;
+                }
+            }
+            _ = redraw.tick() => {
+                terminal.draw(|frame| ui::draw(frame, &app)).ok();
+            }
+        }
+    }
+    ratatui::restore();
+}
+
+async fn refresh(client: &BitRestClient, app: &mut App) -> BitResult<()> {
+    app.um_account = client.um_account().await?;
+    app.orders.clear();
+    app.set_orders(client.open_orders(Market::Linear, CURRENCY, None).await?);
+    app.positions.clear();
+    app.set_positions(client.positions(CURRENCY).await?);
+    let mut fills = client.user_trades(Market::Linear, CURRENCY, None).await?;
+    fills.sort_by_key(|t| t.created_at);
+    app.fills.clear();
+    app.add_fills(fills);
+    app.status = "snapshot loaded".to_string();
+    Ok(())
+}
+
+/// Returns false to quit.
+async fn on_input(client: &BitRestClient, app: &mut App, event: Event) -> bool {
+    let Event::Key(key) = event else {
+        return true;
+    };
+    if key.kind != KeyEventKind::Press {
+        return true;
+    }
+
+    if let Some(confirm) = app.confirm.take() {
+        if key.code == KeyCode::Char('y') {
+            let result = match &confirm {
+                Confirm::CancelOne(order) => {
+                    client
+                        .cancel_order(
+                            Market::of_instrument(&order.instrument_id),
+                            &CancelRequest::by_order_id(&order.instrument_id, &order.order_id),
+                        )
+                        .await
+                }
+                Confirm::CancelAll => cancel_all(client, app).await,
+            };
+            app.status = match result {
+                Ok(n) => format!("cancelled {} order(s)", n),
+                Err(e) => format!("cancel failed: {}", e),
+            };
+        } else {
+            app.status = "cancel aborted".to_string();
+        }
+        return true;
+    }
+
+    match key.code {
+        KeyCode::Char('q') | KeyCode::Esc => return false,
+        KeyCode::Up | KeyCode::Char('k') => app.select_prev(),
+        KeyCode::Down | KeyCode::Char('j') => app.select_next(),
+        KeyCode::Tab => app.next_ladder(),
+        KeyCode::Char('c') => {
+            app.confirm = app.selected().cloned().map(Confirm::CancelOne);
+        }
+        KeyCode::Char('C') if !app.orders.is_empty() => app.confirm = Some(Confirm::CancelAll),
+        KeyCode::Char('r') => {
+            if let Err(e) = refresh(client, app).await {
+                app.status = format!("refresh failed: {}", e);
+            }
+        }
+        _ => {}
+    }
+    true
+}
+
+/// Cancel every open order shown, one request per market and currency.
+async fn cancel_all(client: &BitRestClient, app: &App) -> BitResult<i64> {
+    let mut groups: Vec<(Market, &str)> = app
+        .orders
+        .values()
+        .map(|o| {
+            (
+                Market::of_instrument(&o.instrument_id),
+                settle_currency(&o.instrument_id),
+            )
+        })
+        .collect();
+    groups.sort_by_key(|&(m, c)| (m as u8, c));
+    groups.dedup();
+    let mut cancelled = 0;
+    for (market, currency) in groups {
+        cancelled += client
+            .cancel_order(market, &CancelRequest::all(currency))
+            .await?;
+    }
+    Ok(cancelled)
+}
@@ -0,0 +1,279 @@
+// Dashboard layout and widgets.
+
+use crate::app::{App, Confirm};
+use bitsdk_rust::models::Side;
+use ratatui::Frame;
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, Borders, Cell, Paragraph, Row, Table, TableState};
+
+const LADDER_DEPTH: usize = 10;
+
+pub fn draw(frame: &mut Frame, app: &App) {
+    let [account, middle, lower, fills, footer] = Layout::vertical([
+        Constraint::Length(3),
+        Constraint::Percentage(30),
+        Constraint::Percentage(40),
+        Constraint::Min(5),
+        Constraint::Length(1),
+    ])
+    .areas(frame.area());
+    let [positions, watchlist] =
+        Layout::horizontal([Constraint::Percentage(55), Constraint::Percentage(45)]).areas(middle);
+    let [orders, ladder] =
+        Layout::horizontal([Constraint::Percentage(55), Constraint::Percentage(45)]).areas(lower);
+
+    draw_account(frame, app, account);
+    draw_positions(frame, app, positions);
+    draw_watchlist(frame, app, watchlist);
+    draw_orders(frame, app, orders);
+    draw_ladder(frame, app, ladder);
+    draw_fills(frame, app, fills);
+    draw_footer(frame, app, footer);
+}
+
+fn side_style(side: Side) -> Style {
+    match side {
+        Side::Buy => Style::default().fg(Color::Green),
+        Side::Sell => Style::default().fg(Color::Red),
+    }
+}
+
+fn pnl_style(pnl: f64) -> Style {
+    if pnl >= 0.0 {
+        Style::default().fg(Color::Green)
+    } else {
+        Style::default().fg(Color::Red)
+    }
+}
+
+fn header(cells: &[&'static str]) -> Row<'static> {
+    Row::new(cells.to_vec()).style(Style::default().add_modifier(Modifier::BOLD))
+}
+
+fn draw_account(frame: &mut Frame, app: &App, area: ratatui::layout::Rect) {
+    let a = &app.um_account;
+    let line = Line::from(vec![
+        Span::raw(format!("Equity {:.2}   ", a.total_margin_balance)),
+        Span::raw(format!("Collateral {:.2}   ", a.total_collateral)),
+        Span::raw(format!("Available {:.2}   ", a.total_available)),
+        Span::raw(format!("IM {:.2}   ", a.total_initial_margin)),
+        Span::raw(format!("MM {:.2}   ", a.total_maintenance_margin)),
+        Span::styled(
+            format!("PnL {:.2}", a.total_position_pnl),
+            pnl_style(a.total_position_pnl),
+        ),
+    ]);
+    let title = format!(
+        "UM account  [private {}  market {}]",
+        if app.private_connected { "up" } else { "down" },
+        if app.public_connected { "up" } else { "down" },
+    );
+    frame.render_widget(
+        Paragraph::new(line).block(Block::default().borders(Borders::ALL).title(title)),
+        area,
+    );
+}
+
+fn draw_positions(frame: &mut Frame, app: &App, area: ratatui::layout::Rect) {
+    let rows = app.positions.values().map(|p| {
+        Row::new(vec![
+            Cell::from(p.instrument_id.clone()),
+            Cell::from(format!("{}", p.qty)).style(if p.qty >= 0.0 {
+                side_style(Side::Buy)
+            } else {
+                side_style(Side::Sell)
+            }),
+            Cell::from(format!("{:.2}", p.avg_price)),
+            Cell::from(format!("{:.2}", p.mark_price)),
+            Cell::from(format!("{:.2}", p.position_pnl)).style(pnl_style(p.position_pnl)),
+            Cell::from(format!("{:.2}", p.liq_price)),
+        ])
+    });
+    let table = Table::new(
+        rows,
+        [
+            Constraint::Min(20),
+            Constraint::Length(10),
+            Constraint::Length(12),
+            Constraint::Length(12),
+            Constraint::Length(12),
+            Constraint::Length(12),
+        ],
+    )
+    .header(header(&["Instrument", "Qty", "Avg", "Mark", "PnL", "Liq"]))
+    .block(Block::default().borders(Borders::ALL).title("Positions"));
+    frame.render_widget(table, area);
+}
+
+fn draw_watchlist(frame: &mut Frame, app: &App, area: ratatui::layout::Rect) {
+    let rows = app.watchlist.iter().map(|inst| {
+        let t = app.tickers.get(inst).cloned().unwrap_or_default();
+        Row::new(vec![
+            inst.clone(),
+            format!("{}", t.best_bid),
+            format!("{}", t.best_ask),
+            format!("{}", t.last_price),
+            format!("{}", t.mark_price),
+        ])
+    });
+    let table = Table::new(
+        rows,
+        [
+            Constraint::Min(20),
+            Constraint::Length(11),
+            Constraint::Length(11),
+            Constraint::Length(11),
+            Constraint::Length(11),
+        ],
+    )
+    .header(header(&["Instrument", "Bid", "Ask", "Last", "Mark"]))
+    .block(Block::default().borders(Borders::ALL).title("Watchlist"));
+    frame.render_widget(table, area);
+}
+
+fn draw_orders(frame: &mut Frame, app: &App, area: ratatui::layout::Rect) {
+    let rows = app.orders.values().map(|o| {
+        Row::new(vec![
+            Cell::from(o.order_id.clone()),
+            Cell::from(o.instrument_id.clone()),
+            Cell::from(format!("{:?}", o.side)).style(side_style(o.side)),
+            Cell::from(format!("{}", o.price)),
+            Cell::from(format!("{}/{}", o.filled_qty, o.qty)),
+            Cell::from(o.label.clone()),
+        ])
+    });
+    let table = Table::new(
+        rows,
+        [
+            Constraint::Length(12),
+            Constraint::Min(20),
+            Constraint::Length(5),
+            Constraint::Length(11),
+            Constraint::Length(12),
+            Constraint::Length(12),
+        ],
+    )
+    .header(header(&[
+        "Id",
+        "Instrument",
+        "Side",
+        "Price",
+        "Filled",
+        "Label",
+    ]))
+    .row_highlight_style(Style::default().add_modifier(Modifier::REVERSED))
+    .block(
+        Block::default()
+            .borders(Borders::ALL)
+            .title(format!("Open orders ({})", app.orders.len())),
+    );
+    let mut state = TableState::default().with_selected(Some(app.selected_order));
+    frame.render_stateful_widget(table, area, &mut state);
+}
+
+fn draw_ladder(frame: &mut Frame, app: &App, area: ratatui::layout::Rect) {
+    let instrument = app.ladder_instrument().unwrap_or("");
+    let mut rows = Vec::new();
+    if let Some(book) = app.books.get(instrument) {
+        let asks: Vec<(f64, f64)> = book.asks().take(LADDER_DEPTH).collect();
+        for (price, qty) in asks.into_iter().rev() {
+            rows.push(
+                Row::new(vec![
+                    String::new(),
+                    format!("{}", price),
+                    format!("{}", qty),
+                ])
+                .style(side_style(Side::Sell)),
+            );
+        }
+        for (price, qty) in book.bids().take(LADDER_DEPTH) {
+            rows.push(
+                Row::new(vec![
+                    format!("{}", qty),
+                    format!("{}", price),
+                    String::new(),
+                ])
+                .style(side_style(Side::Buy)),
+            );
+        }
+    }
+    let table = Table::new(
+        rows,
+        [
+            Constraint::Percentage(33),
+            Constraint::Percentage(34),
+            Constraint::Percentage(33),
+        ],
+    )
+    .header(header(&["Bid qty", "Price", "Ask qty"]))
+    .block(
+        Block::default()
+            .borders(Borders::ALL)
+            .title(format!("Book {}  [tab]", instrument)),
+    );
+    frame.render_widget(table, area);
+}
+
+fn draw_fills(frame: &mut Frame, app: &App, area: ratatui::layout::Rect) {
+    let rows = app.fills.iter().map(|t| {
+        Row::new(vec![
+            Cell::from(
+                chrono::DateTime::from_timestamp_millis(t.created_at)
+                    .map(|d| d.format("%H:%M:%S%.3f").to_string())
+                    .unwrap_or_default(),
+            ),
+            Cell::from(t.instrument_id.clone()),
+            Cell::from(format!("{:?}", t.side)).style(side_style(t.side)),
+            Cell::from(format!("{}", t.price)),
+            Cell::from(format!("{}", t.qty)),
+            Cell::from(format!("{:.4}", t.fee)),
+            Cell::from(if t.is_taker { "taker" } else { "maker" }),
+        ])
+    });
+    let table = Table::new(
+        rows,
+        [
+            Constraint::Length(13),
+            Constraint::Min(20),
+            Constraint::Length(5),
+            Constraint::Length(11),
+            Constraint::Length(10),
+            Constraint::Length(10),
+            Constraint::Length(6),
+        ],
+    )
+    .header(header(&[
+        "Time",
+        "Instrument",
+        "Side",
+        "Price",
+        "Qty",
+        "Fee",
+        "",
+    ]))
+    .block(Block::default().borders(Borders::ALL).title("Recent fills"));
+    frame.render_widget(table, area);
+}
+
+fn draw_footer(frame: &mut Frame, app: &App, area: ratatui::layout::Rect) {
+    let line = match &app.confirm {
+        Some(Confirm::CancelOne(order)) => Line::styled(
+            format!(
+                "Cancel order {} {} {:?} {}@{}? [y/n]",
+                order.order_id, order.instrument_id, order.side, order.qty, order.price
+            ),
+            Style::default().fg(Color::Yellow),
+        ),
+        Some(Confirm::CancelAll) => Line::styled(
+            format!("Cancel ALL {} open orders? [y/n]", app.orders.len()),
+            Style::default().fg(Color::Yellow),
+        ),
+        None => Line::from(format!(
+            "q quit  up/down select  c cancel order  C cancel all  tab next book  r refresh | {}",
+            app.status
+        )),
+    };
+    frame.render_widget(Paragraph::new(line), area);
+}
@@ -0,0 +1,108 @@
+// bitload: order round-trip load generator against a local mock exchange.
+//
+//     bitload --requests 20000 --concurrency 1,8,64
+//
+// Starts a `MockExchange` on localhost and sends limit orders through `BitRestClient`'s typed
+// API at each concurrency level, timing each call from building the request to the parsed
+// order: parameters, signing, headers, HTTP and JSON decoding.
+
+use bitsdk_rust::bit_rest_client::BitRestClient;
+use bitsdk_rust::latency::LatencyTracker;
+use bitsdk_rust::mock::MockExchange;
+use bitsdk_rust::models::{Market, OrderRequest, Side};
+use bitsdk_rust::trading::TradingApi;
+use clap::Parser;
+use std::process;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Instant;
+
+#[derive(Parser)]
+#[command(
+    name = "bitload",
+    about = "Order round-trip load test against a local mock exchange"
+)]
+struct Args {
+    /// Orders per concurrency level.
+    #[arg(long, default_value_t = 10_000)]
+    requests: u64,
+    /// Comma-separated numbers of concurrent senders.
+    #[arg(long, value_delimiter = ',', default_value = "1,4,16,64")]
+    concurrency: Vec<usize>,
+    #[arg(long, default_value = "BTC-USDT-PERPETUAL")]
+    instrument: String,
+}
+
+#[tokio::main]
+async fn main() {
+    let args = Args::parse();
+    let mock = MockExchange::start("127.0.0.1:0")
+        .await
+        .unwrap_or_else(|e| {
+            eprintln!("{}", e);
+            process::exit(1);
+        });
+    let client = Arc::new(BitRestClient::new("bench-ak", "bench-sk", &mock.url()));
+    let req = OrderRequest::limit(&args.instrument, Side::Buy, 1.0, 20000.0);
+
+    // warm up the connection pool
+    for _ in 0..100 {
+        let _ = client.new_order(Market::Linear, &req).await;
+    }
+
+    println!(
+        "{:>11} {:>9} {:>10} {:>9} {:>9} {:>9} {:>9}",
+        "concurrency", "orders", "orders/s", "p50 ms", "p90 ms", "p99 ms", "max ms"
+    );
+    for &concurrency in &args.concurrency {
+        let tracker = Arc::new(LatencyTracker::new(args.requests as usize));
+        let remaining = Arc::new(AtomicU64::new(args.requests));
+        let errors = Arc::new(AtomicU64::new(0));
+        let started = Instant::now();
+        let workers: Vec<_> = (0..concurrency.max(1))
+            .map(|_| {
+                let (client, req) = (client.clone(), req.clone());
+                let (tracker, remaining, errors) =
+                    (tracker.clone(), remaining.clone(), errors.clone());
+                tokio::spawn(async move {
+                    while remaining
+                        .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |n| n.checked_sub(1))
+                        .is_ok()
+                    {
+                        let sent = Instant::now();
+                        match client.new_order(Market::Linear, &req).await {
+                            Ok(_) => tracker.record_rest("new_order", sent.elapsed()),
+                            Err(_) => {
+                                errors.fetch_add(1, Ordering::Relaxed);
+                            }
+                        }
+                    }
+                })
+            })
+            .collect();
+        for worker in workers {
+            let _ = worker.await;
+        }
+        let elapsed = started.elapsed().as_secs_f64();
+
+        let stats = tracker
+            .summary()
+            .endpoints
+            .remove("new_order")
+            .unwrap_or_default();
+        println!(
+            "{:>11} {:>9} {:>10.0} {:>9.3} {:>9.3} {:>9.3} {:>9.3}",
+            concurrency,
+            stats.count,
+            stats.count as f64 / elapsed,
+            stats.p50_ms,
+            stats.p90_ms,
+            stats.p99_ms,
+            stats.max_ms
+        );
+        let errors = errors.load(Ordering::Relaxed);
+        if errors > 0 {
+            println!("{:>11} {} orders failed", "", errors);
+        }
+    }
+}
@@ -0,0 +1,51 @@
+// bitsdk-recorder: record public bit.com market data to rolling hourly files.
+//
+//     bitsdk-recorder recorder.toml
+//
+// See `RecorderConfig` for the config format.
+
+use bitsdk_rust::recorder::{Recorder, RecorderConfig};
+use std::env;
+use std::process;
+
+#[tokio::main]
+async fn main() {
+    let path = env::args().nth(1).unwrap_or("recorder.toml".to_string());
+    let config = RecorderConfig::load(&path).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        process::exit(1);
+    });
+
+    println!(
+        "Recording {} subscriptions from {} into {}",
+        config.subscriptions.len(),
+        config.ws_url,
+        config.output_dir.display()
+    );
+
+    let mut recorder = Recorder::new(&config.output_dir, &config.formats);
+    let mut frames = config.ws_client().spawn_raw();
+
+    loop {
+        tokio::select! {
+            frame = frames.recv() => {
+                let Some(frame) = frame else {
+                    break;
+                };
+                if let Err(e) = recorder.handle(frame) {
+                    eprintln!("write failed: {}", e);
+                    break;
+                }
+            }
+            _ = tokio::signal::ctrl_c() => {
+                println!("Stopping");
+                break;
+            }
+        }
+    }
+
+    if let Err(e) = recorder.close() {
+        eprintln!("close failed: {}", e);
+        process::exit(1);
+    }
+}
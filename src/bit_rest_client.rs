@@ -1,13 +1,23 @@
 // we can't use reqwest::blocking::Client since it's not allowed to work with tokio
 
+use crate::credentials::{CredentialProvider, Credentials};
+use crate::error::{BitError, BitResult, parse_body};
+use crate::latency::{LatencyTracker, Stamp, Timed, Timing, exchange_timestamp};
+use crate::metrics::{self, RestOutcome};
+use crate::models::Param;
+use crate::rate_limit::RateLimiter;
 use chrono::Utc;
 use hmac::{Hmac, Mac};
-use reqwest::Client;
-use reqwest::Method;
-use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use reqwest::header::{CONTENT_TYPE, HeaderMap, HeaderName, HeaderValue};
+use reqwest::{Client, Method, RequestBuilder, Url};
+use serde::de::DeserializeOwned;
+use serde::ser::{SerializeMap, Serializer};
 use serde_json::Value;
 use sha2::Sha256;
-use std::collections::BTreeMap;
+use std::collections::HashMap;
+use std::fmt::{self, Write};
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
 
 type HmacSha256 = Hmac<Sha256>;
 
@@ -38,6 +48,16 @@ pub const V1_UM_ACCOUNT_MODE: &str = "/um/v1/account_mode";
 pub const V1_UM_ACCOUNTS: &str = "/um/v1/accounts";
 pub const V1_UM_TRANSACTIONS: &str = "/um/v1/transactions";
 
+// WALLET
+pub const V1_WALLET_DEPOSIT_ADDRESS: &str = "/v1/wallet/deposit_address";
+pub const V1_WALLET_DEPOSITS: &str = "/v1/wallet/deposits";
+pub const V1_WALLET_WITHDRAW: &str = "/v1/wallet/withdraw";
+pub const V1_WALLET_WITHDRAWALS: &str = "/v1/wallet/withdrawals";
+pub const V1_WALLET_WITHDRAW_FEE: &str = "/v1/wallet/withdraw_fee";
+pub const V1_WALLET_TRANSFER: &str = "/v1/wallet/transfer";
+pub const V1_WALLET_TRANSFERS: &str = "/v1/wallet/transfers";
+pub const V1_SUBACCOUNT_TRANSFER: &str = "/v1/subaccount/transfer";
+
 // LINEAR
 pub const V1_LINEAR_POSITIONS: &str = "/linear/v1/positions";
 pub const V1_LINEAR_ORDERS: &str = "/linear/v1/orders";
@@ -53,71 +73,402 @@ pub const V1_LINEAR_BLOCK_TRADES: &str = "/linear/v1/blocktrades";
 pub const V1_LINEAR_USER_INFO: &str = "/linear/v1/user/info";
 pub const V1_LINEAR_PLATFORM_BLOCK_TRADES: &str = "/linear/v1/platform_blocktrades";
 pub const V1_LINEAR_ACCOUNT_CONFIGS: &str = "/linear/v1/account_configs";
+pub const V1_LINEAR_ACCOUNT_CONFIGS_COD: &str = "/linear/v1/account_configs/cod";
+pub const V1_LINEAR_POSITION_MODE: &str = "/linear/v1/account_configs/position_mode";
 pub const V1_LINEAR_LEVERAGE_RATIO: &str = "/linear/v1/leverage_ratio";
 pub const V1_LINEAR_AGG_POSITIONS: &str = "/linear/v1/aggregated/positions";
 pub const V1_LINEAR_MMP_STATE: &str = "/linear/v1/mmp_state";
 pub const V1_LINEAR_MMP_UPDATE_CONFIG: &str = "/linear/v1/update_mmp_config";
 pub const V1_LINEAR_RESET_MMP: &str = "/linear/v1/reset_mmp";
 
+// OPTION (coin-margined, settled in the underlying)
+pub const V1_OPTION_INSTRUMENTS: &str = "/v1/instruments";
+pub const V1_OPTION_TICKERS: &str = "/v1/tickers";
+pub const V1_OPTION_MARKET_SUMMARY: &str = "/v1/market/summary";
+pub const V1_OPTION_POSITIONS: &str = "/v1/positions";
+pub const V1_OPTION_ORDERS: &str = "/v1/orders";
+pub const V1_OPTION_CANCEL_ORDERS: &str = "/v1/cancel_orders";
+pub const V1_OPTION_OPENORDERS: &str = "/v1/open_orders";
+pub const V1_OPTION_USER_TRADES: &str = "/v1/user/trades";
+pub const V1_OPTION_AMEND_ORDERS: &str = "/v1/amend_orders";
+pub const V1_OPTION_BATCH_ORDERS: &str = "/v1/batchorders";
+pub const V1_OPTION_AMEND_BATCH_ORDERS: &str = "/v1/amend_batchorders";
+pub const V1_OPTION_DELIVERIES: &str = "/v1/deliveries";
+pub const V1_OPTION_SETTLEMENTS: &str = "/v1/settlements";
+pub const V1_OPTION_ACCOUNT_CONFIGS_COD: &str = "/v1/account_configs/cod";
+pub const V1_OPTION_MMP_STATE: &str = "/v1/mmp_state";
+pub const V1_OPTION_MMP_UPDATE_CONFIG: &str = "/v1/update_mmp_config";
+pub const V1_OPTION_RESET_MMP: &str = "/v1/reset_mmp";
+
+// Everything a signed request needs from the credentials, built once per key: the HMAC keyed
+// with the secret, cloned for every signature, and the auth headers.
+struct Signer {
+    credentials: Arc<Credentials>,
+    mac: HmacSha256,
+    headers: HeaderMap,
+}
+
+impl Signer {
+    fn new(credentials: Credentials) -> BitResult<Self> {
+        let mac = HmacSha256::new_from_slice(credentials.secret_key.expose().as_bytes())
+            .expect("HMAC initialization failed");
+        let access_key = HeaderValue::from_str(&credentials.access_key).map_err(|_| {
+            BitError::Config("access key contains characters not allowed in a header".to_string())
+        })?;
+        let mut headers = HeaderMap::new();
+        headers.insert(HeaderName::from_static("x-bit-access-key"), access_key);
+        headers.insert(
+            HeaderName::from_static("language-type"),
+            HeaderValue::from_static("1"),
+        );
+        Ok(Self {
+            credentials: Arc::new(credentials),
+            mac,
+            headers,
+        })
+    }
+
+    /// Hex HMAC-SHA256 of `path&canonical`, the canonical form written by `canonical`.
+    fn sign(&self, path: &str, canonical: impl FnOnce(&mut MacWriter) -> fmt::Result) -> Signature {
+        sign_with(self.mac.clone(), path, canonical)
+    }
+}
+
+// Feeds the canonical form straight into the HMAC, no string is built for it.
+struct MacWriter(HmacSha256);
+
+impl fmt::Write for MacWriter {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.0.update(s.as_bytes());
+        Ok(())
+    }
+}
+
+/// A hex encoded HMAC-SHA256.
+struct Signature([u8; 64]);
+
+impl Signature {
+    fn as_str(&self) -> &str {
+        // hex digits are ASCII
+        std::str::from_utf8(&self.0).unwrap()
+    }
+}
+
+fn sign_with(
+    mac: HmacSha256,
+    path: &str,
+    canonical: impl FnOnce(&mut MacWriter) -> fmt::Result,
+) -> Signature {
+    let mut writer = MacWriter(mac);
+    writer.0.update(path.as_bytes());
+    writer.0.update(b"&");
+    // writing into the HMAC cannot fail
+    let _ = canonical(&mut writer);
+    let mut hex = [0u8; 64];
+    let _ = hex::encode_to_slice(writer.0.finalize().into_bytes(), &mut hex);
+    Signature(hex)
+}
+
+/// The canonical form of a request body: `key=value` joined by `&` with keys sorted, nested
+/// objects and arrays encoded the same way, arrays in brackets.
+fn write_canonical<W: fmt::Write>(out: &mut W, value: &Value) -> fmt::Result {
+    match value {
+        Value::Object(map) => {
+            // keys come sorted unless serde_json's preserve_order feature got enabled
+            if map.keys().is_sorted() {
+                write_members(out, map.iter())
+            } else {
+                let mut members: Vec<_> = map.iter().collect();
+                members.sort_by_key(|(k, _)| *k);
+                write_members(out, members.into_iter())
+            }
+        }
+        Value::Array(arr) => {
+            out.write_char('[')?;
+            for (i, item) in arr.iter().enumerate() {
+                if i > 0 {
+                    out.write_char('&')?;
+                }
+                write_canonical(out, item)?;
+            }
+            out.write_char(']')
+        }
+        _ => write!(out, "{}", value),
+    }
+}
+
+fn write_members<'a, W: fmt::Write>(
+    out: &mut W,
+    members: impl Iterator<Item = (&'a String, &'a Value)>,
+) -> fmt::Result {
+    for (i, (k, v)) in members.enumerate() {
+        if i > 0 {
+            out.write_char('&')?;
+        }
+        write!(out, "{}=", k)?;
+        match v {
+            Value::String(s) => out.write_str(s)?,
+            Value::Array(_) | Value::Object(_) => write_canonical(out, v)?,
+            _ => write!(out, "{}", v)?,
+        }
+    }
+    Ok(())
+}
+
+/// The canonical form of typed fields sorted by key, with `timestamp` merged in.
+fn write_params<'a, W: fmt::Write>(
+    out: &mut W,
+    params: impl Iterator<Item = (&'a str, Param<'a>)>,
+    timestamp: i64,
+) -> fmt::Result {
+    let mut timestamp = Some(timestamp);
+    let mut first = true;
+    for (k, v) in params {
+        if k > "timestamp"
+            && let Some(ts) = timestamp.take()
+        {
+            write!(out, "{}timestamp={}", if first { "" } else { "&" }, ts)?;
+            first = false;
+        }
+        write!(out, "{}{}={}", if first { "" } else { "&" }, k, v)?;
+        first = false;
+    }
+    if let Some(ts) = timestamp {
+        write!(out, "{}timestamp={}", if first { "" } else { "&" }, ts)?;
+    }
+    Ok(())
+}
+
+fn signed_body<'a>(
+    signer: &Signer,
+    path: &str,
+    params: impl Iterator<Item = (&'a str, Param<'a>)> + Clone,
+    timestamp: i64,
+) -> Vec<u8> {
+    let signature = signer.sign(path, |w| write_params(w, params.clone(), timestamp));
+    let mut body = Vec::with_capacity(512);
+    let mut ser = serde_json::Serializer::new(&mut body);
+    let write = |ser: &mut serde_json::Serializer<&mut Vec<u8>>| {
+        let mut map = ser.serialize_map(None)?;
+        for (k, v) in params {
+            map.serialize_entry(k, &v)?;
+        }
+        map.serialize_entry("timestamp", &timestamp)?;
+        map.serialize_entry("signature", signature.as_str())?;
+        map.end()
+    };
+    // serializing strings, numbers and bools into a Vec cannot fail
+    write(&mut ser).unwrap();
+    body
+}
+
+// `exchange_timestamp` of a response body, without building a `Value` of it.
+fn body_timestamp(body: &[u8]) -> Option<i64> {
+    #[derive(serde::Deserialize)]
+    struct Stamped<'a> {
+        timestamp: Option<i64>,
+        #[serde(borrow)]
+        data: Option<&'a serde_json::value::RawValue>,
+    }
+    let top: Stamped = serde_json::from_slice(body).ok()?;
+    top.timestamp.or_else(|| {
+        let data: Stamped = serde_json::from_str(top.data?.get()).ok()?;
+        data.timestamp
+    })
+}
+
+// Add `timestamp` and `signature` to the params of a raw `Value` request.
+fn sign_value(signer: &Signer, path: &str, param_map: &mut Value, timestamp: i64) {
+    param_map["timestamp"] = Value::from(timestamp);
+    let signature = signer.sign(path, |w| write_canonical(w, param_map));
+    param_map["signature"] = Value::String(signature.as_str().to_string());
+}
+
+// the label of a method in the metrics, without cloning it
+fn method_label(method: &Method) -> &'static str {
+    if *method == Method::GET {
+        "GET"
+    } else if *method == Method::POST {
+        "POST"
+    } else if *method == Method::PUT {
+        "PUT"
+    } else if *method == Method::DELETE {
+        "DELETE"
+    } else {
+        "OTHER"
+    }
+}
+
+/// `url` with the members of `params` as its query string, values that are not strings in
+/// their JSON form.
+fn with_query(mut url: Url, params: &Value) -> Url {
+    if let Some(map) = params.as_object()
+        && !map.is_empty()
+    {
+        let mut scratch = String::new();
+        let mut pairs = url.query_pairs_mut();
+        for (k, v) in map {
+            match v {
+                Value::String(s) => pairs.append_pair(k, s),
+                v => {
+                    scratch.clear();
+                    let _ = write!(scratch, "{}", v);
+                    pairs.append_pair(k, &scratch)
+                }
+            };
+        }
+    }
+    url
+}
+
 pub struct BitRestClient {
-    access_key: String,
-    secret_key: String,
+    signer: RwLock<Arc<Signer>>,
     base_url: String,
+    // parsed `base_url + path` per endpoint, filled on first use
+    urls: RwLock<HashMap<String, Url>>,
     client: Client,
+    rate_limiters: Vec<Arc<RateLimiter>>,
+    latency: Option<Arc<LatencyTracker>>,
 }
 
 impl BitRestClient {
     pub fn new(access_key: &str, secret_key: &str, base_url: &str) -> Self {
-        Self {
-            access_key: access_key.to_string(),
-            secret_key: secret_key.to_string(),
+        Self::with_client(
+            Credentials::new(access_key, secret_key),
+            base_url,
+            Client::new(),
+        )
+    }
+
+    pub fn from_provider(provider: &dyn CredentialProvider, base_url: &str) -> BitResult<Self> {
+        Self::try_with_client(provider.load()?, base_url, Client::new())
+    }
+
+    /// Share one `reqwest::Client` (and its connection pool) between several accounts.
+    ///
+    /// Panics when the access key is not a valid header value, use `try_with_client`
+    /// for keys that were not written by the caller.
+    pub fn with_client(credentials: Credentials, base_url: &str, client: Client) -> Self {
+        Self::try_with_client(credentials, base_url, client).expect("invalid access key")
+    }
+
+    /// `with_client` that returns `BitError::Config` for an access key that cannot be sent.
+    pub fn try_with_client(
+        credentials: Credentials,
+        base_url: &str,
+        client: Client,
+    ) -> BitResult<Self> {
+        Ok(Self {
+            signer: RwLock::new(Arc::new(Signer::new(credentials)?)),
             base_url: base_url.to_string(),
-            client: Client::new(),
-        }
+            urls: RwLock::new(HashMap::new()),
+            client,
+            rate_limiters: Vec::new(),
+            latency: None,
+        })
+    }
+
+    pub fn access_key(&self) -> String {
+        self.credentials().access_key.clone()
+    }
+
+    fn signer(&self) -> Arc<Signer> {
+        self.signer.read().unwrap().clone()
+    }
+
+    fn credentials(&self) -> Arc<Credentials> {
+        self.signer().credentials.clone()
+    }
+
+    /// Swap keys in place. Requests already in flight finish with the old key.
+    /// An access key that cannot be sent is rejected and the old key stays in use.
+    pub fn rotate_credentials(&self, credentials: Credentials) -> BitResult<()> {
+        let signer = Signer::new(credentials)?;
+        *self.signer.write().unwrap() = Arc::new(signer);
+        Ok(())
+    }
+
+    /// Reload from `provider` every `interval` and rotate when the key changed.
+    /// Stops once the client is dropped.
+    pub fn spawn_credential_refresh(
+        self: &Arc<Self>,
+        provider: Arc<dyn CredentialProvider>,
+        interval: Duration,
+    ) -> tokio::task::JoinHandle<()> {
+        let client = Arc::downgrade(self);
+        tokio::spawn(async move {
+            loop {
+                tokio::time::sleep(interval).await;
+                let Some(client) = client.upgrade() else {
+                    return;
+                };
+                let p = provider.clone();
+                match tokio::task::spawn_blocking(move || p.load()).await {
+                    Ok(Ok(credentials)) => {
+                        if *client.credentials() != credentials {
+                            println!(
+                                "rotating credentials, new access-key:{}",
+                                credentials.access_key
+                            );
+                            if let Err(e) = client.rotate_credentials(credentials) {
+                                println!("credential refresh failed: {}", e);
+                            }
+                        }
+                    }
+                    Ok(Err(e)) => println!("credential refresh failed: {}", e),
+                    Err(e) => println!("credential refresh failed: {}", e),
+                }
+            }
+        })
+    }
+
+    /// Every request waits for a token from each limiter. A limiter can be shared by
+    /// several clients when the exchange counts their requests against the same budget.
+    pub fn add_rate_limiter(mut self, limiter: Arc<RateLimiter>) -> Self {
+        self.rate_limiters.push(limiter);
+        self
+    }
+
+    /// Record the round trip of every request in `tracker`.
+    pub fn with_latency_tracker(mut self, tracker: Arc<LatencyTracker>) -> Self {
+        self.latency = Some(tracker);
+        self
     }
 
     fn get_nonce(&self) -> i64 {
         Utc::now().timestamp_millis()
     }
 
-    fn encode_object(&self, value: &Value) -> String {
-        match value {
-            Value::Object(map) => {
-                let mut sorted_keys: Vec<_> = map.keys().collect();
-                sorted_keys.sort();
-                sorted_keys
-                    .iter()
-                    .map(|k| {
-                        let v = &map[*k];
-                        let val = match v {
-                            Value::Bool(b) => b.to_string(),
-                            Value::Number(n) => n.to_string(),
-                            Value::String(s) => s.clone(),
-                            Value::Array(_) | Value::Object(_) => self.encode_object(v),
-                            _ => v.to_string(),
-                        };
-                        format!("{}={}", k, val)
-                    })
-                    .collect::<Vec<_>>()
-                    .join("&")
-            }
-            Value::Array(arr) => {
-                let vlist = arr
-                    .iter()
-                    .map(|item| self.encode_object(item))
-                    .collect::<Vec<_>>();
-                format!("[{}]", vlist.join("&"))
-            }
-            _ => value.to_string(),
+    async fn acquire(&self) {
+        for limiter in &self.rate_limiters {
+            limiter.acquire().await;
         }
     }
 
-    fn get_signature(&self, _method: &str, api_path: &str, params: &Value) -> String {
-        let data = format!("{}&{}", api_path, self.encode_object(params));
-        let mut mac = HmacSha256::new_from_slice(self.secret_key.as_bytes())
-            .expect("HMAC initialization failed");
-        mac.update(data.as_bytes());
-        hex::encode(mac.finalize().into_bytes())
+    /// The endpoint url, parsed once. `None` when `base_url + path` is not a valid url.
+    fn url(&self, path: &str) -> Option<Url> {
+        if let Some(url) = self.urls.read().unwrap().get(path) {
+            return Some(url.clone());
+        }
+        let url = Url::parse(&format!("{}{}", self.base_url, path)).ok()?;
+        self.urls
+            .write()
+            .unwrap()
+            .insert(path.to_string(), url.clone());
+        Some(url)
+    }
+
+    fn request(&self, method: Method, path: &str, query: Option<&Value>) -> RequestBuilder {
+        match self.url(path) {
+            Some(url) => match query {
+                Some(params) => self.client.request(method, with_query(url, params)),
+                None => self.client.request(method, url),
+            },
+            // let reqwest report the bad url when the request is sent
+            None => self
+                .client
+                .request(method, format!("{}{}", self.base_url, path)),
+        }
     }
 
     pub async fn call_private_api(
@@ -126,39 +477,173 @@ impl BitRestClient {
         method: Method,
         param_map: &mut Value,
     ) -> Result<Value, reqwest::Error> {
-        param_map["timestamp"] = Value::from(self.get_nonce());
-        let signature = self.get_signature(method.as_str(), path, param_map);
-        param_map["signature"] = Value::String(signature);
+        Ok(self
+            .call_private_api_timed(path, method, param_map)
+            .await?
+            .value)
+    }
 
-        let url = format!("{}{}", self.base_url, path);
+    /// `call_private_api` with the send and receive timestamps of the request.
+    pub async fn call_private_api_timed(
+        &self,
+        path: &str,
+        method: Method,
+        param_map: &mut Value,
+    ) -> Result<Timed<Value>, reqwest::Error> {
+        self.acquire().await;
 
-        let mut headers = HeaderMap::new();
-        headers.insert(
-            HeaderName::from_static("x-bit-access-key"),
-            HeaderValue::from_str(&self.access_key).unwrap(),
-        );
-        headers.insert(
-            HeaderName::from_static("language-type"),
-            HeaderValue::from_static("1"),
-        );
+        let label = method_label(&method);
+        let mut sent = Stamp::now();
+        let rsp = self.send_private(path, method, param_map, &mut sent).await;
+        let received = Stamp::now();
+        let code = metrics::response_code(&rsp);
+        metrics::rest_request(path, label, received.mono - sent.mono, code);
+        Ok(self.timed(path, rsp?, sent, received))
+    }
+
+    /// Signed POST of a typed request, decoded into `T`. The fields are signed and serialized
+    /// as they are, see `signed_body`, and the response body is decoded without an
+    /// intermediate `Value`.
+    pub async fn call_private_typed<'a, T: DeserializeOwned>(
+        &self,
+        path: &str,
+        params: impl Iterator<Item = (&'a str, Param<'a>)> + Clone,
+    ) -> BitResult<T> {
+        Ok(self.send_typed(path, params, false).await?.value)
+    }
+
+    /// `call_private_typed` with the send and receive timestamps of the request.
+    pub async fn call_private_typed_timed<'a, T: DeserializeOwned>(
+        &self,
+        path: &str,
+        params: impl Iterator<Item = (&'a str, Param<'a>)> + Clone,
+    ) -> BitResult<Timed<T>> {
+        self.send_typed(path, params, true).await
+    }
+
+    // the exchange timestamp is only looked for when `timed`, it costs another pass over the body
+    async fn send_typed<'a, T: DeserializeOwned>(
+        &self,
+        path: &str,
+        params: impl Iterator<Item = (&'a str, Param<'a>)> + Clone,
+        timed: bool,
+    ) -> BitResult<Timed<T>> {
+        self.acquire().await;
 
-        let request = self.client.request(method.clone(), &url).headers(headers);
+        let signer = self.signer();
+        let body = signed_body(&signer, path, params, self.get_nonce());
+        let request = self
+            .request(Method::POST, path, None)
+            .headers(signer.headers.clone())
+            .header(CONTENT_TYPE, HeaderValue::from_static("application/json"))
+            .body(body);
+        let sent = Stamp::now();
+        let rsp = match request.send().await {
+            Ok(response) => response.bytes().await,
+            Err(e) => Err(e),
+        };
+        let received = Stamp::now();
+        self.record_latency(path, sent, received);
+        let bytes = match rsp {
+            Ok(bytes) => bytes,
+            Err(e) => {
+                metrics::rest_request(path, "POST", received.mono - sent.mono, RestOutcome::Http);
+                return Err(BitError::Http(e));
+            }
+        };
+        let (value, outcome) = match parse_body(&bytes) {
+            Ok(data) => (Ok(data), RestOutcome::Code(0)),
+            Err(BitError::Api { code, message }) => {
+                // parse_data reports a body that is not JSON as code -1
+                let outcome = match serde_json::from_slice::<Value>(&bytes) {
+                    Ok(_) => RestOutcome::Code(code),
+                    Err(_) => RestOutcome::Http,
+                };
+                (Err(BitError::Api { code, message }), outcome)
+            }
+            Err(e) => (Err(e), RestOutcome::Decode),
+        };
+        metrics::rest_request(path, "POST", received.mono - sent.mono, outcome);
+        let timing = Timing {
+            sent: Some(sent),
+            received,
+            exchange_ms: if timed { body_timestamp(&bytes) } else { None },
+        };
+        Ok(Timed {
+            value: value?,
+            timing,
+        })
+    }
 
-        let response = if method == Method::GET {
-            let query = param_map
-                .as_object()
-                .unwrap_or(&Default::default())
-                .iter()
-                .map(|(k, v)| (k.clone(), v.as_str().unwrap_or(&v.to_string()).to_string()))
-                .collect::<BTreeMap<_, _>>();
-            request.query(&query).send().await?
+    fn record_latency(&self, path: &str, sent: Stamp, received: Stamp) {
+        if let Some(tracker) = &self.latency {
+            tracker.record_rest(path, received.mono - sent.mono);
+        }
+    }
+
+    fn timed(&self, path: &str, value: Value, sent: Stamp, received: Stamp) -> Timed<Value> {
+        let timing = Timing {
+            sent: Some(sent),
+            received,
+            exchange_ms: exchange_timestamp(&value),
+        };
+        self.record_latency(path, sent, received);
+        Timed { value, timing }
+    }
+
+    async fn send_private(
+        &self,
+        path: &str,
+        method: Method,
+        param_map: &mut Value,
+        sent: &mut Stamp,
+    ) -> Result<Value, reqwest::Error> {
+        let signer = self.signer();
+        sign_value(&signer, path, param_map, self.get_nonce());
+
+        let request = if method == Method::GET {
+            self.request(method, path, Some(param_map))
         } else {
-            println!("POST with param: {:?}", param_map);
-            request.json(param_map).send().await?
+            self.request(method, path, None).json(param_map)
         };
+        let request = request.headers(signer.headers.clone());
+        *sent = Stamp::now();
+        let response = request.send().await?;
+
+        let text = response.text().await?;
+        Ok(serde_json::from_str(&text).unwrap_or(Value::String(text)))
+    }
+
+    /// Unsigned GET for public market data.
+    pub async fn call_public_api(
+        &self,
+        path: &str,
+        param_map: &Value,
+    ) -> Result<Value, reqwest::Error> {
+        Ok(self.call_public_api_timed(path, param_map).await?.value)
+    }
+
+    /// `call_public_api` with the send and receive timestamps of the request.
+    pub async fn call_public_api_timed(
+        &self,
+        path: &str,
+        param_map: &Value,
+    ) -> Result<Timed<Value>, reqwest::Error> {
+        let sent = Stamp::now();
+        let rsp = self.send_public(path, param_map).await;
+        let received = Stamp::now();
+        let code = metrics::response_code(&rsp);
+        metrics::rest_request(path, "GET", received.mono - sent.mono, code);
+        Ok(self.timed(path, rsp?, sent, received))
+    }
 
+    async fn send_public(&self, path: &str, param_map: &Value) -> Result<Value, reqwest::Error> {
+        let response = self
+            .request(Method::GET, path, Some(param_map))
+            .send()
+            .await?;
         let text = response.text().await?;
-        Ok(serde_json::from_str(&text).unwrap_or_else(|_| Value::String(text)))
+        Ok(serde_json::from_str(&text).unwrap_or(Value::String(text)))
     }
 
     /////////////////////////////////////////
@@ -179,6 +664,15 @@ impl BitRestClient {
             .await;
     }
 
+    pub async fn update_um_account_mode(
+        &self,
+        param_map: &mut Value,
+    ) -> Result<Value, reqwest::Error> {
+        return self
+            .call_private_api(V1_UM_ACCOUNT_MODE, Method::POST, param_map)
+            .await;
+    }
+
     pub async fn get_um_account(&self, param_map: &mut Value) -> Result<Value, reqwest::Error> {
         return self
             .call_private_api(V1_UM_ACCOUNTS, Method::GET, param_map)
@@ -191,6 +685,63 @@ impl BitRestClient {
             .await;
     }
 
+    /////////////////////////////////////////
+    // wallet functions
+    /////////////////////////////////////////
+    pub async fn get_deposit_address(
+        &self,
+        param_map: &mut Value,
+    ) -> Result<Value, reqwest::Error> {
+        return self
+            .call_private_api(V1_WALLET_DEPOSIT_ADDRESS, Method::GET, param_map)
+            .await;
+    }
+
+    pub async fn get_deposits(&self, param_map: &mut Value) -> Result<Value, reqwest::Error> {
+        return self
+            .call_private_api(V1_WALLET_DEPOSITS, Method::GET, param_map)
+            .await;
+    }
+
+    pub async fn withdraw(&self, param_map: &mut Value) -> Result<Value, reqwest::Error> {
+        return self
+            .call_private_api(V1_WALLET_WITHDRAW, Method::POST, param_map)
+            .await;
+    }
+
+    pub async fn get_withdrawals(&self, param_map: &mut Value) -> Result<Value, reqwest::Error> {
+        return self
+            .call_private_api(V1_WALLET_WITHDRAWALS, Method::GET, param_map)
+            .await;
+    }
+
+    pub async fn get_withdraw_fee(&self, param_map: &mut Value) -> Result<Value, reqwest::Error> {
+        return self
+            .call_private_api(V1_WALLET_WITHDRAW_FEE, Method::GET, param_map)
+            .await;
+    }
+
+    pub async fn transfer(&self, param_map: &mut Value) -> Result<Value, reqwest::Error> {
+        return self
+            .call_private_api(V1_WALLET_TRANSFER, Method::POST, param_map)
+            .await;
+    }
+
+    pub async fn get_transfers(&self, param_map: &mut Value) -> Result<Value, reqwest::Error> {
+        return self
+            .call_private_api(V1_WALLET_TRANSFERS, Method::GET, param_map)
+            .await;
+    }
+
+    pub async fn subaccount_transfer(
+        &self,
+        param_map: &mut Value,
+    ) -> Result<Value, reqwest::Error> {
+        return self
+            .call_private_api(V1_SUBACCOUNT_TRANSFER, Method::POST, param_map)
+            .await;
+    }
+
     /////////////////////////////////////////
     // spot functions
     /////////////////////////////////////////
@@ -323,6 +874,39 @@ impl BitRestClient {
             .await;
     }
 
+    pub async fn linear_enable_cod(&self, param_map: &mut Value) -> Result<Value, reqwest::Error> {
+        return self
+            .call_private_api(V1_LINEAR_ACCOUNT_CONFIGS_COD, Method::POST, param_map)
+            .await;
+    }
+
+    pub async fn linear_update_position_mode(
+        &self,
+        param_map: &mut Value,
+    ) -> Result<Value, reqwest::Error> {
+        return self
+            .call_private_api(V1_LINEAR_POSITION_MODE, Method::POST, param_map)
+            .await;
+    }
+
+    pub async fn linear_get_leverage_ratio(
+        &self,
+        param_map: &mut Value,
+    ) -> Result<Value, reqwest::Error> {
+        return self
+            .call_private_api(V1_LINEAR_LEVERAGE_RATIO, Method::GET, param_map)
+            .await;
+    }
+
+    pub async fn linear_update_leverage_ratio(
+        &self,
+        param_map: &mut Value,
+    ) -> Result<Value, reqwest::Error> {
+        return self
+            .call_private_api(V1_LINEAR_LEVERAGE_RATIO, Method::POST, param_map)
+            .await;
+    }
+
     pub async fn linear_get_positions(
         &self,
         param_map: &mut Value,
@@ -332,6 +916,15 @@ impl BitRestClient {
             .await;
     }
 
+    pub async fn linear_get_est_margins(
+        &self,
+        param_map: &mut Value,
+    ) -> Result<Value, reqwest::Error> {
+        return self
+            .call_private_api(V1_LINEAR_EST_MARGINS, Method::GET, param_map)
+            .await;
+    }
+
     pub async fn linear_get_orders(&self, param_map: &mut Value) -> Result<Value, reqwest::Error> {
         return self
             .call_private_api(V1_LINEAR_ORDERS, Method::GET, param_map)
@@ -395,6 +988,33 @@ impl BitRestClient {
             .await;
     }
 
+    pub async fn linear_new_block_trade(
+        &self,
+        param_map: &mut Value,
+    ) -> Result<Value, reqwest::Error> {
+        return self
+            .call_private_api(V1_LINEAR_BLOCK_TRADES, Method::POST, param_map)
+            .await;
+    }
+
+    pub async fn linear_get_block_trades(
+        &self,
+        param_map: &mut Value,
+    ) -> Result<Value, reqwest::Error> {
+        return self
+            .call_private_api(V1_LINEAR_BLOCK_TRADES, Method::GET, param_map)
+            .await;
+    }
+
+    pub async fn linear_get_platform_block_trades(
+        &self,
+        param_map: &mut Value,
+    ) -> Result<Value, reqwest::Error> {
+        return self
+            .call_private_api(V1_LINEAR_PLATFORM_BLOCK_TRADES, Method::GET, param_map)
+            .await;
+    }
+
     pub async fn linear_get_mmp_state(
         &self,
         param_map: &mut Value,
@@ -418,4 +1038,253 @@ impl BitRestClient {
             .call_private_api(V1_LINEAR_RESET_MMP, Method::POST, param_map)
             .await;
     }
+
+    /////////////////////////////////////////
+    // option functions
+    /////////////////////////////////////////
+    pub async fn option_get_instruments(&self, param_map: &Value) -> Result<Value, reqwest::Error> {
+        return self.call_public_api(V1_OPTION_INSTRUMENTS, param_map).await;
+    }
+
+    pub async fn option_get_tickers(&self, param_map: &Value) -> Result<Value, reqwest::Error> {
+        return self.call_public_api(V1_OPTION_TICKERS, param_map).await;
+    }
+
+    pub async fn option_get_market_summary(
+        &self,
+        param_map: &Value,
+    ) -> Result<Value, reqwest::Error> {
+        return self
+            .call_public_api(V1_OPTION_MARKET_SUMMARY, param_map)
+            .await;
+    }
+
+    pub async fn option_get_positions(
+        &self,
+        param_map: &mut Value,
+    ) -> Result<Value, reqwest::Error> {
+        return self
+            .call_private_api(V1_OPTION_POSITIONS, Method::GET, param_map)
+            .await;
+    }
+
+    pub async fn option_get_orders(&self, param_map: &mut Value) -> Result<Value, reqwest::Error> {
+        return self
+            .call_private_api(V1_OPTION_ORDERS, Method::GET, param_map)
+            .await;
+    }
+
+    pub async fn option_get_open_orders(
+        &self,
+        param_map: &mut Value,
+    ) -> Result<Value, reqwest::Error> {
+        return self
+            .call_private_api(V1_OPTION_OPENORDERS, Method::GET, param_map)
+            .await;
+    }
+
+    pub async fn option_get_user_trades(
+        &self,
+        param_map: &mut Value,
+    ) -> Result<Value, reqwest::Error> {
+        return self
+            .call_private_api(V1_OPTION_USER_TRADES, Method::GET, param_map)
+            .await;
+    }
+
+    pub async fn option_new_order(&self, param_map: &mut Value) -> Result<Value, reqwest::Error> {
+        return self
+            .call_private_api(V1_OPTION_ORDERS, Method::POST, param_map)
+            .await;
+    }
+
+    pub async fn option_amend_order(&self, param_map: &mut Value) -> Result<Value, reqwest::Error> {
+        return self
+            .call_private_api(V1_OPTION_AMEND_ORDERS, Method::POST, param_map)
+            .await;
+    }
+
+    pub async fn option_cancel_order(
+        &self,
+        param_map: &mut Value,
+    ) -> Result<Value, reqwest::Error> {
+        return self
+            .call_private_api(V1_OPTION_CANCEL_ORDERS, Method::POST, param_map)
+            .await;
+    }
+
+    pub async fn option_batch_new_orders(
+        &self,
+        param_map: &mut Value,
+    ) -> Result<Value, reqwest::Error> {
+        return self
+            .call_private_api(V1_OPTION_BATCH_ORDERS, Method::POST, param_map)
+            .await;
+    }
+
+    pub async fn option_batch_amend_orders(
+        &self,
+        param_map: &mut Value,
+    ) -> Result<Value, reqwest::Error> {
+        return self
+            .call_private_api(V1_OPTION_AMEND_BATCH_ORDERS, Method::POST, param_map)
+            .await;
+    }
+
+    pub async fn option_get_deliveries(
+        &self,
+        param_map: &mut Value,
+    ) -> Result<Value, reqwest::Error> {
+        return self
+            .call_private_api(V1_OPTION_DELIVERIES, Method::GET, param_map)
+            .await;
+    }
+
+    pub async fn option_get_settlements(
+        &self,
+        param_map: &mut Value,
+    ) -> Result<Value, reqwest::Error> {
+        return self
+            .call_private_api(V1_OPTION_SETTLEMENTS, Method::GET, param_map)
+            .await;
+    }
+
+    pub async fn option_enable_cod(&self, param_map: &mut Value) -> Result<Value, reqwest::Error> {
+        return self
+            .call_private_api(V1_OPTION_ACCOUNT_CONFIGS_COD, Method::POST, param_map)
+            .await;
+    }
+
+    pub async fn option_get_mmp_state(
+        &self,
+        param_map: &mut Value,
+    ) -> Result<Value, reqwest::Error> {
+        return self
+            .call_private_api(V1_OPTION_MMP_STATE, Method::GET, param_map)
+            .await;
+    }
+
+    pub async fn option_update_mmp_config(
+        &self,
+        param_map: &mut Value,
+    ) -> Result<Value, reqwest::Error> {
+        return self
+            .call_private_api(V1_OPTION_MMP_UPDATE_CONFIG, Method::POST, param_map)
+            .await;
+    }
+
+    pub async fn option_reset_mmp(&self, param_map: &mut Value) -> Result<Value, reqwest::Error> {
+        return self
+            .call_private_api(V1_OPTION_RESET_MMP, Method::POST, param_map)
+            .await;
+    }
+}
+
+/// The signing steps of a request, for `benches/rest_client.rs`. Not a stable API.
+#[cfg(feature = "bench")]
+#[doc(hidden)]
+pub mod bench {
+    use super::{BitRestClient, HeaderMap, Param, Value, write_canonical};
+
+    /// The canonical `key=value&...` form of `value` that gets signed, keys sorted.
+    pub fn encode_object(value: &Value) -> String {
+        let mut out = String::new();
+        let _ = write_canonical(&mut out, value);
+        out
+    }
+
+    /// Add `timestamp` and `signature` to `params` as the raw `Value` methods do.
+    pub fn sign_value(client: &BitRestClient, path: &str, params: &mut Value, timestamp: i64) {
+        super::sign_value(&client.signer(), path, params, timestamp)
+    }
+
+    /// The signed JSON body `call_private_typed` sends.
+    pub fn signed_body<'a>(
+        client: &BitRestClient,
+        path: &str,
+        params: impl Iterator<Item = (&'a str, Param<'a>)> + Clone,
+        timestamp: i64,
+    ) -> Vec<u8> {
+        super::signed_body(&client.signer(), path, params, timestamp)
+    }
+
+    /// The headers of every signed request.
+    pub fn auth_headers(client: &BitRestClient) -> HeaderMap {
+        client.signer().headers.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{AmendRequest, Market, OrderRequest, Side, TimeInForce};
+
+    const TIMESTAMP: i64 = 1_700_000_000_000;
+
+    fn signer() -> Signer {
+        Signer::new(Credentials::new("test-ak", "test-sk")).unwrap()
+    }
+
+    // the typed body must carry the same fields and signature as the `Value` path
+    fn assert_same_body<'a>(
+        path: &str,
+        mut value: Value,
+        typed: impl Iterator<Item = (&'a str, Param<'a>)> + Clone,
+    ) {
+        let signer = signer();
+        let mut canonical = String::new();
+        write_params(&mut canonical, typed.clone(), TIMESTAMP).unwrap();
+
+        sign_value(&signer, path, &mut value, TIMESTAMP);
+        let body: Value =
+            serde_json::from_slice(&signed_body(&signer, path, typed, TIMESTAMP)).unwrap();
+        assert_eq!(body, value);
+
+        let mut unsigned = value;
+        unsigned.as_object_mut().unwrap().remove("signature");
+        let mut expected = String::new();
+        write_canonical(&mut expected, &unsigned).unwrap();
+        assert_eq!(canonical, expected);
+    }
+
+    #[test]
+    fn typed_order_signs_like_value() {
+        let req = OrderRequest::limit("BTC-USDT-PERPETUAL", Side::Buy, 1.5, 20000.25)
+            .with_label("strat-1")
+            .with_time_in_force(TimeInForce::Gtc)
+            .post_only();
+        for market in [Market::Linear, Market::Option] {
+            let params = req.params(market);
+            assert_same_body(
+                V1_LINEAR_ORDERS,
+                req.to_params(market),
+                params.into_iter().flatten(),
+            );
+        }
+
+        let req = OrderRequest::market("BTC-USDT", Side::Sell, 0.001);
+        let params = req.params(Market::Spot);
+        assert_same_body(
+            V1_SPOT_ORDERS,
+            req.to_params(Market::Spot),
+            params.into_iter().flatten(),
+        );
+    }
+
+    #[test]
+    fn typed_amend_signs_like_value() {
+        let req = AmendRequest {
+            instrument_id: "BTC-USDT-PERPETUAL".to_string(),
+            order_id: Some("1234".to_string()),
+            price: Some(20100.5),
+            qty: Some(2.0),
+            ..Default::default()
+        };
+        let params = req.params(Market::Linear);
+        assert_same_body(
+            V1_LINEAR_AMEND_ORDERS,
+            req.to_params(Market::Linear),
+            params.into_iter().flatten(),
+        );
+    }
 }
@@ -0,0 +1,435 @@
+// WebSocket client with typed events and automatic reconnect.
+//
+// Each connection is driven by a background task that (re)subscribes after every reconnect
+// and forwards parsed events to an mpsc channel. Private subscriptions fetch a fresh token
+// through `BitRestClient::ws_auth` on every connect. Text and binary frames, compressed or
+// not, are decoded by `ws_frame` straight into the typed events.
+
+use crate::bit_rest_client::BitRestClient;
+use crate::latency::{LatencyTracker, Stamp, Timed, Timing};
+use crate::metrics;
+use crate::models::{Depth, Market, Order, Position, PublicTrade, Ticker, Trade, UmAccount};
+use crate::options::OptionTicker;
+use crate::record::RecordedFrame;
+use crate::util::now_ms;
+use crate::ws_frame::{FrameDecoder, WsFrame};
+use futures_util::{SinkExt, StreamExt};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::borrow::Cow;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio_tungstenite::{connect_async, tungstenite::Message};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum WsEvent {
+    Ticker(Ticker),
+    /// `ticker` channel of an option instrument, with IV and greeks.
+    OptionTicker(OptionTicker),
+    Depth(Depth),
+    Trades(Vec<PublicTrade>),
+    Orders(Vec<Order>),
+    UserTrades(Vec<Trade>),
+    Positions(Vec<Position>),
+    UmAccount(UmAccount),
+    /// Channels without a typed model, or payloads that failed to decode.
+    Other {
+        channel: String,
+        data: Value,
+    },
+    Connected,
+    Disconnected {
+        reason: String,
+    },
+}
+
+impl WsEvent {
+    /// Parse a raw `{"channel": ..., "data": ...}` message.
+    pub fn parse(msg: &Value) -> Option<WsEvent> {
+        Self::dispatch(msg["channel"].as_str()?, &msg["data"])
+    }
+
+    /// `parse` straight from the message text: `data` is deserialized into the typed event
+    /// without a `Value` in between.
+    pub fn from_frame(frame: &WsFrame) -> Option<WsEvent> {
+        Self::dispatch(frame.channel()?, frame)
+    }
+
+    fn dispatch(channel: &str, data: &impl EventData) -> Option<WsEvent> {
+        let event = match channel {
+            // only option tickers pay for the flattened IV and greeks
+            "ticker" => match data.instrument_id() {
+                Some(id) if Market::of_instrument(&id) == Market::Option => {
+                    data.decode().map(WsEvent::OptionTicker)
+                }
+                _ => data.decode().map(WsEvent::Ticker),
+            },
+            "depth" => data.decode().map(WsEvent::Depth),
+            "trade" => data.decode().map(WsEvent::Trades),
+            "order" => data.decode().map(WsEvent::Orders),
+            "user_trade" => data.decode().map(WsEvent::UserTrades),
+            "position" => data.decode().map(WsEvent::Positions),
+            "um_account" => data.decode().map(WsEvent::UmAccount),
+            "subscription" => return None,
+            _ => None,
+        };
+        Some(event.unwrap_or_else(|| WsEvent::Other {
+            channel: channel.to_string(),
+            data: data.to_value(),
+        }))
+    }
+}
+
+// The `data` of a message, already a `Value` or still in the frame text.
+trait EventData {
+    fn decode<T: DeserializeOwned>(&self) -> Option<T>;
+    fn to_value(&self) -> Value;
+    /// `instrument_id` (or `pair`) of an object, without decoding the rest.
+    fn instrument_id(&self) -> Option<Cow<'_, str>>;
+}
+
+impl EventData for Value {
+    fn decode<T: DeserializeOwned>(&self) -> Option<T> {
+        T::deserialize(self).ok()
+    }
+
+    fn instrument_id(&self) -> Option<Cow<'_, str>> {
+        self["instrument_id"]
+            .as_str()
+            .or(self["pair"].as_str())
+            .map(Cow::Borrowed)
+    }
+
+    fn to_value(&self) -> Value {
+        self.clone()
+    }
+}
+
+impl EventData for WsFrame<'_> {
+    fn decode<T: DeserializeOwned>(&self) -> Option<T> {
+        WsFrame::decode(self)
+    }
+
+    fn instrument_id(&self) -> Option<Cow<'_, str>> {
+        #[derive(Deserialize)]
+        struct Peek<'a> {
+            #[serde(borrow, alias = "pair")]
+            instrument_id: Cow<'a, str>,
+        }
+
+        WsFrame::decode::<Peek>(self).map(|p| p.instrument_id)
+    }
+
+    fn to_value(&self) -> Value {
+        self.data_value()
+    }
+}
+
+/// What a connection task forwards: parsed events for strategies, raw frames for recording.
+/// `frame` borrows from the read buffer, so an implementation decoding into its own borrowed
+/// types pays only for what it keeps.
+pub trait FromWsFrame: Sized + Send + 'static {
+    fn from_frame(received: Stamp, frame: &WsFrame) -> Option<Self>;
+    fn connected(at_ms: i64) -> Self;
+    fn disconnected(at_ms: i64, reason: String) -> Self;
+}
+
+impl FromWsFrame for WsEvent {
+    fn from_frame(_received: Stamp, frame: &WsFrame) -> Option<Self> {
+        WsEvent::from_frame(frame)
+    }
+
+    fn connected(_at_ms: i64) -> Self {
+        WsEvent::Connected
+    }
+
+    fn disconnected(_at_ms: i64, reason: String) -> Self {
+        WsEvent::Disconnected { reason }
+    }
+}
+
+/// Unparsed traffic stamped with the local receive time.
+#[derive(Debug, Clone, PartialEq)]
+pub enum RawWsFrame {
+    Message(RecordedFrame),
+    Connected { at_ms: i64 },
+    Disconnected { at_ms: i64, reason: String },
+}
+
+impl FromWsFrame for RawWsFrame {
+    fn from_frame(received: Stamp, frame: &WsFrame) -> Option<Self> {
+        Some(RawWsFrame::Message(RecordedFrame {
+            recv_ms: received.wall_ms,
+            message: frame.to_value(),
+        }))
+    }
+
+    fn connected(at_ms: i64) -> Self {
+        RawWsFrame::Connected { at_ms }
+    }
+
+    fn disconnected(at_ms: i64, reason: String) -> Self {
+        RawWsFrame::Disconnected { at_ms, reason }
+    }
+}
+
+/// Parsed events with their receive time and exchange timestamp.
+impl FromWsFrame for Timed<WsEvent> {
+    fn from_frame(received: Stamp, frame: &WsFrame) -> Option<Self> {
+        let value = WsEvent::from_frame(frame)?;
+        let timing = Timing {
+            sent: None,
+            received,
+            exchange_ms: frame.exchange_timestamp(),
+        };
+        Some(Timed { value, timing })
+    }
+
+    fn connected(_at_ms: i64) -> Self {
+        Self::now(WsEvent::Connected)
+    }
+
+    fn disconnected(_at_ms: i64, reason: String) -> Self {
+        Self::now(WsEvent::Disconnected { reason })
+    }
+}
+
+impl Timed<WsEvent> {
+    fn now(value: WsEvent) -> Self {
+        Self {
+            value,
+            timing: Timing {
+                sent: None,
+                received: Stamp::now(),
+                exchange_ms: None,
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct WsSubscription {
+    pub channels: Vec<String>,
+    pub instruments: Vec<String>,
+    pub currencies: Vec<String>,
+    pub pairs: Vec<String>,
+    pub interval: String,
+    pub private: bool,
+}
+
+impl WsSubscription {
+    pub fn public(channels: &[&str], instruments: &[&str]) -> Self {
+        Self {
+            channels: channels.iter().map(|c| c.to_string()).collect(),
+            instruments: instruments.iter().map(|i| i.to_string()).collect(),
+            interval: "100ms".to_string(),
+            ..Default::default()
+        }
+    }
+
+    pub fn private(channels: &[&str]) -> Self {
+        Self {
+            channels: channels.iter().map(|c| c.to_string()).collect(),
+            interval: "100ms".to_string(),
+            private: true,
+            ..Default::default()
+        }
+    }
+
+    pub fn with_interval(mut self, interval: &str) -> Self {
+        self.interval = interval.to_string();
+        self
+    }
+
+    pub fn to_message(&self, token: Option<&str>) -> Value {
+        let mut msg = serde_json::json!({
+            "type": "subscribe",
+            "channels": self.channels,
+            "interval": self.interval,
+        });
+        if !self.instruments.is_empty() {
+            msg["instruments"] = serde_json::json!(self.instruments);
+        }
+        if !self.currencies.is_empty() {
+            msg["currencies"] = serde_json::json!(self.currencies);
+        }
+        if !self.pairs.is_empty() {
+            msg["pairs"] = serde_json::json!(self.pairs);
+        }
+        if let Some(token) = token {
+            msg["token"] = Value::String(token.to_string());
+        }
+        msg
+    }
+}
+
+pub struct BitWsClient {
+    url: String,
+    subscriptions: Vec<WsSubscription>,
+    rest_client: Option<Arc<BitRestClient>>,
+    reconnect_delay: Duration,
+    max_reconnect_delay: Duration,
+    latency: Option<Arc<LatencyTracker>>,
+}
+
+impl BitWsClient {
+    pub fn new(url: &str) -> Self {
+        Self {
+            url: url.to_string(),
+            subscriptions: Vec::new(),
+            rest_client: None,
+            reconnect_delay: Duration::from_millis(500),
+            max_reconnect_delay: Duration::from_secs(30),
+            latency: None,
+        }
+    }
+
+    pub fn subscribe(mut self, subscription: WsSubscription) -> Self {
+        self.subscriptions.push(subscription);
+        self
+    }
+
+    /// Required for private subscriptions, used to fetch the ws token on each connect.
+    pub fn with_auth(mut self, rest_client: Arc<BitRestClient>) -> Self {
+        self.rest_client = Some(rest_client);
+        self
+    }
+
+    pub fn with_reconnect_delay(mut self, initial: Duration, max: Duration) -> Self {
+        self.reconnect_delay = initial;
+        self.max_reconnect_delay = max;
+        self
+    }
+
+    /// Record the one-way latency of every message with an exchange timestamp in `tracker`,
+    /// per channel.
+    pub fn with_latency_tracker(mut self, tracker: Arc<LatencyTracker>) -> Self {
+        self.latency = Some(tracker);
+        self
+    }
+
+    /// Run the connection in a background task and return the event stream.
+    /// The task stops when the receiver is dropped.
+    pub fn spawn(self) -> mpsc::Receiver<WsEvent> {
+        self.spawn_as()
+    }
+
+    /// Like `spawn`, with the receive time and exchange timestamp of every event.
+    pub fn spawn_timed(self) -> mpsc::Receiver<Timed<WsEvent>> {
+        self.spawn_as()
+    }
+
+    /// Like `spawn`, but forwards every message unparsed with its receive time.
+    pub fn spawn_raw(self) -> mpsc::Receiver<RawWsFrame> {
+        self.spawn_as()
+    }
+
+    fn spawn_as<T: FromWsFrame>(self) -> mpsc::Receiver<T> {
+        let (tx, rx) = mpsc::channel(4096);
+        tokio::spawn(async move { self.run(tx).await });
+        rx
+    }
+
+    pub async fn run<T: FromWsFrame>(&self, tx: mpsc::Sender<T>) {
+        let mut delay = self.reconnect_delay;
+        loop {
+            let mut connected = false;
+            let reason = match self.run_once(&tx, &mut connected).await {
+                Ok(()) => "closed by server".to_string(),
+                Err(e) => e,
+            };
+            let event = T::disconnected(now_ms(), reason);
+            if tx.is_closed() || tx.send(event).await.is_err() {
+                return;
+            }
+            if connected {
+                delay = self.reconnect_delay;
+            }
+            tokio::time::sleep(delay).await;
+            metrics::ws_reconnect(&self.url);
+            delay = (delay * 2).min(self.max_reconnect_delay);
+        }
+    }
+
+    async fn run_once<T: FromWsFrame>(
+        &self,
+        tx: &mpsc::Sender<T>,
+        connected: &mut bool,
+    ) -> Result<(), String> {
+        let token = self.fetch_token().await?;
+        let (ws_stream, _) = connect_async(self.url.as_str())
+            .await
+            .map_err(|e| e.to_string())?;
+        let (mut write, mut read) = ws_stream.split();
+
+        for sub in &self.subscriptions {
+            let token = if sub.private { token.as_deref() } else { None };
+            let msg = serde_json::to_string(&sub.to_message(token)).unwrap();
+            write
+                .send(Message::Text(msg.into()))
+                .await
+                .map_err(|e| e.to_string())?;
+        }
+        *connected = true;
+        if tx.send(T::connected(now_ms())).await.is_err() {
+            return Ok(());
+        }
+
+        // inflated binary frames, reused across messages
+        let mut decoder = FrameDecoder::new();
+        while let Some(msg) = read.next().await {
+            let msg = msg.map_err(|e| e.to_string())?;
+            let received = Stamp::now();
+            let text = match &msg {
+                Message::Text(text) => text.as_str(),
+                Message::Binary(payload) => match decoder.text(payload) {
+                    Some(text) => text,
+                    None => continue,
+                },
+                Message::Ping(payload) => {
+                    write
+                        .send(Message::Pong(payload.clone()))
+                        .await
+                        .map_err(|e| e.to_string())?;
+                    continue;
+                }
+                Message::Close(frame) => return Err(format!("close frame: {:?}", frame)),
+                _ => continue,
+            };
+            let Some(frame) = WsFrame::parse(text) else {
+                continue;
+            };
+            metrics::ws_message(frame.channel(), frame.timestamp(), received.wall_ms);
+            if let Some(tracker) = &self.latency
+                && let Some(channel) = frame.channel()
+                && let Some(ts) = frame.exchange_timestamp()
+            {
+                tracker.record_ws(channel, received.wall_ms - ts);
+            }
+            let Some(event) = T::from_frame(received, &frame) else {
+                continue;
+            };
+            if tx.send(event).await.is_err() {
+                return Ok(());
+            }
+        }
+        Ok(())
+    }
+
+    async fn fetch_token(&self) -> Result<Option<String>, String> {
+        if !self.subscriptions.iter().any(|s| s.private) {
+            return Ok(None);
+        }
+        let rest_client = self
+            .rest_client
+            .as_ref()
+            .ok_or("private subscription without with_auth()")?;
+        let rsp = rest_client.ws_auth().await.map_err(|e| e.to_string())?;
+        rsp["data"]["token"]
+            .as_str()
+            .map(|t| Some(t.to_string()))
+            .ok_or(format!("ws_auth failed: {}", rsp))
+    }
+}
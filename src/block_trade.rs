@@ -0,0 +1,290 @@
+// Linear block trades: book a multi-leg trade with a known counterparty, list our own
+// block trades and page through the platform-wide block trade feed.
+
+use crate::bit_rest_client::BitRestClient;
+use crate::error::{BitError, BitResult, Page, parse_data, parse_page};
+use crate::models::{Market, Side, Trade, base_currency, settle_currency};
+use crate::util::{de_f64, de_string};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{BTreeSet, HashMap};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BlockTradeLeg {
+    pub instrument_id: String,
+    pub side: Side,
+    pub price: f64,
+    pub qty: f64,
+}
+
+impl BlockTradeLeg {
+    pub fn new(instrument_id: &str, side: Side, qty: f64, price: f64) -> Self {
+        Self {
+            instrument_id: instrument_id.to_string(),
+            side,
+            price,
+            qty,
+        }
+    }
+
+    pub fn to_params(&self) -> Value {
+        serde_json::json!({
+            "instrument_id": self.instrument_id,
+            "side": self.side,
+            "price": self.price.to_string(),
+            "qty": self.qty.to_string(),
+        })
+    }
+}
+
+/// Which side of the negotiation we are on. The taker books the trade, the maker confirms it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BlockTradeRole {
+    Taker,
+    Maker,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BlockTradeRequest {
+    /// Shared by both sides so the exchange can match the two submissions.
+    pub label: String,
+    /// User id of the other side.
+    pub counterparty: String,
+    pub role: BlockTradeRole,
+    pub legs: Vec<BlockTradeLeg>,
+}
+
+/// Desk rules checked before a block trade is sent.
+#[derive(Debug, Clone)]
+pub struct BlockTradeLimits {
+    pub max_legs: usize,
+    /// Minimum qty per leg, keyed by base currency ("BTC"). Missing currencies are unchecked.
+    pub min_leg_qty: HashMap<String, f64>,
+    /// When set, only these user ids may be counterparties.
+    pub allowed_counterparties: Option<BTreeSet<String>>,
+    /// Our own user id, trading against ourselves is rejected.
+    pub own_user_id: Option<String>,
+}
+
+impl Default for BlockTradeLimits {
+    fn default() -> Self {
+        Self {
+            max_legs: 20,
+            min_leg_qty: HashMap::new(),
+            allowed_counterparties: None,
+            own_user_id: None,
+        }
+    }
+}
+
+impl BlockTradeRequest {
+    pub fn new(label: &str, counterparty: &str, role: BlockTradeRole) -> Self {
+        Self {
+            label: label.to_string(),
+            counterparty: counterparty.to_string(),
+            role,
+            legs: vec![],
+        }
+    }
+
+    pub fn leg(mut self, leg: BlockTradeLeg) -> Self {
+        self.legs.push(leg);
+        self
+    }
+
+    pub fn validate(&self, limits: &BlockTradeLimits) -> BitResult<()> {
+        let invalid = |msg: String| Err(BitError::Invalid(msg));
+
+        if self.label.is_empty() {
+            return invalid("block trade needs a label".to_string());
+        }
+        if self.counterparty.is_empty() {
+            return invalid("block trade needs a counterparty".to_string());
+        }
+        if limits.own_user_id.as_deref() == Some(self.counterparty.as_str()) {
+            return invalid("counterparty is our own account".to_string());
+        }
+        if let Some(allowed) = &limits.allowed_counterparties
+            && !allowed.contains(&self.counterparty)
+        {
+            return invalid(format!("counterparty {} is not allowed", self.counterparty));
+        }
+
+        if self.legs.is_empty() {
+            return invalid("block trade has no legs".to_string());
+        }
+        if self.legs.len() > limits.max_legs {
+            return invalid(format!(
+                "{} legs, at most {}",
+                self.legs.len(),
+                limits.max_legs
+            ));
+        }
+
+        let currency = settle_currency(&self.legs[0].instrument_id);
+        let mut seen = BTreeSet::new();
+        for leg in &self.legs {
+            let inst = &leg.instrument_id;
+            if Market::of_instrument(inst) != Market::Linear {
+                return invalid(format!("{}: not a linear instrument", inst));
+            }
+            if settle_currency(inst) != currency {
+                return invalid(format!("{}: legs settle in different currencies", inst));
+            }
+            if !seen.insert(inst.as_str()) {
+                return invalid(format!("{}: more than one leg", inst));
+            }
+            if !(leg.qty.is_finite() && leg.qty > 0.0 && leg.price.is_finite() && leg.price > 0.0) {
+                return invalid(format!("{}: qty and price must be positive", inst));
+            }
+            if let Some(min) = limits.min_leg_qty.get(base_currency(inst))
+                && leg.qty < *min
+            {
+                return invalid(format!(
+                    "{}: qty {} below block minimum {}",
+                    inst, leg.qty, min
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    pub fn to_params(&self) -> Value {
+        let legs: Vec<Value> = self.legs.iter().map(BlockTradeLeg::to_params).collect();
+        serde_json::json!({
+            "currency": self.legs.first().map(|l| settle_currency(&l.instrument_id)),
+            "label": self.label,
+            "counterparty": self.counterparty,
+            "role": self.role,
+            "trades": legs,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BlockTrade {
+    #[serde(deserialize_with = "de_string")]
+    pub block_trade_id: String,
+    #[serde(default)]
+    pub label: String,
+    #[serde(default)]
+    pub trades: Vec<Trade>,
+    #[serde(default)]
+    pub created_at: i64,
+}
+
+/// One leg of a block trade from the platform-wide feed.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PlatformBlockTrade {
+    #[serde(deserialize_with = "de_string")]
+    pub block_trade_id: String,
+    #[serde(deserialize_with = "de_string")]
+    pub trade_id: String,
+    pub instrument_id: String,
+    pub side: Side,
+    #[serde(deserialize_with = "de_f64")]
+    pub price: f64,
+    #[serde(deserialize_with = "de_f64")]
+    pub qty: f64,
+    #[serde(default)]
+    pub created_at: i64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct BlockTradeQuery {
+    pub currency: String,
+    pub instrument_id: Option<String>,
+    pub block_trade_id: Option<String>,
+    pub start_time: Option<i64>,
+    pub end_time: Option<i64>,
+    /// Page size.
+    pub limit: u32,
+}
+
+impl BlockTradeQuery {
+    pub fn new(currency: &str) -> Self {
+        Self {
+            currency: currency.to_string(),
+            instrument_id: None,
+            block_trade_id: None,
+            start_time: None,
+            end_time: None,
+            limit: 100,
+        }
+    }
+
+    pub fn to_params(&self) -> Value {
+        let mut params = serde_json::json!({
+            "currency": self.currency,
+            "limit": self.limit,
+        });
+        if let Some(instrument_id) = &self.instrument_id {
+            params["instrument_id"] = Value::String(instrument_id.clone());
+        }
+        if let Some(block_trade_id) = &self.block_trade_id {
+            params["block_trade_id"] = Value::String(block_trade_id.clone());
+        }
+        if let Some(start_time) = self.start_time {
+            params["start_time"] = Value::from(start_time);
+        }
+        if let Some(end_time) = self.end_time {
+            params["end_time"] = Value::from(end_time);
+        }
+        params
+    }
+}
+
+/////////////////////////////////////////
+// typed endpoints
+/////////////////////////////////////////
+/// Validate against `limits`, then submit.
+pub async fn create_block_trade(
+    client: &BitRestClient,
+    req: &BlockTradeRequest,
+    limits: &BlockTradeLimits,
+) -> BitResult<BlockTrade> {
+    req.validate(limits)?;
+    parse_data(client.linear_new_block_trade(&mut req.to_params()).await?)
+}
+
+pub async fn block_trades(
+    client: &BitRestClient,
+    query: &BlockTradeQuery,
+) -> BitResult<Vec<BlockTrade>> {
+    parse_data(
+        client
+            .linear_get_block_trades(&mut query.to_params())
+            .await?,
+    )
+}
+
+/// One page of the platform feed. `offset` is the 1-based page number.
+pub async fn platform_block_trades_page(
+    client: &BitRestClient,
+    query: &BlockTradeQuery,
+    offset: u32,
+) -> BitResult<Page<PlatformBlockTrade>> {
+    let mut params = query.to_params();
+    params["offset"] = Value::from(offset);
+    let rsp = client.linear_get_platform_block_trades(&mut params).await?;
+    parse_page(rsp, query.limit)
+}
+
+/// Walk every page of the platform feed.
+pub async fn platform_block_trades(
+    client: &BitRestClient,
+    query: &BlockTradeQuery,
+) -> BitResult<Vec<PlatformBlockTrade>> {
+    let mut all = vec![];
+    let mut offset = 1;
+    loop {
+        let page = platform_block_trades_page(client, query, offset).await?;
+        let done = !page.has_more || page.items.is_empty();
+        all.extend(page.items);
+        if done {
+            return Ok(all);
+        }
+        offset += 1;
+    }
+}
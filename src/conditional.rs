@@ -0,0 +1,525 @@
+// Stop-loss, take-profit, trailing-stop and OCO orders emulated on the client.
+//
+// `ConditionalEngine` watches tickers and submits a market or limit order through `TradingApi`
+// once a trigger is hit. Triggers are kept in a JSON file that is rewritten on every change, so
+// they survive restarts; `reconcile` then drops or shrinks reduce-only triggers that no longer
+// match the actual positions. A trigger is removed from the file before its order is sent, so
+// a crash in between can lose an order but never send it twice.
+
+use crate::bit_ws_client::WsEvent;
+use crate::error::{BitError, BitResult};
+use crate::models::{Market, Order, OrderRequest, Side, Ticker, settle_currency};
+use crate::trading::TradingApi;
+use crate::util::now_ms;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use tokio::sync::{broadcast, mpsc};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TriggerPrice {
+    #[default]
+    Mark,
+    Last,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Condition {
+    /// Sell when the price falls to `trigger`, buy when it rises to it.
+    StopLoss { trigger: f64 },
+    /// Sell when the price rises to `trigger`, buy when it falls to it.
+    TakeProfit { trigger: f64 },
+    /// Sell once the price is `distance` below its high since the trigger was placed, buy
+    /// once it is `distance` above its low.
+    TrailingStop {
+        distance: f64,
+        #[serde(default)]
+        extreme: Option<f64>,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ConditionalOrder {
+    #[serde(default)]
+    pub id: String,
+    pub market: Market,
+    pub instrument_id: String,
+    pub side: Side,
+    pub qty: f64,
+    pub condition: Condition,
+    #[serde(default)]
+    pub trigger_price: TriggerPrice,
+    /// Limit price of the order sent on trigger, a market order when unset.
+    #[serde(default)]
+    pub limit_price: Option<f64>,
+    #[serde(default)]
+    pub reduce_only: bool,
+    /// Triggers sharing a group are one-cancels-other: the first to fire removes the rest.
+    #[serde(default)]
+    pub oco_group: Option<String>,
+    #[serde(default)]
+    pub created_at: i64,
+}
+
+impl ConditionalOrder {
+    pub fn new(
+        market: Market,
+        instrument_id: &str,
+        side: Side,
+        qty: f64,
+        condition: Condition,
+    ) -> Self {
+        Self {
+            id: String::new(),
+            market,
+            instrument_id: instrument_id.to_string(),
+            side,
+            qty,
+            condition,
+            trigger_price: TriggerPrice::default(),
+            limit_price: None,
+            reduce_only: false,
+            oco_group: None,
+            created_at: 0,
+        }
+    }
+
+    pub fn with_limit_price(mut self, price: f64) -> Self {
+        self.limit_price = Some(price);
+        self
+    }
+
+    pub fn with_trigger_price(mut self, trigger_price: TriggerPrice) -> Self {
+        self.trigger_price = trigger_price;
+        self
+    }
+
+    pub fn reduce_only(mut self) -> Self {
+        self.reduce_only = true;
+        self
+    }
+
+    pub fn with_oco_group(mut self, group: &str) -> Self {
+        self.oco_group = Some(group.to_string());
+        self
+    }
+
+    /// Update trailing state with `price` and report whether the trigger fires.
+    fn check(&mut self, price: f64) -> bool {
+        let falling = self.side == Side::Sell;
+        match &mut self.condition {
+            Condition::StopLoss { trigger } => {
+                if falling {
+                    price <= *trigger
+                } else {
+                    price >= *trigger
+                }
+            }
+            Condition::TakeProfit { trigger } => {
+                if falling {
+                    price >= *trigger
+                } else {
+                    price <= *trigger
+                }
+            }
+            Condition::TrailingStop { distance, extreme } => {
+                let best = match (*extreme, falling) {
+                    (Some(e), true) => e.max(price),
+                    (Some(e), false) => e.min(price),
+                    (None, _) => price,
+                };
+                *extreme = Some(best);
+                if falling {
+                    price <= best - *distance
+                } else {
+                    price >= best + *distance
+                }
+            }
+        }
+    }
+
+    fn order_request(&self) -> OrderRequest {
+        let mut req = match self.limit_price {
+            Some(price) => OrderRequest::limit(&self.instrument_id, self.side, self.qty, price),
+            None => OrderRequest::market(&self.instrument_id, self.side, self.qty),
+        };
+        req.reduce_only = self.reduce_only && self.market != Market::Spot;
+        req
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConditionalEvent {
+    Triggered {
+        id: String,
+        price: f64,
+        order: Order,
+    },
+    /// The trigger fired but the order was rejected. The trigger is gone either way.
+    Failed { id: String, error: String },
+    /// Removed without firing, by `cancel`, OCO or `reconcile`.
+    Removed { id: String, reason: String },
+    /// Triggers fired but could not be saved. They are restored and fire again on the next
+    /// ticker, nothing was sent.
+    SaveFailed { ids: Vec<String>, error: String },
+}
+
+/// What `reconcile` changed.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ReconcileReport {
+    pub removed: Vec<String>,
+    /// `(id, new qty)`.
+    pub resized: Vec<(String, f64)>,
+}
+
+pub struct ConditionalEngine<T> {
+    api: Arc<T>,
+    path: PathBuf,
+    orders: Mutex<BTreeMap<String, ConditionalOrder>>,
+    next_id: AtomicU64,
+    sender: broadcast::Sender<ConditionalEvent>,
+}
+
+impl<T: TradingApi> ConditionalEngine<T> {
+    /// Load the triggers stored at `path`, a missing file starts empty.
+    pub fn open(api: Arc<T>, path: impl AsRef<Path>) -> BitResult<Self> {
+        let path = path.as_ref().to_path_buf();
+        let orders: BTreeMap<String, ConditionalOrder> = match std::fs::read_to_string(&path) {
+            Ok(text) => serde_json::from_str(&text)?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => BTreeMap::new(),
+            Err(e) => return Err(BitError::Config(format!("{}: {}", path.display(), e))),
+        };
+        let (sender, _) = broadcast::channel(256);
+        Ok(Self {
+            api,
+            path,
+            orders: Mutex::new(orders),
+            next_id: AtomicU64::new(1),
+            sender,
+        })
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<ConditionalEvent> {
+        self.sender.subscribe()
+    }
+
+    pub fn orders(&self) -> Vec<ConditionalOrder> {
+        self.orders.lock().unwrap().values().cloned().collect()
+    }
+
+    /// Store a trigger and return its id.
+    pub fn add(&self, mut order: ConditionalOrder) -> BitResult<String> {
+        if !(order.qty.is_finite() && order.qty > 0.0) {
+            return Err(BitError::Invalid("qty must be positive".to_string()));
+        }
+        order.id = format!(
+            "cond-{}-{}",
+            now_ms(),
+            self.next_id.fetch_add(1, Ordering::Relaxed)
+        );
+        order.created_at = now_ms();
+        let id = order.id.clone();
+        let mut orders = self.orders.lock().unwrap();
+        orders.insert(id.clone(), order);
+        self.save(&orders)?;
+        Ok(id)
+    }
+
+    /// Remove a trigger without firing it. Returns false for an unknown id.
+    pub fn cancel(&self, id: &str) -> BitResult<bool> {
+        let mut orders = self.orders.lock().unwrap();
+        if orders.remove(id).is_none() {
+            return Ok(false);
+        }
+        self.save(&orders)?;
+        drop(orders);
+        self.emit(ConditionalEvent::Removed {
+            id: id.to_string(),
+            reason: "cancelled".to_string(),
+        });
+        Ok(true)
+    }
+
+    fn emit(&self, event: ConditionalEvent) {
+        // no subscribers is not an error
+        let _ = self.sender.send(event);
+    }
+
+    // write to a temp file and rename, so a crash never leaves a half-written file
+    fn save(&self, orders: &BTreeMap<String, ConditionalOrder>) -> BitResult<()> {
+        let io_err =
+            |e: std::io::Error| BitError::Config(format!("{}: {}", self.path.display(), e));
+        let tmp = self.path.with_extension("tmp");
+        std::fs::write(&tmp, serde_json::to_string_pretty(orders)?).map_err(io_err)?;
+        std::fs::rename(&tmp, &self.path).map_err(io_err)
+    }
+
+    /// Check the triggers of the ticker's instrument and send the orders of those that fire.
+    pub async fn on_ticker(&self, ticker: &Ticker) -> BitResult<()> {
+        let fired = {
+            let mut orders = self.orders.lock().unwrap();
+            let mut fired = vec![];
+            let mut changed = false;
+            for order in orders.values_mut() {
+                if order.instrument_id != ticker.instrument_id {
+                    continue;
+                }
+                let price = match order.trigger_price {
+                    TriggerPrice::Mark if ticker.mark_price > 0.0 => ticker.mark_price,
+                    _ => ticker.last_price,
+                };
+                if price <= 0.0 {
+                    continue;
+                }
+                let before = order.condition.clone();
+                if order.check(price) {
+                    fired.push((order.clone(), price));
+                }
+                changed |= order.condition != before;
+            }
+
+            // a fired order already removed as the OCO sibling of an earlier one does not fire
+            let mut firing = vec![];
+            let mut removed: Vec<ConditionalOrder> = vec![];
+            for (order, price) in fired {
+                if orders.remove(&order.id).is_none() {
+                    continue;
+                }
+                if let Some(group) = &order.oco_group {
+                    let siblings: Vec<String> = orders
+                        .values()
+                        .filter(|o| o.oco_group.as_ref() == Some(group))
+                        .map(|o| o.id.clone())
+                        .collect();
+                    for id in siblings {
+                        removed.extend(orders.remove(&id));
+                    }
+                }
+                firing.push((order, price));
+            }
+            if (changed || !firing.is_empty())
+                && let Err(e) = self.save(&orders)
+            {
+                // put them back rather than send orders the file does not know are gone
+                let ids = firing.iter().map(|(o, _)| o.id.clone()).collect();
+                for order in firing.into_iter().map(|(o, _)| o).chain(removed) {
+                    orders.insert(order.id.clone(), order);
+                }
+                drop(orders);
+                self.emit(ConditionalEvent::SaveFailed {
+                    ids,
+                    error: e.to_string(),
+                });
+                return Err(e);
+            }
+            drop(orders);
+            for ConditionalOrder { id, .. } in removed {
+                self.emit(ConditionalEvent::Removed {
+                    id,
+                    reason: "oco".to_string(),
+                });
+            }
+            firing
+        };
+
+        for (order, price) in fired {
+            let event = match self
+                .api
+                .new_order(order.market, &order.order_request())
+                .await
+            {
+                Ok(sent) => ConditionalEvent::Triggered {
+                    id: order.id,
+                    price,
+                    order: sent,
+                },
+                Err(e) => ConditionalEvent::Failed {
+                    id: order.id,
+                    error: e.to_string(),
+                },
+            };
+            self.emit(event);
+        }
+        Ok(())
+    }
+
+    /// Feed tickers from a WebSocket event stream until it closes. A failed write is reported
+    /// as `SaveFailed` and retried on the next ticker.
+    pub async fn run(&self, mut events: mpsc::Receiver<WsEvent>) -> BitResult<()> {
+        while let Some(event) = events.recv().await {
+            let _ = match event {
+                WsEvent::Ticker(ticker) => self.on_ticker(&ticker).await,
+                WsEvent::OptionTicker(ticker) => self.on_ticker(&ticker.ticker).await,
+                _ => Ok(()),
+            };
+        }
+        Ok(())
+    }
+
+    /// Align reduce-only triggers of `currency` with the actual positions, e.g. after a
+    /// restart: triggers that would no longer reduce a position are removed, and triggers
+    /// larger than their position are shrunk to it. Spot triggers are left alone.
+    pub async fn reconcile(&self, currency: &str) -> BitResult<ReconcileReport> {
+        let positions: BTreeMap<String, f64> = self
+            .api
+            .positions(currency)
+            .await?
+            .into_iter()
+            .map(|p| (p.instrument_id, p.qty))
+            .collect();
+
+        let mut report = ReconcileReport::default();
+        let mut orders = self.orders.lock().unwrap();
+        for order in orders.values_mut() {
+            if !order.reduce_only
+                || order.market == Market::Spot
+                || settle_currency(&order.instrument_id) != currency
+            {
+                continue;
+            }
+            let qty = positions.get(&order.instrument_id).copied().unwrap_or(0.0);
+            // a reducing order trades against the position's sign
+            let reducible = -order.side.sign() * qty;
+            if reducible <= 0.0 {
+                report.removed.push(order.id.clone());
+            } else if order.qty > reducible {
+                order.qty = reducible;
+                report.resized.push((order.id.clone(), reducible));
+            }
+        }
+        for id in &report.removed {
+            orders.remove(id);
+        }
+        if !report.removed.is_empty() || !report.resized.is_empty() {
+            self.save(&orders)?;
+        }
+        drop(orders);
+        for id in &report.removed {
+            self.emit(ConditionalEvent::Removed {
+                id: id.clone(),
+                reason: "no position to reduce".to_string(),
+            });
+        }
+        Ok(report)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::matching::FeeSchedule;
+    use crate::models::Depth;
+    use crate::paper::PaperExchange;
+    use std::collections::HashMap;
+
+    const INST: &str = "BTC-USDT-PERPETUAL";
+
+    fn trigger(side: Side, condition: Condition) -> ConditionalOrder {
+        ConditionalOrder::new(Market::Linear, INST, side, 1.0, condition)
+    }
+
+    #[test]
+    fn stop_loss_fires_against_the_position() {
+        let mut sell = trigger(Side::Sell, Condition::StopLoss { trigger: 95.0 });
+        assert!(!sell.check(96.0));
+        assert!(sell.check(95.0));
+        let mut buy = trigger(Side::Buy, Condition::StopLoss { trigger: 105.0 });
+        assert!(!buy.check(104.0));
+        assert!(buy.check(106.0));
+    }
+
+    #[test]
+    fn take_profit_fires_with_the_position() {
+        let mut sell = trigger(Side::Sell, Condition::TakeProfit { trigger: 105.0 });
+        assert!(!sell.check(104.0));
+        assert!(sell.check(105.0));
+        let mut buy = trigger(Side::Buy, Condition::TakeProfit { trigger: 95.0 });
+        assert!(!buy.check(96.0));
+        assert!(buy.check(94.0));
+    }
+
+    #[test]
+    fn trailing_stop_follows_the_extreme() {
+        let condition = Condition::TrailingStop {
+            distance: 5.0,
+            extreme: None,
+        };
+        let mut sell = trigger(Side::Sell, condition.clone());
+        for price in [100.0, 110.0, 106.0] {
+            assert!(!sell.check(price));
+        }
+        assert_eq!(
+            sell.condition,
+            Condition::TrailingStop {
+                distance: 5.0,
+                extreme: Some(110.0)
+            }
+        );
+        assert!(sell.check(105.0));
+
+        let mut buy = trigger(Side::Buy, condition);
+        for price in [100.0, 90.0, 94.0] {
+            assert!(!buy.check(price));
+        }
+        assert!(buy.check(95.0));
+    }
+
+    #[tokio::test]
+    async fn oco_fires_one_trigger_and_removes_its_siblings() {
+        let path = std::env::temp_dir().join(format!("bitsdk-oco-{}.json", std::process::id()));
+        let api = Arc::new(PaperExchange::new(
+            FeeSchedule::default(),
+            HashMap::from([("USDT".to_string(), 1e6)]),
+        ));
+        api.on_market_event(&WsEvent::Depth(Depth {
+            instrument_id: INST.to_string(),
+            kind: "snapshot".to_string(),
+            sequence: 1,
+            bids: vec![(94.0, 10.0)],
+            asks: vec![(95.0, 10.0)],
+            ..Default::default()
+        }));
+        let engine = ConditionalEngine::open(api, &path).unwrap();
+        let mut events = engine.subscribe();
+        let stop = engine
+            .add(trigger(Side::Sell, Condition::StopLoss { trigger: 95.0 }).with_oco_group("tp-sl"))
+            .unwrap();
+        let take = engine
+            .add(
+                trigger(Side::Sell, Condition::TakeProfit { trigger: 105.0 })
+                    .with_oco_group("tp-sl"),
+            )
+            .unwrap();
+        let other = engine
+            .add(trigger(Side::Sell, Condition::StopLoss { trigger: 90.0 }))
+            .unwrap();
+
+        let ticker = Ticker {
+            instrument_id: INST.to_string(),
+            mark_price: 94.0,
+            last_price: 94.0,
+            ..Default::default()
+        };
+        engine.on_ticker(&ticker).await.unwrap();
+
+        let left: Vec<String> = engine.orders().into_iter().map(|o| o.id).collect();
+        assert_eq!(left, [other]);
+        assert_eq!(
+            events.try_recv().unwrap(),
+            ConditionalEvent::Removed {
+                id: take,
+                reason: "oco".to_string()
+            }
+        );
+        assert!(matches!(
+            events.try_recv().unwrap(),
+            ConditionalEvent::Triggered { id, order, .. } if id == stop && order.side == Side::Sell
+        ));
+        assert!(events.try_recv().is_err());
+        std::fs::remove_file(&path).unwrap();
+    }
+}
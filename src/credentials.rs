@@ -0,0 +1,342 @@
+// Where API keys come from. Secrets are held in zeroizing buffers and never printed.
+
+use crate::error::{BitError, BitResult};
+use chacha20poly1305::aead::rand_core::RngCore;
+use chacha20poly1305::aead::{Aead, KeyInit, OsRng};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use serde::{Deserialize, Deserializer, Serialize};
+use std::fmt;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use zeroize::Zeroizing;
+
+/// A string that is wiped from memory on drop and redacted in `Debug`/`Display`.
+#[derive(Clone, Default, PartialEq, Eq)]
+pub struct SecretString(Zeroizing<String>);
+
+impl SecretString {
+    pub fn new(secret: String) -> Self {
+        Self(Zeroizing::new(secret))
+    }
+
+    pub fn expose(&self) -> &str {
+        &self.0
+    }
+}
+
+impl From<String> for SecretString {
+    fn from(secret: String) -> Self {
+        Self::new(secret)
+    }
+}
+
+impl From<&str> for SecretString {
+    fn from(secret: &str) -> Self {
+        Self::new(secret.to_string())
+    }
+}
+
+impl fmt::Debug for SecretString {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("\"***\"")
+    }
+}
+
+impl fmt::Display for SecretString {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("***")
+    }
+}
+
+impl<'de> Deserialize<'de> for SecretString {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer).map(Self::new)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct Credentials {
+    pub access_key: String,
+    pub secret_key: SecretString,
+}
+
+impl Credentials {
+    pub fn new(access_key: &str, secret_key: impl Into<SecretString>) -> Self {
+        Self {
+            access_key: access_key.to_string(),
+            secret_key: secret_key.into(),
+        }
+    }
+}
+
+/// Loads credentials on demand. `load` is called again on every rotation check, so an
+/// implementation should always return the current key rather than caching it.
+pub trait CredentialProvider: Send + Sync {
+    fn load(&self) -> BitResult<Credentials>;
+}
+
+// fixed keys, e.g. read from a config file by the caller
+impl CredentialProvider for Credentials {
+    fn load(&self) -> BitResult<Credentials> {
+        Ok(self.clone())
+    }
+}
+
+fn config_err(context: &str, e: impl fmt::Display) -> BitError {
+    BitError::Config(format!("{}: {}", context, e))
+}
+
+/////////////////////////////////////////
+// environment variables
+/////////////////////////////////////////
+pub struct EnvProvider {
+    access_key_var: String,
+    secret_key_var: String,
+}
+
+impl EnvProvider {
+    pub fn new(access_key_var: &str, secret_key_var: &str) -> Self {
+        Self {
+            access_key_var: access_key_var.to_string(),
+            secret_key_var: secret_key_var.to_string(),
+        }
+    }
+}
+
+// BITCOM_AK / BITCOM_SK, same as the examples
+impl Default for EnvProvider {
+    fn default() -> Self {
+        Self::new("BITCOM_AK", "BITCOM_SK")
+    }
+}
+
+impl CredentialProvider for EnvProvider {
+    fn load(&self) -> BitResult<Credentials> {
+        let access_key =
+            std::env::var(&self.access_key_var).map_err(|e| config_err(&self.access_key_var, e))?;
+        let secret_key =
+            std::env::var(&self.secret_key_var).map_err(|e| config_err(&self.secret_key_var, e))?;
+        Ok(Credentials::new(&access_key, secret_key))
+    }
+}
+
+/////////////////////////////////////////
+// encrypted key file
+/////////////////////////////////////////
+const KEYFILE_VERSION: u32 = 1;
+const KEYFILE_PBKDF2_ROUNDS: u32 = 600_000;
+
+/// On-disk format: the JSON `{"access_key", "secret_key"}` sealed with ChaCha20-Poly1305
+/// under a key derived from a passphrase with PBKDF2-HMAC-SHA256.
+#[derive(Debug, Serialize, Deserialize)]
+struct KeyFile {
+    version: u32,
+    rounds: u32,
+    salt: String,
+    nonce: String,
+    ciphertext: String,
+}
+
+#[derive(Serialize)]
+struct KeyFilePlain<'a> {
+    access_key: &'a str,
+    secret_key: &'a str,
+}
+
+fn derive_key(passphrase: &SecretString, salt: &[u8], rounds: u32) -> Zeroizing<[u8; 32]> {
+    let mut key = Zeroizing::new([0u8; 32]);
+    pbkdf2::pbkdf2_hmac::<sha2::Sha256>(passphrase.expose().as_bytes(), salt, rounds, &mut *key);
+    key
+}
+
+pub struct KeyFileProvider {
+    path: PathBuf,
+    passphrase: SecretString,
+}
+
+impl KeyFileProvider {
+    pub fn new(path: impl Into<PathBuf>, passphrase: impl Into<SecretString>) -> Self {
+        Self {
+            path: path.into(),
+            passphrase: passphrase.into(),
+        }
+    }
+
+    /// Passphrase is read from the environment variable `passphrase_var`.
+    pub fn from_env(path: impl Into<PathBuf>, passphrase_var: &str) -> BitResult<Self> {
+        let passphrase =
+            std::env::var(passphrase_var).map_err(|e| config_err(passphrase_var, e))?;
+        Ok(Self::new(path, passphrase))
+    }
+
+    /// Encrypt `credentials` into a new key file at `path`.
+    pub fn write(
+        path: impl AsRef<Path>,
+        credentials: &Credentials,
+        passphrase: &SecretString,
+    ) -> BitResult<()> {
+        let mut salt = [0u8; 16];
+        OsRng.fill_bytes(&mut salt);
+        let mut nonce = [0u8; 12];
+        OsRng.fill_bytes(&mut nonce);
+
+        let key = derive_key(passphrase, &salt, KEYFILE_PBKDF2_ROUNDS);
+        let plain = Zeroizing::new(serde_json::to_vec(&KeyFilePlain {
+            access_key: &credentials.access_key,
+            secret_key: credentials.secret_key.expose(),
+        })?);
+        let ciphertext = ChaCha20Poly1305::new(Key::from_slice(&*key))
+            .encrypt(Nonce::from_slice(&nonce), plain.as_slice())
+            .map_err(|e| config_err("encrypt key file", e))?;
+
+        let file = KeyFile {
+            version: KEYFILE_VERSION,
+            rounds: KEYFILE_PBKDF2_ROUNDS,
+            salt: hex::encode(salt),
+            nonce: hex::encode(nonce),
+            ciphertext: hex::encode(ciphertext),
+        };
+        let path = path.as_ref();
+        let context = path.display().to_string();
+        let mut options = std::fs::OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+        // owner only, the file can be attacked offline with a passphrase guesser
+        #[cfg(unix)]
+        std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+        let mut out = options.open(path).map_err(|e| config_err(&context, e))?;
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            // `mode` only applies when the file is created
+            out.set_permissions(std::fs::Permissions::from_mode(0o600))
+                .map_err(|e| config_err(&context, e))?;
+        }
+        out.write_all(serde_json::to_string_pretty(&file)?.as_bytes())
+            .map_err(|e| config_err(&context, e))
+    }
+}
+
+impl CredentialProvider for KeyFileProvider {
+    fn load(&self) -> BitResult<Credentials> {
+        let context = self.path.display().to_string();
+        let text = std::fs::read_to_string(&self.path).map_err(|e| config_err(&context, e))?;
+        let file: KeyFile = serde_json::from_str(&text)?;
+        if file.version != KEYFILE_VERSION {
+            return Err(config_err(
+                &context,
+                format!("unsupported version {}", file.version),
+            ));
+        }
+
+        let salt = hex::decode(&file.salt).map_err(|e| config_err(&context, e))?;
+        let nonce = hex::decode(&file.nonce).map_err(|e| config_err(&context, e))?;
+        let ciphertext = hex::decode(&file.ciphertext).map_err(|e| config_err(&context, e))?;
+        if nonce.len() != 12 {
+            return Err(config_err(&context, "bad nonce"));
+        }
+        // a tampered file could otherwise lower the cost of guessing the passphrase
+        if file.rounds < KEYFILE_PBKDF2_ROUNDS {
+            return Err(config_err(
+                &context,
+                format!(
+                    "{} PBKDF2 rounds, at least {} required",
+                    file.rounds, KEYFILE_PBKDF2_ROUNDS
+                ),
+            ));
+        }
+
+        let key = derive_key(&self.passphrase, &salt, file.rounds);
+        let plain = Zeroizing::new(
+            ChaCha20Poly1305::new(Key::from_slice(&*key))
+                .decrypt(Nonce::from_slice(&nonce), ciphertext.as_slice())
+                .map_err(|_| config_err(&context, "wrong passphrase or corrupted file"))?,
+        );
+        Ok(serde_json::from_slice(&plain)?)
+    }
+}
+
+/////////////////////////////////////////
+// OS keyring
+/////////////////////////////////////////
+/// Secret key stored in the platform keyring (Keychain, Credential Manager, kernel keyutils)
+/// under `service`, with the access key as the user name.
+#[cfg(feature = "keyring")]
+pub struct KeyringProvider {
+    service: String,
+    access_key: String,
+}
+
+#[cfg(feature = "keyring")]
+impl KeyringProvider {
+    pub fn new(service: &str, access_key: &str) -> Self {
+        Self {
+            service: service.to_string(),
+            access_key: access_key.to_string(),
+        }
+    }
+
+    fn entry(&self) -> BitResult<keyring::Entry> {
+        keyring::Entry::new(&self.service, &self.access_key)
+            .map_err(|e| config_err(&self.service, e))
+    }
+
+    pub fn store(&self, secret_key: &SecretString) -> BitResult<()> {
+        self.entry()?
+            .set_password(secret_key.expose())
+            .map_err(|e| config_err(&self.service, e))
+    }
+}
+
+#[cfg(feature = "keyring")]
+impl CredentialProvider for KeyringProvider {
+    fn load(&self) -> BitResult<Credentials> {
+        let secret_key = self
+            .entry()?
+            .get_password()
+            .map_err(|e| config_err(&self.service, e))?;
+        Ok(Credentials::new(&self.access_key, secret_key))
+    }
+}
+
+/////////////////////////////////////////
+// external command
+/////////////////////////////////////////
+/// Runs a command (e.g. `pass show bitcom/sk` or `vault kv get -field=sk ...`) that prints
+/// the secret key on stdout.
+pub struct CommandProvider {
+    access_key: String,
+    program: String,
+    args: Vec<String>,
+}
+
+impl CommandProvider {
+    pub fn new(access_key: &str, program: &str, args: &[&str]) -> Self {
+        Self {
+            access_key: access_key.to_string(),
+            program: program.to_string(),
+            args: args.iter().map(|a| a.to_string()).collect(),
+        }
+    }
+}
+
+impl CredentialProvider for CommandProvider {
+    fn load(&self) -> BitResult<Credentials> {
+        let output = Command::new(&self.program)
+            .args(&self.args)
+            .output()
+            .map_err(|e| config_err(&self.program, e))?;
+        let stdout = Zeroizing::new(output.stdout);
+        if !output.status.success() {
+            return Err(config_err(&self.program, output.status));
+        }
+
+        let secret_key = std::str::from_utf8(&stdout)
+            .map_err(|e| config_err(&self.program, e))?
+            .trim();
+        if secret_key.is_empty() {
+            return Err(config_err(&self.program, "printed no secret"));
+        }
+        Ok(Credentials::new(&self.access_key, secret_key))
+    }
+}
@@ -0,0 +1,94 @@
+use serde::Deserialize;
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+use std::fmt;
+
+/// Errors raised by the typed layers built on top of `BitRestClient`.
+/// The raw `Value` methods keep returning `reqwest::Error`.
+#[derive(Debug)]
+pub enum BitError {
+    Http(reqwest::Error),
+    Api {
+        code: i64,
+        message: String,
+    },
+    Decode(String),
+    Config(String),
+    /// Rejected locally before anything was sent.
+    Invalid(String),
+}
+
+pub type BitResult<T> = Result<T, BitError>;
+
+impl fmt::Display for BitError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BitError::Http(e) => write!(f, "http error: {}", e),
+            BitError::Api { code, message } => write!(f, "api error {}: {}", code, message),
+            BitError::Decode(msg) => write!(f, "decode error: {}", msg),
+            BitError::Config(msg) => write!(f, "config error: {}", msg),
+            BitError::Invalid(msg) => write!(f, "invalid request: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for BitError {}
+
+impl From<reqwest::Error> for BitError {
+    fn from(e: reqwest::Error) -> Self {
+        BitError::Http(e)
+    }
+}
+
+impl From<serde_json::Error> for BitError {
+    fn from(e: serde_json::Error) -> Self {
+        BitError::Decode(e.to_string())
+    }
+}
+
+/// Check the `code` of a bit.com response and decode its `data` field.
+pub fn parse_data<T: DeserializeOwned>(rsp: Value) -> BitResult<T> {
+    let code = rsp["code"].as_i64().unwrap_or(-1);
+    if code != 0 {
+        let message = match &rsp["message"] {
+            Value::String(s) => s.clone(),
+            Value::Null => rsp.to_string(),
+            v => v.to_string(),
+        };
+        return Err(BitError::Api { code, message });
+    }
+    Ok(serde_json::from_value(rsp["data"].clone())?)
+}
+
+/// `parse_data` on a raw response body. A successful response is decoded straight into `T`,
+/// anything else goes through `parse_data` for its error.
+pub fn parse_body<T: DeserializeOwned>(body: &[u8]) -> BitResult<T> {
+    #[derive(Deserialize)]
+    struct Envelope<T> {
+        code: i64,
+        data: T,
+    }
+    match serde_json::from_slice::<Envelope<T>>(body) {
+        Ok(Envelope { code: 0, data }) => Ok(data),
+        _ => parse_data(
+            serde_json::from_slice(body)
+                .unwrap_or_else(|_| Value::String(String::from_utf8_lossy(body).into_owned())),
+        ),
+    }
+}
+
+/// One page of a paged endpoint.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    pub has_more: bool,
+}
+
+/// `parse_data` for paged endpoints. They report `page_info.has_more`; without it a full
+/// page of `limit` items means there may be more.
+pub fn parse_page<T: DeserializeOwned>(rsp: Value, limit: u32) -> BitResult<Page<T>> {
+    let has_more = rsp["page_info"]["has_more"].as_bool();
+    let items: Vec<T> = parse_data(rsp)?;
+    let has_more = has_more.unwrap_or(items.len() as u32 >= limit);
+    Ok(Page { items, has_more })
+}
@@ -0,0 +1,244 @@
+// Client order ids.
+//
+// bit.com knows an order by its exchange `order_id` and by an optional client-chosen `label`.
+// `LabeledApi` wraps a `TradingApi` and gives every new order without a label a unique one from
+// its `LabelGenerator`, so after a timeout or a restart a strategy can ask the exchange whether
+// an order it sent made it. The `*_by_label` helpers resolve a label to its exchange order id
+// before acting on it.
+
+use crate::error::{BitError, BitResult};
+use crate::models::{
+    AmendRequest, CancelRequest, Market, Order, OrderRequest, Position, Trade, UmAccount,
+};
+use crate::trading::TradingApi;
+use crate::util::now_ms;
+use rand::Rng;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+fn base36(mut n: u64) -> String {
+    const DIGITS: &[u8] = b"0123456789abcdefghijklmnopqrstuvwxyz";
+    let mut out = vec![];
+    loop {
+        out.push(DIGITS[(n % 36) as usize]);
+        n /= 36;
+        if n == 0 {
+            break;
+        }
+    }
+    out.reverse();
+    String::from_utf8(out).unwrap()
+}
+
+/// Labels of the form `{prefix}-{session}-{seq}`. The session part is the start time plus a
+/// random suffix, so labels stay unique across restarts and between processes sharing a
+/// prefix.
+#[derive(Debug)]
+pub struct LabelGenerator {
+    prefix: String,
+    session: String,
+    seq: AtomicU64,
+}
+
+impl LabelGenerator {
+    pub fn new(prefix: &str) -> Self {
+        let salt: u32 = rand::thread_rng().gen_range(0..36 * 36 * 36);
+        Self {
+            prefix: prefix.to_string(),
+            session: format!("{}{:0>3}", base36(now_ms() as u64), base36(salt as u64)),
+            seq: AtomicU64::new(1),
+        }
+    }
+
+    pub fn prefix(&self) -> &str {
+        &self.prefix
+    }
+
+    pub fn next_label(&self) -> String {
+        let seq = self.seq.fetch_add(1, Ordering::Relaxed);
+        format!("{}-{}-{}", self.prefix, self.session, base36(seq))
+    }
+
+    /// Whether `label` came from a generator with this prefix, in this or an earlier session.
+    pub fn is_ours(&self, label: &str) -> bool {
+        label
+            .strip_prefix(&self.prefix)
+            .is_some_and(|rest| rest.starts_with('-'))
+    }
+}
+
+/// A `TradingApi` that labels every new order that has no label yet. Returned orders always
+/// carry their label, also when the exchange leaves it out of the response.
+pub struct LabeledApi<T> {
+    inner: Arc<T>,
+    labels: LabelGenerator,
+}
+
+impl<T: TradingApi> LabeledApi<T> {
+    pub fn new(inner: Arc<T>, prefix: &str) -> Self {
+        Self {
+            inner,
+            labels: LabelGenerator::new(prefix),
+        }
+    }
+
+    pub fn inner(&self) -> &Arc<T> {
+        &self.inner
+    }
+
+    pub fn labels(&self) -> &LabelGenerator {
+        &self.labels
+    }
+
+    fn labeled(&self, req: &OrderRequest) -> OrderRequest {
+        let mut req = req.clone();
+        if req.label.is_none() {
+            req.label = Some(self.labels.next_label());
+        }
+        req
+    }
+
+    async fn resolve(&self, market: Market, currency: &str, label: &str) -> BitResult<Order> {
+        self.inner
+            .order_by_label(market, currency, label)
+            .await?
+            .ok_or_else(|| BitError::Invalid(format!("no order with label {}", label)))
+    }
+
+    /// Amend the order carrying `label`. Fails when it is unknown or no longer open.
+    pub async fn amend_by_label(
+        &self,
+        market: Market,
+        currency: &str,
+        label: &str,
+        price: Option<f64>,
+        qty: Option<f64>,
+    ) -> BitResult<Order> {
+        let order = self.resolve(market, currency, label).await?;
+        if !order.is_open() {
+            return Err(BitError::Invalid(format!(
+                "order with label {} is {}",
+                label, order.status
+            )));
+        }
+        let req = AmendRequest {
+            instrument_id: order.instrument_id,
+            order_id: Some(order.order_id),
+            label: None,
+            price,
+            qty,
+        };
+        self.amend_order(market, &req).await
+    }
+
+    /// Cancel the order carrying `label`. Returns 0 when it is no longer open.
+    pub async fn cancel_by_label(
+        &self,
+        market: Market,
+        currency: &str,
+        label: &str,
+    ) -> BitResult<i64> {
+        let order = self.resolve(market, currency, label).await?;
+        if !order.is_open() {
+            return Ok(0);
+        }
+        let req = CancelRequest::by_order_id(&order.instrument_id, &order.order_id);
+        self.cancel_order(market, &req).await
+    }
+}
+
+impl<T: TradingApi> TradingApi for LabeledApi<T> {
+    async fn new_order(&self, market: Market, req: &OrderRequest) -> BitResult<Order> {
+        let req = self.labeled(req);
+        let mut order = self.inner.new_order(market, &req).await?;
+        if order.label.is_empty() {
+            order.label = req.label.unwrap_or_default();
+        }
+        Ok(order)
+    }
+
+    async fn amend_order(&self, market: Market, req: &AmendRequest) -> BitResult<Order> {
+        self.inner.amend_order(market, req).await
+    }
+
+    async fn cancel_order(&self, market: Market, req: &CancelRequest) -> BitResult<i64> {
+        self.inner.cancel_order(market, req).await
+    }
+
+    async fn batch_new_orders(
+        &self,
+        market: Market,
+        reqs: &[OrderRequest],
+    ) -> BitResult<Vec<Order>> {
+        let reqs: Vec<OrderRequest> = reqs.iter().map(|r| self.labeled(r)).collect();
+        let mut orders = self.inner.batch_new_orders(market, &reqs).await?;
+        // batch responses come back in request order
+        if orders.len() == reqs.len() {
+            for (order, req) in orders.iter_mut().zip(&reqs) {
+                if order.label.is_empty() {
+                    order.label = req.label.clone().unwrap_or_default();
+                }
+            }
+        }
+        Ok(orders)
+    }
+
+    async fn batch_amend_orders(
+        &self,
+        market: Market,
+        reqs: &[AmendRequest],
+    ) -> BitResult<Vec<Order>> {
+        self.inner.batch_amend_orders(market, reqs).await
+    }
+
+    async fn open_orders(
+        &self,
+        market: Market,
+        currency: &str,
+        instrument_id: Option<&str>,
+    ) -> BitResult<Vec<Order>> {
+        self.inner
+            .open_orders(market, currency, instrument_id)
+            .await
+    }
+
+    async fn order_by_label(
+        &self,
+        market: Market,
+        currency: &str,
+        label: &str,
+    ) -> BitResult<Option<Order>> {
+        self.inner.order_by_label(market, currency, label).await
+    }
+
+    async fn user_trades(
+        &self,
+        market: Market,
+        currency: &str,
+        instrument_id: Option<&str>,
+    ) -> BitResult<Vec<Trade>> {
+        self.inner
+            .user_trades(market, currency, instrument_id)
+            .await
+    }
+
+    async fn user_trades_since(
+        &self,
+        market: Market,
+        currency: &str,
+        instrument_id: Option<&str>,
+        start_ms: i64,
+    ) -> BitResult<Vec<Trade>> {
+        self.inner
+            .user_trades_since(market, currency, instrument_id, start_ms)
+            .await
+    }
+
+    async fn positions(&self, currency: &str) -> BitResult<Vec<Position>> {
+        self.inner.positions(currency).await
+    }
+
+    async fn um_account(&self) -> BitResult<UmAccount> {
+        self.inner.um_account().await
+    }
+}
@@ -0,0 +1,196 @@
+// Wire latency: local timestamps on REST responses and WebSocket messages, and percentile
+// summaries per endpoint and per channel.
+//
+// A `Stamp` pairs the wall clock, comparable with exchange timestamps, with a monotonic
+// instant, for intervals that clock adjustments cannot distort. REST round trips only use the
+// monotonic side. One-way latency needs the exchange timestamp and so includes the offset
+// between the local and the exchange clock.
+
+use crate::util::now_ms;
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Stamp {
+    pub wall_ms: i64,
+    pub mono: Instant,
+}
+
+impl Stamp {
+    pub fn now() -> Self {
+        Self {
+            wall_ms: now_ms(),
+            mono: Instant::now(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Timing {
+    /// When the request went out, REST only. Taken after signing, right before sending.
+    pub sent: Option<Stamp>,
+    pub received: Stamp,
+    /// The `timestamp` of the payload, in milliseconds, when it has one.
+    pub exchange_ms: Option<i64>,
+}
+
+impl Timing {
+    pub fn round_trip(&self) -> Option<Duration> {
+        self.sent
+            .map(|sent| self.received.mono.saturating_duration_since(sent.mono))
+    }
+
+    /// Half the round trip, a one-way estimate independent of clocks for a symmetric path.
+    pub fn half_round_trip(&self) -> Option<Duration> {
+        self.round_trip().map(|rtt| rtt / 2)
+    }
+
+    /// Receive time minus the exchange timestamp: one-way latency plus clock offset.
+    pub fn one_way_ms(&self) -> Option<i64> {
+        self.exchange_ms.map(|ts| self.received.wall_ms - ts)
+    }
+
+    /// How far the exchange clock is ahead of ours, assuming the exchange stamped the
+    /// response halfway through the round trip. REST only.
+    pub fn clock_offset_ms(&self) -> Option<f64> {
+        let sent = self.sent?;
+        let ts = self.exchange_ms?;
+        let midpoint = sent.wall_ms as f64 + self.round_trip()?.as_secs_f64() * 500.0;
+        Some(ts as f64 - midpoint)
+    }
+}
+
+/// A value with the timestamps of the message it came in.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Timed<T> {
+    pub value: T,
+    pub timing: Timing,
+}
+
+/// The exchange timestamp of a REST response or WebSocket message: top-level `timestamp`,
+/// else `data.timestamp`.
+pub fn exchange_timestamp(message: &Value) -> Option<i64> {
+    message["timestamp"]
+        .as_i64()
+        .or_else(|| message["data"]["timestamp"].as_i64())
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize)]
+pub struct LatencyStats {
+    /// Samples ever recorded. The percentiles cover the most recent `window` of them.
+    pub count: u64,
+    pub mean_ms: f64,
+    pub p50_ms: f64,
+    pub p90_ms: f64,
+    pub p99_ms: f64,
+    pub max_ms: f64,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct LatencySummary {
+    /// REST round trips per endpoint path.
+    pub endpoints: BTreeMap<String, LatencyStats>,
+    /// WebSocket one-way latency per channel, see `Timing::one_way_ms`.
+    pub channels: BTreeMap<String, LatencyStats>,
+}
+
+#[derive(Debug, Default)]
+struct Window {
+    samples: Vec<f64>,
+    next: usize,
+    count: u64,
+}
+
+impl Window {
+    fn push(&mut self, ms: f64, capacity: usize) {
+        if self.samples.len() < capacity {
+            self.samples.push(ms);
+        } else {
+            self.samples[self.next] = ms;
+            self.next = (self.next + 1) % capacity;
+        }
+        self.count += 1;
+    }
+
+    fn stats(&self) -> LatencyStats {
+        let mut sorted = self.samples.clone();
+        sorted.sort_by(f64::total_cmp);
+        let at = |q: f64| {
+            let i = ((sorted.len() as f64 - 1.0) * q).round() as usize;
+            sorted.get(i).copied().unwrap_or(0.0)
+        };
+        LatencyStats {
+            count: self.count,
+            mean_ms: sorted.iter().sum::<f64>() / sorted.len().max(1) as f64,
+            p50_ms: at(0.5),
+            p90_ms: at(0.9),
+            p99_ms: at(0.99),
+            max_ms: sorted.last().copied().unwrap_or(0.0),
+        }
+    }
+}
+
+/// Latency samples per endpoint and channel, keeping the last `window` of each. Hand one to
+/// `BitRestClient::with_latency_tracker` and `BitWsClient::with_latency_tracker`.
+#[derive(Debug)]
+pub struct LatencyTracker {
+    window: usize,
+    endpoints: Mutex<HashMap<String, Window>>,
+    channels: Mutex<HashMap<String, Window>>,
+}
+
+impl Default for LatencyTracker {
+    fn default() -> Self {
+        Self::new(10_000)
+    }
+}
+
+fn record(map: &Mutex<HashMap<String, Window>>, key: &str, ms: f64, window: usize) {
+    let mut map = map.lock().unwrap();
+    match map.get_mut(key) {
+        Some(w) => w.push(ms, window),
+        None => map.entry(key.to_string()).or_default().push(ms, window),
+    }
+}
+
+fn stats(map: &Mutex<HashMap<String, Window>>) -> BTreeMap<String, LatencyStats> {
+    map.lock()
+        .unwrap()
+        .iter()
+        .map(|(k, w)| (k.clone(), w.stats()))
+        .collect()
+}
+
+impl LatencyTracker {
+    pub fn new(window: usize) -> Self {
+        Self {
+            window: window.max(1),
+            endpoints: Mutex::new(HashMap::new()),
+            channels: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn record_rest(&self, endpoint: &str, round_trip: Duration) {
+        let ms = round_trip.as_secs_f64() * 1000.0;
+        record(&self.endpoints, endpoint, ms, self.window);
+    }
+
+    pub fn record_ws(&self, channel: &str, one_way_ms: i64) {
+        record(&self.channels, channel, one_way_ms as f64, self.window);
+    }
+
+    pub fn summary(&self) -> LatencySummary {
+        LatencySummary {
+            endpoints: stats(&self.endpoints),
+            channels: stats(&self.channels),
+        }
+    }
+
+    pub fn reset(&self) {
+        self.endpoints.lock().unwrap().clear();
+        self.channels.lock().unwrap().clear();
+    }
+}
@@ -0,0 +1,326 @@
+// Transaction log export for accounting.
+//
+// Walks the spot or UM transaction log over a date range in fixed windows, pages through each
+// window, normalizes entries into `LedgerRecord`s and drops the duplicates that overlapping
+// pages return. Records can be written as CSV or, with the "parquet" feature, Parquet.
+
+use crate::bit_rest_client::BitRestClient;
+use crate::error::{BitError, BitResult, Page, parse_page};
+use chrono::DateTime;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::io::{self, Write};
+use std::path::Path;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LedgerSource {
+    Spot,
+    Um,
+}
+
+impl LedgerSource {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LedgerSource::Spot => "spot",
+            LedgerSource::Um => "um",
+        }
+    }
+}
+
+/// One balance change. Empty strings mean the field does not apply (e.g. no trade id on a
+/// deposit).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LedgerRecord {
+    pub source: LedgerSource,
+    pub tx_id: String,
+    pub time: i64,
+    pub tx_type: String,
+    pub currency: String,
+    pub instrument_id: String,
+    /// Signed balance change.
+    pub amount: f64,
+    pub fee: f64,
+    /// Balance after the change.
+    pub balance: f64,
+    pub trade_id: String,
+    pub order_id: String,
+}
+
+// the spot and UM logs name the same things differently
+fn field<'a>(v: &'a Value, keys: &[&str]) -> &'a Value {
+    keys.iter()
+        .map(|k| &v[*k])
+        .find(|x| !x.is_null())
+        .unwrap_or(&Value::Null)
+}
+
+fn text(v: &Value, keys: &[&str]) -> String {
+    match field(v, keys) {
+        Value::String(s) => s.clone(),
+        Value::Null => String::new(),
+        x => x.to_string(),
+    }
+}
+
+fn number(v: &Value, keys: &[&str]) -> f64 {
+    match field(v, keys) {
+        Value::String(s) => s.parse().unwrap_or(0.0),
+        x => x.as_f64().unwrap_or(0.0),
+    }
+}
+
+/// Milliseconds, given as a number, a numeric string or an RFC 3339 string.
+fn timestamp(v: &Value, keys: &[&str]) -> Option<i64> {
+    match field(v, keys) {
+        Value::String(s) => s.trim().parse().ok().or_else(|| {
+            DateTime::parse_from_rfc3339(s.trim())
+                .ok()
+                .map(|t| t.timestamp_millis())
+        }),
+        x => x.as_i64(),
+    }
+}
+
+impl LedgerRecord {
+    /// Fails on an entry without a readable time, it could not be placed in the range.
+    pub fn from_value(source: LedgerSource, v: &Value) -> BitResult<LedgerRecord> {
+        let time = timestamp(v, &["tx_time", "created_at", "time"])
+            .ok_or_else(|| BitError::Decode(format!("ledger entry without a time: {}", v)))?;
+        Ok(LedgerRecord {
+            source,
+            tx_id: text(v, &["tx_id", "id"]),
+            time,
+            tx_type: text(v, &["type", "tx_type"]),
+            currency: text(v, &["currency", "ccy"]),
+            instrument_id: text(v, &["instrument_id", "pair"]),
+            amount: number(v, &["change", "cash_flow", "amount"]),
+            fee: number(v, &["fee_paid", "fee"]),
+            balance: number(v, &["cash_balance", "balance"]),
+            trade_id: text(v, &["trade_id"]),
+            order_id: text(v, &["order_id"]),
+        })
+    }
+
+    /// Identity used to drop duplicates. Entries without an id fall back to their content.
+    fn key(&self) -> String {
+        if !self.tx_id.is_empty() {
+            return self.tx_id.clone();
+        }
+        format!(
+            "{}|{}|{}|{}|{}|{}|{}|{}",
+            self.time,
+            self.tx_type,
+            self.currency,
+            self.instrument_id,
+            self.amount,
+            self.balance,
+            self.trade_id,
+            self.order_id
+        )
+    }
+}
+
+/// Which part of the log to export.
+#[derive(Debug, Clone)]
+pub struct LedgerQuery {
+    pub source: LedgerSource,
+    pub currency: Option<String>,
+    pub start_ms: i64,
+    pub end_ms: i64,
+    /// The range is requested in windows of this size, one day by default.
+    pub window_ms: i64,
+    pub page_size: u32,
+}
+
+impl LedgerQuery {
+    pub fn new(source: LedgerSource, start_ms: i64, end_ms: i64) -> Self {
+        Self {
+            source,
+            currency: None,
+            start_ms,
+            end_ms,
+            window_ms: 24 * 3600 * 1000,
+            page_size: 100,
+        }
+    }
+
+    pub fn with_currency(mut self, currency: &str) -> Self {
+        self.currency = Some(currency.to_string());
+        self
+    }
+}
+
+async fn fetch_page(
+    client: &BitRestClient,
+    query: &LedgerQuery,
+    start_ms: i64,
+    end_ms: i64,
+    offset: u32,
+) -> BitResult<Page<Value>> {
+    let mut params = serde_json::json!({
+        "start_time": start_ms,
+        "end_time": end_ms,
+        "offset": offset,
+        "limit": query.page_size,
+    });
+    if let Some(currency) = &query.currency {
+        params["currency"] = Value::String(currency.clone());
+    }
+    let rsp = match query.source {
+        LedgerSource::Spot => client.spot_get_class_txlogs(&mut params).await?,
+        LedgerSource::Um => client.get_um_txlogs(&mut params).await?,
+    };
+    parse_page(rsp, query.page_size)
+}
+
+/// Every record in `[start_ms, end_ms)`, deduplicated and sorted by time.
+pub async fn fetch_ledger(
+    client: &BitRestClient,
+    query: &LedgerQuery,
+) -> BitResult<Vec<LedgerRecord>> {
+    if query.window_ms <= 0 || query.page_size == 0 {
+        return Err(BitError::Invalid(
+            "window_ms and page_size must be positive".to_string(),
+        ));
+    }
+
+    let mut records = BTreeMap::new();
+    let mut start = query.start_ms;
+    while start < query.end_ms {
+        let end = (start + query.window_ms).min(query.end_ms);
+        let mut offset = 1;
+        loop {
+            let page = fetch_page(client, query, start, end, offset).await?;
+            let done = !page.has_more || page.items.is_empty();
+            for item in &page.items {
+                let record = LedgerRecord::from_value(query.source, item)?;
+                // windows share their boundary on some endpoints
+                if record.time < start || record.time >= end {
+                    continue;
+                }
+                records.entry(record.key()).or_insert(record);
+            }
+            if done {
+                break;
+            }
+            offset += 1;
+        }
+        start = end;
+    }
+
+    let mut records: Vec<LedgerRecord> = records.into_values().collect();
+    records.sort_by(|a, b| a.time.cmp(&b.time).then_with(|| a.tx_id.cmp(&b.tx_id)));
+    Ok(records)
+}
+
+/////////////////////////////////////////
+// output
+/////////////////////////////////////////
+const CSV_HEADER: &str =
+    "source,tx_id,time,tx_type,currency,instrument_id,amount,fee,balance,trade_id,order_id";
+
+fn csv_escape(s: &str) -> String {
+    if s.contains([',', '"', '\n']) {
+        format!("\"{}\"", s.replace('"', "\"\""))
+    } else {
+        s.to_string()
+    }
+}
+
+pub fn write_csv<W: Write>(mut out: W, records: &[LedgerRecord]) -> io::Result<()> {
+    writeln!(out, "{}", CSV_HEADER)?;
+    for r in records {
+        writeln!(
+            out,
+            "{},{},{},{},{},{},{},{},{},{},{}",
+            r.source.as_str(),
+            csv_escape(&r.tx_id),
+            r.time,
+            csv_escape(&r.tx_type),
+            csv_escape(&r.currency),
+            csv_escape(&r.instrument_id),
+            r.amount,
+            r.fee,
+            r.balance,
+            csv_escape(&r.trade_id),
+            csv_escape(&r.order_id),
+        )?;
+    }
+    out.flush()
+}
+
+#[cfg(feature = "parquet")]
+pub fn write_parquet(path: &Path, records: &[LedgerRecord]) -> io::Result<()> {
+    use arrow_array::{ArrayRef, Float64Array, Int64Array, RecordBatch, StringArray};
+    use arrow_schema::{DataType, Field, Schema};
+    use parquet::arrow::ArrowWriter;
+    use parquet::basic::Compression;
+    use parquet::file::properties::WriterProperties;
+    use std::sync::Arc;
+
+    let schema = Arc::new(Schema::new(vec![
+        Field::new("source", DataType::Utf8, false),
+        Field::new("tx_id", DataType::Utf8, false),
+        Field::new("time", DataType::Int64, false),
+        Field::new("tx_type", DataType::Utf8, false),
+        Field::new("currency", DataType::Utf8, false),
+        Field::new("instrument_id", DataType::Utf8, false),
+        Field::new("amount", DataType::Float64, false),
+        Field::new("fee", DataType::Float64, false),
+        Field::new("balance", DataType::Float64, false),
+        Field::new("trade_id", DataType::Utf8, false),
+        Field::new("order_id", DataType::Utf8, false),
+    ]));
+    let strings = |f: fn(&LedgerRecord) -> &str| -> ArrayRef {
+        Arc::new(StringArray::from_iter_values(records.iter().map(f)))
+    };
+    let floats = |f: fn(&LedgerRecord) -> f64| -> ArrayRef {
+        Arc::new(Float64Array::from_iter_values(records.iter().map(f)))
+    };
+    let columns: Vec<ArrayRef> = vec![
+        strings(|r| r.source.as_str()),
+        strings(|r| &r.tx_id),
+        Arc::new(Int64Array::from_iter_values(records.iter().map(|r| r.time))),
+        strings(|r| &r.tx_type),
+        strings(|r| &r.currency),
+        strings(|r| &r.instrument_id),
+        floats(|r| r.amount),
+        floats(|r| r.fee),
+        floats(|r| r.balance),
+        strings(|r| &r.trade_id),
+        strings(|r| &r.order_id),
+    ];
+    let batch = RecordBatch::try_new(schema.clone(), columns).map_err(io::Error::other)?;
+
+    let props = WriterProperties::builder()
+        .set_compression(Compression::SNAPPY)
+        .build();
+    let mut writer = ArrowWriter::try_new(std::fs::File::create(path)?, schema, Some(props))
+        .map_err(io::Error::other)?;
+    writer.write(&batch).map_err(io::Error::other)?;
+    writer.close().map_err(io::Error::other)?;
+    Ok(())
+}
+
+/// Write by file extension: `.csv`, or `.parquet` with the "parquet" feature.
+pub fn write_file(path: &Path, records: &[LedgerRecord]) -> BitResult<()> {
+    let io_err = |e: io::Error| BitError::Config(format!("{}: {}", path.display(), e));
+    match path.extension().and_then(|e| e.to_str()) {
+        Some("csv") => {
+            let file = std::fs::File::create(path).map_err(io_err)?;
+            write_csv(io::BufWriter::new(file), records).map_err(io_err)
+        }
+        #[cfg(feature = "parquet")]
+        Some("parquet") => write_parquet(path, records).map_err(io_err),
+        #[cfg(not(feature = "parquet"))]
+        Some("parquet") => Err(BitError::Config(
+            "parquet output requires the \"parquet\" cargo feature".to_string(),
+        )),
+        _ => Err(BitError::Config(format!(
+            "{}: expected a .csv or .parquet file",
+            path.display()
+        ))),
+    }
+}
@@ -1 +1,34 @@
-pub mod bit_rest_client;
\ No newline at end of file
+pub mod account_config;
+pub mod account_pool;
+pub mod algo;
+pub mod backtest;
+pub mod bit_rest_client;
+pub mod bit_ws_client;
+pub mod block_trade;
+pub mod conditional;
+pub mod credentials;
+pub mod error;
+pub mod label;
+pub mod latency;
+pub mod ledger;
+pub mod margin;
+pub mod matching;
+pub mod metrics;
+pub mod mmp;
+#[cfg(feature = "bench")]
+pub mod mock;
+pub mod models;
+pub mod options;
+pub mod order_book;
+pub mod paper;
+pub mod pnl;
+pub mod quote;
+pub mod rate_limit;
+pub mod record;
+pub mod recorder;
+#[cfg(feature = "sqlite")]
+pub mod store;
+pub mod trading;
+pub mod util;
+pub mod wallet;
+pub mod ws_frame;
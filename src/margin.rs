@@ -0,0 +1,304 @@
+// Pre-trade margin what-if for the linear book.
+//
+// `MarginSimulator` holds a snapshot of collateral, positions and open orders. `local` answers
+// "what if these orders filled" without any I/O, for the risk check in front of order entry.
+// `estimate` asks the exchange's margin endpoint for the hypothetical orders and falls back
+// to the local numbers when the call fails.
+
+use crate::bit_rest_client::BitRestClient;
+use crate::error::{BitError, BitResult, parse_data};
+use crate::models::{Market, Order, OrderRequest, Position, Side};
+use crate::trading::TradingApi;
+use crate::util::de_f64;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Parameters of the local approximation.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct MarginParams {
+    /// Initial margin is notional / leverage.
+    pub leverage: f64,
+    /// Maintenance margin is notional * rate.
+    pub maintenance_rate: f64,
+}
+
+impl Default for MarginParams {
+    fn default() -> Self {
+        Self {
+            leverage: 10.0,
+            maintenance_rate: 0.005,
+        }
+    }
+}
+
+/// Response of `linear_get_est_margins` for one order.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct EstMargins {
+    #[serde(default, alias = "im", deserialize_with = "de_f64")]
+    pub initial_margin: f64,
+    #[serde(default, alias = "mm", deserialize_with = "de_f64")]
+    pub maintenance_margin: f64,
+    #[serde(default, alias = "liquidation_price", deserialize_with = "de_f64")]
+    pub liq_price: f64,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct MarginSnapshot {
+    /// Collateral plus unrealized PnL at the marks.
+    pub equity: f64,
+    pub initial_margin: f64,
+    pub maintenance_margin: f64,
+    /// Gross position notional over equity.
+    pub leverage: f64,
+    /// Mark at which equity falls to maintenance margin, per instrument with a position,
+    /// other marks unchanged. Missing when the position cannot be liquidated by its own move.
+    pub liq_prices: BTreeMap<String, f64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MarginSource {
+    Exchange,
+    Local,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MarginImpact {
+    pub before: MarginSnapshot,
+    pub after: MarginSnapshot,
+    pub initial_margin_change: f64,
+    pub maintenance_margin_change: f64,
+    /// Where the margin change came from. Liquidation prices and leverage are always local.
+    pub source: MarginSource,
+}
+
+impl MarginImpact {
+    /// Whether the hypothetical orders fit in the available margin.
+    pub fn fits(&self) -> bool {
+        self.before.initial_margin + self.initial_margin_change <= self.after.equity
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct MarginSimulator {
+    params: MarginParams,
+    collateral: f64,
+    positions: BTreeMap<String, Position>,
+    open_orders: Vec<Order>,
+    marks: BTreeMap<String, f64>,
+}
+
+impl MarginSimulator {
+    /// Marks start at each position's `mark_price`, or its `avg_price` when that is missing.
+    pub fn new(
+        params: MarginParams,
+        collateral: f64,
+        positions: Vec<Position>,
+        open_orders: Vec<Order>,
+    ) -> Self {
+        let marks = positions
+            .iter()
+            .map(|p| {
+                let mark = if p.mark_price > 0.0 {
+                    p.mark_price
+                } else {
+                    p.avg_price
+                };
+                (p.instrument_id.clone(), mark)
+            })
+            .collect();
+        Self {
+            params,
+            collateral,
+            positions: positions
+                .into_iter()
+                .map(|p| (p.instrument_id.clone(), p))
+                .collect(),
+            open_orders,
+            marks,
+        }
+    }
+
+    /// Snapshot of the account: UM collateral, linear positions and open orders in `currency`.
+    pub async fn load(
+        client: &BitRestClient,
+        currency: &str,
+        params: MarginParams,
+    ) -> BitResult<Self> {
+        let account = client.um_account().await?;
+        let positions = client.positions(currency).await?;
+        let open_orders = client.open_orders(Market::Linear, currency, None).await?;
+        Ok(Self::new(
+            params,
+            account.total_collateral,
+            positions,
+            open_orders,
+        ))
+    }
+
+    pub fn set_mark(&mut self, instrument_id: &str, price: f64) {
+        self.marks.insert(instrument_id.to_string(), price);
+    }
+
+    fn mark_of(&self, instrument_id: &str, fallback: f64) -> f64 {
+        self.marks.get(instrument_id).copied().unwrap_or(fallback)
+    }
+
+    // The price an order is expected to fill at: its limit price, or the mark for a market
+    // order. Pricing a market order at zero would make any size look free.
+    fn fill_price(&self, req: &OrderRequest) -> BitResult<f64> {
+        req.price
+            .or_else(|| self.marks.get(&req.instrument_id).copied())
+            .ok_or_else(|| {
+                BitError::Invalid(format!(
+                    "{}: market order without a mark, call set_mark first",
+                    req.instrument_id
+                ))
+            })
+    }
+
+    /// Margin if `orders` filled now at their limit price (market orders at the mark).
+    /// Open orders keep resting and count on their worse side.
+    /// Fails with `BitError::Invalid` for a market order on an instrument without a mark.
+    pub fn local(&self, orders: &[OrderRequest]) -> BitResult<MarginImpact> {
+        let before = self.snapshot(&self.positions, self.collateral);
+
+        let mut positions = self.positions.clone();
+        let mut collateral = self.collateral;
+        for req in orders {
+            let price = self.fill_price(req)?;
+            let mark = self.mark_of(&req.instrument_id, price);
+            let qty = req.side.sign() * req.qty;
+            let pos = positions
+                .entry(req.instrument_id.clone())
+                .or_insert_with(|| Position {
+                    instrument_id: req.instrument_id.clone(),
+                    qty: 0.0,
+                    avg_price: mark,
+                    mark_price: mark,
+                    position_pnl: 0.0,
+                    liq_price: 0.0,
+                    leverage: 0.0,
+                });
+            // filling away from the mark moves equity by the difference right away, after
+            // that the fill is booked at the mark
+            collateral += qty * (mark - price);
+            if pos.qty * qty < 0.0 {
+                let closed = qty.abs().min(pos.qty.abs()) * pos.qty.signum();
+                collateral += closed * (mark - pos.avg_price);
+            }
+            let new_qty = pos.qty + qty;
+            if pos.qty * qty >= 0.0 && new_qty != 0.0 {
+                pos.avg_price = (pos.avg_price * pos.qty + mark * qty) / new_qty;
+            } else if pos.qty * new_qty < 0.0 {
+                pos.avg_price = mark;
+            }
+            pos.qty = new_qty;
+        }
+        let after = self.snapshot(&positions, collateral);
+
+        Ok(MarginImpact {
+            initial_margin_change: after.initial_margin - before.initial_margin,
+            maintenance_margin_change: after.maintenance_margin - before.maintenance_margin,
+            before,
+            after,
+            source: MarginSource::Local,
+        })
+    }
+
+    /// `local`, with the initial and maintenance margin change taken from the exchange's
+    /// estimate of each order. Any failed call falls back to the local numbers, errors are
+    /// those of `local`.
+    pub async fn estimate(
+        &self,
+        client: &BitRestClient,
+        orders: &[OrderRequest],
+    ) -> BitResult<MarginImpact> {
+        let mut impact = self.local(orders)?;
+        let mut initial = 0.0;
+        let mut maintenance = 0.0;
+        for req in orders {
+            let price = self.fill_price(req)?;
+            let mut params = serde_json::json!({
+                "instrument_id": req.instrument_id,
+                "side": req.side,
+                "price": price.to_string(),
+                "qty": req.qty.to_string(),
+            });
+            let est: BitResult<EstMargins> = match client.linear_get_est_margins(&mut params).await
+            {
+                Ok(rsp) => parse_data(rsp),
+                Err(e) => Err(e.into()),
+            };
+            let Ok(est) = est else {
+                return Ok(impact);
+            };
+            initial += est.initial_margin;
+            maintenance += est.maintenance_margin;
+        }
+        impact.initial_margin_change = initial;
+        impact.maintenance_margin_change = maintenance;
+        impact.source = MarginSource::Exchange;
+        Ok(impact)
+    }
+
+    fn snapshot(&self, positions: &BTreeMap<String, Position>, collateral: f64) -> MarginSnapshot {
+        let MarginParams {
+            leverage,
+            maintenance_rate,
+        } = self.params;
+
+        // worst-side exposure per instrument: the position plus all resting buys or all sells
+        let mut resting: BTreeMap<&str, (f64, f64)> = BTreeMap::new();
+        for order in &self.open_orders {
+            let entry = resting.entry(&order.instrument_id).or_default();
+            match order.side {
+                Side::Buy => entry.0 += order.remaining_qty(),
+                Side::Sell => entry.1 += order.remaining_qty(),
+            }
+        }
+
+        let mut snap = MarginSnapshot {
+            equity: collateral,
+            ..Default::default()
+        };
+        let mut gross = 0.0;
+        let mut instruments: Vec<&str> = positions.keys().map(String::as_str).collect();
+        instruments.extend(resting.keys().copied());
+        instruments.sort_unstable();
+        instruments.dedup();
+        for inst in instruments {
+            let pos = positions.get(inst);
+            let qty = pos.map_or(0.0, |p| p.qty);
+            let mark = self.mark_of(inst, pos.map_or(0.0, |p| p.avg_price));
+            let (buys, sells) = resting.get(inst).copied().unwrap_or_default();
+            let exposure = (qty + buys).abs().max((qty - sells).abs());
+
+            if let Some(p) = pos {
+                snap.equity += qty * (mark - p.avg_price);
+            }
+            snap.initial_margin += exposure * mark / leverage;
+            snap.maintenance_margin += qty.abs() * mark * maintenance_rate;
+            gross += qty.abs() * mark;
+        }
+        if snap.equity > 0.0 {
+            snap.leverage = gross / snap.equity;
+        }
+
+        // equity + q * (p - mark) = other maintenance + |q| * p * rate, solved for p
+        for (inst, pos) in positions {
+            let q = pos.qty;
+            if q == 0.0 {
+                continue;
+            }
+            let mark = self.mark_of(inst, pos.avg_price);
+            let other_mm = snap.maintenance_margin - q.abs() * mark * maintenance_rate;
+            let denom = q - q.abs() * maintenance_rate;
+            let liq = (other_mm - snap.equity + q * mark) / denom;
+            if liq.is_finite() && liq > 0.0 {
+                snap.liq_prices.insert(inst.clone(), liq);
+            }
+        }
+        snap
+    }
+}
@@ -0,0 +1,870 @@
+// A local matching engine used by the paper-trading and backtesting backends.
+//
+// Orders cross against the book built from public depth messages (taker fills walk the
+// levels), and resting orders fill as makers when public trades or book updates trade
+// through their price. Nothing here does I/O: callers feed market data in and get the
+// private events (orders, fills, positions) that a live account would have produced.
+
+use crate::bit_ws_client::WsEvent;
+use crate::error::{BitError, BitResult};
+use crate::models::{
+    AmendRequest, CancelRequest, Depth, Market, Order, OrderRequest, OrderType, Position,
+    PublicTrade, Side, Ticker, TimeInForce, Trade, UmAccount, base_currency, settle_currency,
+};
+use crate::order_book::OrderBook;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+
+const EPS: f64 = 1e-12;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct FeeSchedule {
+    pub maker_fee_rate: f64,
+    pub taker_fee_rate: f64,
+}
+
+impl Default for FeeSchedule {
+    fn default() -> Self {
+        Self {
+            maker_fee_rate: 0.0002,
+            taker_fee_rate: 0.0005,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct RestingOrder {
+    market: Market,
+    order: Order,
+    /// Displayed quantity ahead of us at our price when the order started resting.
+    queue_ahead: f64,
+    post_only: bool,
+}
+
+pub struct MatchingEngine {
+    fees: FeeSchedule,
+    leverage: f64,
+    queue_position: bool,
+    books: HashMap<String, OrderBook>,
+    marks: HashMap<String, f64>,
+    // ordered maps keep iteration, and so simulation results, deterministic
+    orders: BTreeMap<String, RestingOrder>,
+    trades: Vec<Trade>,
+    positions: BTreeMap<String, Position>,
+    balances: BTreeMap<String, f64>,
+    next_id: u64,
+    now_ms: i64,
+}
+
+impl MatchingEngine {
+    pub fn new(fees: FeeSchedule, balances: HashMap<String, f64>) -> Self {
+        Self {
+            fees,
+            leverage: 10.0,
+            queue_position: true,
+            books: HashMap::new(),
+            marks: HashMap::new(),
+            orders: BTreeMap::new(),
+            trades: Vec::new(),
+            positions: BTreeMap::new(),
+            balances: balances.into_iter().collect(),
+            next_id: 1,
+            now_ms: 0,
+        }
+    }
+
+    /// Leverage used to estimate initial margin in `um_account()`. Defaults to 10.
+    pub fn with_leverage(mut self, leverage: f64) -> Self {
+        self.leverage = leverage;
+        self
+    }
+
+    /// When enabled (the default) a resting order only fills after the quantity displayed
+    /// ahead of it at its price has traded. When disabled it fills on the first touch.
+    pub fn with_queue_position(mut self, enabled: bool) -> Self {
+        self.queue_position = enabled;
+        self
+    }
+
+    pub fn set_time(&mut self, now_ms: i64) {
+        self.now_ms = now_ms;
+    }
+
+    pub fn now_ms(&self) -> i64 {
+        self.now_ms
+    }
+
+    pub fn book(&self, instrument_id: &str) -> Option<&OrderBook> {
+        self.books.get(instrument_id)
+    }
+
+    pub fn balances(&self) -> &BTreeMap<String, f64> {
+        &self.balances
+    }
+
+    /////////////////////////////////////////
+    // market data
+    /////////////////////////////////////////
+
+    /// Feed a public market data event. Returns the private events it caused.
+    pub fn on_market_event(&mut self, event: &WsEvent) -> Vec<WsEvent> {
+        match event {
+            WsEvent::Depth(depth) => self.on_depth(depth),
+            WsEvent::Trades(trades) => self.on_trades(trades),
+            WsEvent::Ticker(ticker) => self.on_ticker(ticker),
+            WsEvent::OptionTicker(ticker) => self.on_ticker(&ticker.ticker),
+            _ => vec![],
+        }
+    }
+
+    fn on_depth(&mut self, depth: &Depth) -> Vec<WsEvent> {
+        let book = self
+            .books
+            .entry(depth.instrument_id.clone())
+            .or_insert_with(|| OrderBook::new(&depth.instrument_id));
+        book.apply(depth);
+        if !book.is_valid() {
+            // out of date until the next snapshot, nothing trades against it
+            return vec![];
+        }
+        if let Some(mid) = book.mid() {
+            self.marks.insert(depth.instrument_id.clone(), mid);
+        }
+
+        // resting orders the new book trades through fill at their own price, best price and
+        // then oldest first, sharing the crossed levels so no liquidity is filled twice
+        let mut events = Vec::new();
+        for side in [Side::Buy, Side::Sell] {
+            let mut crossed: Vec<(&str, f64, i64)> = self
+                .orders
+                .values()
+                .filter(|r| r.order.instrument_id == depth.instrument_id && r.order.side == side)
+                .map(|r| (r.order.order_id.as_str(), r.order.price, r.order.created_at))
+                .collect();
+            crossed.sort_by(|a, b| match side {
+                Side::Buy => b.1.total_cmp(&a.1).then(a.2.cmp(&b.2)),
+                Side::Sell => a.1.total_cmp(&b.1).then(a.2.cmp(&b.2)),
+            });
+            let Some(&(_, best, _)) = crossed.first() else {
+                continue;
+            };
+            let mut levels: Vec<(f64, f64)> = self.books[&depth.instrument_id]
+                .opposite_levels(side)
+                .take_while(|(p, _)| trades_through(side, *p, best))
+                .collect();
+            if levels.is_empty() {
+                continue;
+            }
+            let crossed: Vec<(String, f64)> = crossed
+                .into_iter()
+                .map(|(id, price, _)| (id.to_string(), price))
+                .collect();
+
+            for (order_id, price) in crossed {
+                let mut remaining = self.orders[&order_id].order.remaining_qty();
+                let mut fill_qty = 0.0;
+                for level in levels.iter_mut() {
+                    if remaining <= EPS || !trades_through(side, level.0, price) {
+                        break;
+                    }
+                    let take = level.1.min(remaining);
+                    level.1 -= take;
+                    remaining -= take;
+                    fill_qty += take;
+                }
+                self.fill(&order_id, price, fill_qty, false, &mut events);
+                if levels.iter().all(|(_, qty)| *qty <= EPS) {
+                    break;
+                }
+            }
+        }
+        events
+    }
+
+    fn on_trades(&mut self, trades: &[PublicTrade]) -> Vec<WsEvent> {
+        let mut events = Vec::new();
+        for trade in trades {
+            let mut available = trade.qty;
+            let mut candidates: Vec<String> = self
+                .orders
+                .values()
+                .filter(|r| r.order.instrument_id == trade.instrument_id)
+                .filter(|r| match r.order.side {
+                    Side::Buy => trade.price <= r.order.price,
+                    Side::Sell => trade.price >= r.order.price,
+                })
+                .map(|r| r.order.order_id.clone())
+                .collect();
+            // oldest first
+            candidates.sort_by_key(|id| self.orders[id].order.created_at);
+
+            for order_id in candidates {
+                if available <= EPS {
+                    break;
+                }
+                let resting = self.orders.get_mut(&order_id).unwrap();
+                let at_our_price = (trade.price - resting.order.price).abs() < EPS;
+                if self.queue_position && at_our_price && resting.queue_ahead > EPS {
+                    let used = resting.queue_ahead.min(available);
+                    resting.queue_ahead -= used;
+                    available -= used;
+                    if available <= EPS {
+                        break;
+                    }
+                }
+                let price = resting.order.price;
+                let fill_qty = available.min(resting.order.remaining_qty());
+                available -= fill_qty;
+                self.fill(&order_id, price, fill_qty, false, &mut events);
+            }
+        }
+        events
+    }
+
+    fn on_ticker(&mut self, ticker: &Ticker) -> Vec<WsEvent> {
+        let mark = if ticker.mark_price > 0.0 {
+            ticker.mark_price
+        } else {
+            ticker.last_price
+        };
+        if mark > 0.0 {
+            self.marks.insert(ticker.instrument_id.clone(), mark);
+            if let Some(pos) = self.positions.get_mut(&ticker.instrument_id) {
+                pos.mark_price = mark;
+                pos.position_pnl = (mark - pos.avg_price) * pos.qty;
+            }
+        }
+        vec![]
+    }
+
+    /////////////////////////////////////////
+    // order entry
+    /////////////////////////////////////////
+    pub fn new_order(
+        &mut self,
+        market: Market,
+        req: &OrderRequest,
+    ) -> BitResult<(Order, Vec<WsEvent>)> {
+        let mut qty = req.qty;
+        if req.reduce_only {
+            let pos_qty = self
+                .positions
+                .get(&req.instrument_id)
+                .map_or(0.0, |p| p.qty);
+            if pos_qty * req.side.sign() >= 0.0 {
+                return Err(reject("reduce only order would increase position"));
+            }
+            qty = qty.min(pos_qty.abs());
+        }
+        if qty <= EPS {
+            return Err(reject("invalid qty"));
+        }
+        let limit = match (req.order_type, req.price) {
+            (OrderType::Limit, Some(price)) => Some(price),
+            (OrderType::Limit, None) => return Err(reject("limit order without price")),
+            (OrderType::Market, _) => None,
+        };
+
+        let crossing = self.crossing_qty(&req.instrument_id, req.side, limit);
+        if req.post_only && crossing > EPS {
+            return Err(reject("post only order would take liquidity"));
+        }
+        if req.time_in_force == Some(TimeInForce::Fok) && crossing + EPS < qty {
+            return Err(reject("fill or kill order cannot be fully filled"));
+        }
+
+        let order_id = self.next_id.to_string();
+        self.next_id += 1;
+        let order = Order {
+            order_id: order_id.clone(),
+            instrument_id: req.instrument_id.clone(),
+            side: req.side,
+            price: limit.unwrap_or(0.0),
+            qty,
+            filled_qty: 0.0,
+            avg_price: 0.0,
+            status: "open".to_string(),
+            order_type: req.order_type,
+            label: req.label.clone().unwrap_or_default(),
+            created_at: self.now_ms,
+            updated_at: self.now_ms,
+        };
+        self.orders.insert(
+            order_id.clone(),
+            RestingOrder {
+                market,
+                order,
+                queue_ahead: 0.0,
+                post_only: req.post_only,
+            },
+        );
+
+        let mut events = Vec::new();
+        self.take_liquidity(&order_id, &mut events);
+
+        let immediate = req.order_type == OrderType::Market
+            || matches!(
+                req.time_in_force,
+                Some(TimeInForce::Ioc) | Some(TimeInForce::Fok)
+            );
+        let order = match self.orders.get_mut(&order_id) {
+            Some(resting) if immediate => {
+                resting.order.status = "cancelled".to_string();
+                let order = resting.order.clone();
+                self.orders.remove(&order_id);
+                events.push(WsEvent::Orders(vec![order.clone()]));
+                order
+            }
+            Some(resting) => {
+                resting.queue_ahead = self
+                    .books
+                    .get(&req.instrument_id)
+                    .and_then(|b| level_qty(b, resting.order.side, resting.order.price))
+                    .unwrap_or(0.0);
+                events.push(WsEvent::Orders(vec![resting.order.clone()]));
+                resting.order.clone()
+            }
+            None => self.last_state(&order_id, &events),
+        };
+        Ok((order, events))
+    }
+
+    pub fn amend_order(&mut self, req: &AmendRequest) -> BitResult<(Order, Vec<WsEvent>)> {
+        let order_id = self.resolve(req.order_id.as_deref(), req.label.as_deref())?;
+        let resting = &self.orders[&order_id];
+        if let Some(price) = req.price
+            && resting.post_only
+            && self.crossing_qty(
+                &resting.order.instrument_id,
+                resting.order.side,
+                Some(price),
+            ) > EPS
+        {
+            return Err(reject("post only order would take liquidity"));
+        }
+        let mut events = Vec::new();
+        {
+            let resting = self.orders.get_mut(&order_id).unwrap();
+            if let Some(qty) = req.qty {
+                if qty + EPS < resting.order.filled_qty {
+                    return Err(reject("qty below filled qty"));
+                }
+                resting.order.qty = qty;
+            }
+            // a price change loses queue priority
+            if let Some(price) = req.price
+                && (price - resting.order.price).abs() > EPS
+            {
+                resting.order.price = price;
+                resting.queue_ahead = self
+                    .books
+                    .get(&resting.order.instrument_id)
+                    .and_then(|b| level_qty(b, resting.order.side, price))
+                    .unwrap_or(0.0);
+            }
+            resting.order.updated_at = self.now_ms;
+        }
+        self.take_liquidity(&order_id, &mut events);
+        let order = match self.orders.get_mut(&order_id) {
+            Some(resting) if resting.order.remaining_qty() <= EPS => {
+                resting.order.status = "filled".to_string();
+                let order = resting.order.clone();
+                self.orders.remove(&order_id);
+                order
+            }
+            Some(resting) => resting.order.clone(),
+            None => self.last_state(&order_id, &events),
+        };
+        events.push(WsEvent::Orders(vec![order.clone()]));
+        Ok((order, events))
+    }
+
+    pub fn cancel(&mut self, market: Market, req: &CancelRequest) -> (i64, Vec<WsEvent>) {
+        let ids: Vec<String> = self
+            .orders
+            .values()
+            .filter(|r| r.market == market)
+            .filter(|r| settle_currency(&r.order.instrument_id) == req.currency)
+            .filter(|r| {
+                req.instrument_id
+                    .as_ref()
+                    .is_none_or(|i| *i == r.order.instrument_id)
+            })
+            .filter(|r| req.order_id.as_ref().is_none_or(|i| *i == r.order.order_id))
+            .filter(|r| req.label.as_ref().is_none_or(|l| *l == r.order.label))
+            .map(|r| r.order.order_id.clone())
+            .collect();
+
+        let mut cancelled = Vec::new();
+        for id in ids {
+            let mut resting = self.orders.remove(&id).unwrap();
+            resting.order.status = "cancelled".to_string();
+            resting.order.updated_at = self.now_ms;
+            cancelled.push(resting.order);
+        }
+        let count = cancelled.len() as i64;
+        let events = if cancelled.is_empty() {
+            vec![]
+        } else {
+            vec![WsEvent::Orders(cancelled)]
+        };
+        (count, events)
+    }
+
+    /////////////////////////////////////////
+    // queries
+    /////////////////////////////////////////
+    pub fn open_orders(
+        &self,
+        market: Market,
+        currency: &str,
+        instrument_id: Option<&str>,
+    ) -> Vec<Order> {
+        let mut orders: Vec<Order> = self
+            .orders
+            .values()
+            .filter(|r| r.market == market)
+            .filter(|r| {
+                market == Market::Spot || settle_currency(&r.order.instrument_id) == currency
+            })
+            .filter(|r| instrument_id.is_none_or(|i| i == r.order.instrument_id))
+            .map(|r| r.order.clone())
+            .collect();
+        orders.sort_by_key(|o| o.created_at);
+        orders
+    }
+
+    pub fn user_trades(&self, currency: &str, instrument_id: Option<&str>) -> Vec<Trade> {
+        self.trades
+            .iter()
+            .filter(|t| settle_currency(&t.instrument_id) == currency)
+            .filter(|t| instrument_id.is_none_or(|i| i == t.instrument_id))
+            .cloned()
+            .collect()
+    }
+
+    pub fn positions(&self, currency: &str) -> Vec<Position> {
+        self.positions
+            .values()
+            .filter(|p| settle_currency(&p.instrument_id) == currency && p.qty.abs() > EPS)
+            .cloned()
+            .collect()
+    }
+
+    pub fn um_account(&self) -> UmAccount {
+        let total_collateral: f64 = self
+            .balances
+            .iter()
+            .map(|(ccy, amount)| amount * self.usd_price(ccy))
+            .sum();
+        let mut total_position_pnl = 0.0;
+        let mut total_initial_margin = 0.0;
+        for pos in self.positions.values() {
+            let mark = self
+                .marks
+                .get(&pos.instrument_id)
+                .copied()
+                .unwrap_or(pos.avg_price);
+            total_position_pnl += (mark - pos.avg_price) * pos.qty;
+            total_initial_margin += pos.qty.abs() * mark / self.leverage;
+        }
+        let total_margin_balance = total_collateral + total_position_pnl;
+        UmAccount {
+            total_collateral,
+            total_margin_balance,
+            total_available: total_margin_balance - total_initial_margin,
+            total_initial_margin,
+            total_maintenance_margin: total_initial_margin / 2.0,
+            total_position_pnl,
+            details: vec![],
+        }
+    }
+
+    /////////////////////////////////////////
+    // internals
+    /////////////////////////////////////////
+
+    /// The book of `instrument_id`, unless it is invalid after a sequence gap.
+    fn live_book(&self, instrument_id: &str) -> Option<&OrderBook> {
+        self.books.get(instrument_id).filter(|b| b.is_valid())
+    }
+
+    /// Liquidity on the opposite side that a taker would hit within `limit`.
+    fn crossing_qty(&self, instrument_id: &str, side: Side, limit: Option<f64>) -> f64 {
+        let Some(book) = self.live_book(instrument_id) else {
+            return 0.0;
+        };
+        book.opposite_levels(side)
+            .take_while(|(price, _)| crosses(side, *price, limit))
+            .map(|(_, qty)| qty)
+            .sum()
+    }
+
+    fn take_liquidity(&mut self, order_id: &str, events: &mut Vec<WsEvent>) {
+        let (instrument_id, side, limit, order_type) = {
+            let o = &self.orders[order_id].order;
+            (o.instrument_id.clone(), o.side, o.price, o.order_type)
+        };
+        let limit = (order_type == OrderType::Limit).then_some(limit);
+        loop {
+            let remaining = match self.orders.get(order_id) {
+                Some(r) => r.order.remaining_qty(),
+                None => return,
+            };
+            if remaining <= EPS {
+                return;
+            }
+            let Some((price, qty)) = self
+                .live_book(&instrument_id)
+                .and_then(|b| b.opposite_levels(side).next())
+                .filter(|(price, _)| crosses(side, *price, limit))
+            else {
+                return;
+            };
+            let fill_qty = qty.min(remaining);
+            self.books
+                .get_mut(&instrument_id)
+                .unwrap()
+                .consume(side, price, fill_qty);
+            self.fill(order_id, price, fill_qty, true, events);
+        }
+    }
+
+    fn fill(
+        &mut self,
+        order_id: &str,
+        price: f64,
+        qty: f64,
+        is_taker: bool,
+        events: &mut Vec<WsEvent>,
+    ) {
+        if qty <= EPS {
+            return;
+        }
+        let Some(resting) = self.orders.get_mut(order_id) else {
+            return;
+        };
+        let order = &mut resting.order;
+        order.avg_price =
+            (order.avg_price * order.filled_qty + price * qty) / (order.filled_qty + qty);
+        order.filled_qty += qty;
+        order.updated_at = self.now_ms;
+        if order.remaining_qty() <= EPS {
+            order.status = "filled".to_string();
+        }
+        let order = order.clone();
+        let market = resting.market;
+        if order.status == "filled" {
+            self.orders.remove(order_id);
+        }
+
+        let fee_rate = if is_taker {
+            self.fees.taker_fee_rate
+        } else {
+            self.fees.maker_fee_rate
+        };
+        let settle = settle_currency(&order.instrument_id).to_string();
+        let fee = price * qty * fee_rate;
+        let trade = Trade {
+            trade_id: format!("{}", self.next_id),
+            order_id: order.order_id.clone(),
+            instrument_id: order.instrument_id.clone(),
+            side: order.side,
+            price,
+            qty,
+            fee,
+            fee_ccy: settle.clone(),
+            is_taker,
+            label: order.label.clone(),
+            created_at: self.now_ms,
+        };
+        self.next_id += 1;
+        *self.balances.entry(settle.clone()).or_default() -= fee;
+
+        match market {
+            Market::Spot => {
+                let base = base_currency(&order.instrument_id).to_string();
+                *self.balances.entry(base).or_default() += order.side.sign() * qty;
+                *self.balances.entry(settle).or_default() -= order.side.sign() * qty * price;
+            }
+            Market::Linear | Market::Option => {
+                let realized = self.update_position(&order.instrument_id, order.side, price, qty);
+                *self.balances.entry(settle).or_default() += realized;
+                let pos = self.positions[&order.instrument_id].clone();
+                events.push(WsEvent::Positions(vec![pos]));
+            }
+        }
+
+        self.trades.push(trade.clone());
+        events.push(WsEvent::UserTrades(vec![trade]));
+        events.push(WsEvent::Orders(vec![order]));
+    }
+
+    /// Returns realized PnL in settlement currency.
+    fn update_position(&mut self, instrument_id: &str, side: Side, price: f64, qty: f64) -> f64 {
+        let mark = self.marks.get(instrument_id).copied().unwrap_or(price);
+        let pos = self
+            .positions
+            .entry(instrument_id.to_string())
+            .or_insert_with(|| Position {
+                instrument_id: instrument_id.to_string(),
+                qty: 0.0,
+                avg_price: 0.0,
+                mark_price: mark,
+                position_pnl: 0.0,
+                liq_price: 0.0,
+                leverage: 0.0,
+            });
+        let signed = side.sign() * qty;
+        let mut realized = 0.0;
+        if pos.qty.abs() <= EPS || pos.qty.signum() == signed.signum() {
+            let new_qty = pos.qty + signed;
+            pos.avg_price = (pos.avg_price * pos.qty.abs() + price * qty) / new_qty.abs();
+            pos.qty = new_qty;
+        } else {
+            let closing = qty.min(pos.qty.abs());
+            realized = (price - pos.avg_price) * closing * pos.qty.signum();
+            pos.qty += signed;
+            if pos.qty.abs() <= EPS {
+                pos.qty = 0.0;
+                pos.avg_price = 0.0;
+            } else if pos.qty.signum() == signed.signum() {
+                // flipped through zero
+                pos.avg_price = price;
+            }
+        }
+        pos.leverage = self.leverage;
+        pos.mark_price = mark;
+        pos.position_pnl = (mark - pos.avg_price) * pos.qty;
+        realized
+    }
+
+    fn resolve(&self, order_id: Option<&str>, label: Option<&str>) -> BitResult<String> {
+        if let Some(order_id) = order_id {
+            if self.orders.contains_key(order_id) {
+                return Ok(order_id.to_string());
+            }
+        } else if let Some(label) = label
+            && let Some(r) = self.orders.values().find(|r| r.order.label == label)
+        {
+            return Ok(r.order.order_id.clone());
+        }
+        Err(reject("order not found or not open"))
+    }
+
+    /// Latest state of an order that has already left the book.
+    fn last_state(&self, order_id: &str, events: &[WsEvent]) -> Order {
+        events
+            .iter()
+            .rev()
+            .find_map(|e| match e {
+                WsEvent::Orders(orders) => orders.iter().find(|o| o.order_id == order_id).cloned(),
+                _ => None,
+            })
+            .expect("order event for a removed order")
+    }
+
+    fn usd_price(&self, ccy: &str) -> f64 {
+        match ccy {
+            "USD" | "USDT" | "USDC" => 1.0,
+            _ => self
+                .marks
+                .get(&format!("{}-USDT", ccy))
+                .copied()
+                .unwrap_or(0.0),
+        }
+    }
+}
+
+// Whether an opposite level at `level_price` is strictly better than a resting `price`, so the
+// book trades through the resting order.
+fn trades_through(side: Side, level_price: f64, price: f64) -> bool {
+    match side {
+        Side::Buy => level_price < price,
+        Side::Sell => level_price > price,
+    }
+}
+
+fn crosses(side: Side, level_price: f64, limit: Option<f64>) -> bool {
+    match (side, limit) {
+        (_, None) => true,
+        (Side::Buy, Some(limit)) => level_price <= limit + EPS,
+        (Side::Sell, Some(limit)) => level_price + EPS >= limit,
+    }
+}
+
+fn level_qty(book: &OrderBook, side: Side, price: f64) -> Option<f64> {
+    let mut levels: Box<dyn Iterator<Item = (f64, f64)>> = match side {
+        Side::Buy => Box::new(book.bids()),
+        Side::Sell => Box::new(book.asks()),
+    };
+    levels
+        .find(|(p, _)| (p - price).abs() < EPS)
+        .map(|(_, q)| q)
+}
+
+fn reject(message: &str) -> BitError {
+    BitError::Api {
+        code: -1,
+        message: message.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const INST: &str = "BTC-USDT-PERPETUAL";
+
+    fn engine() -> MatchingEngine {
+        let mut engine = MatchingEngine::new(
+            FeeSchedule::default(),
+            HashMap::from([("USDT".to_string(), 1e6)]),
+        );
+        engine.on_market_event(&snapshot(1, &[(99.0, 10.0)], &[(101.0, 10.0)]));
+        engine
+    }
+
+    fn snapshot(sequence: i64, bids: &[(f64, f64)], asks: &[(f64, f64)]) -> WsEvent {
+        WsEvent::Depth(Depth {
+            instrument_id: INST.to_string(),
+            kind: "snapshot".to_string(),
+            sequence,
+            bids: bids.to_vec(),
+            asks: asks.to_vec(),
+            ..Default::default()
+        })
+    }
+
+    fn rest(engine: &mut MatchingEngine, side: Side, qty: f64, price: f64) -> String {
+        let req = OrderRequest::limit(INST, side, qty, price);
+        let (order, _) = engine.new_order(Market::Linear, &req).unwrap();
+        assert_eq!(order.status, "open");
+        engine.set_time(engine.now_ms() + 1);
+        order.order_id
+    }
+
+    fn filled(engine: &MatchingEngine) -> f64 {
+        engine.user_trades("USDT", None).iter().map(|t| t.qty).sum()
+    }
+
+    #[test]
+    fn crossed_level_is_shared_by_resting_orders() {
+        let mut engine = engine();
+        let first = rest(&mut engine, Side::Buy, 5.0, 100.0);
+        rest(&mut engine, Side::Buy, 5.0, 100.0);
+        rest(&mut engine, Side::Buy, 5.0, 100.0);
+
+        engine.on_market_event(&snapshot(2, &[(98.0, 10.0)], &[(99.5, 5.0)]));
+        let trades = engine.user_trades("USDT", None);
+        assert_eq!(trades.len(), 1);
+        assert_eq!(trades[0].order_id, first);
+        assert_eq!(trades[0].qty, 5.0);
+        assert!(!trades[0].is_taker);
+    }
+
+    #[test]
+    fn crossed_book_fills_through_deeper_levels() {
+        let mut engine = engine();
+        rest(&mut engine, Side::Buy, 10.0, 100.0);
+
+        engine.on_market_event(&snapshot(
+            2,
+            &[(97.0, 10.0)],
+            &[(98.0, 4.0), (99.0, 3.0), (100.0, 10.0)],
+        ));
+        assert_eq!(filled(&engine), 7.0);
+        let trades = engine.user_trades("USDT", None);
+        assert!(trades.iter().all(|t| t.price == 100.0));
+    }
+
+    #[test]
+    fn better_priced_resting_order_fills_first() {
+        let mut engine = engine();
+        let older = rest(&mut engine, Side::Sell, 5.0, 102.0);
+        let better = rest(&mut engine, Side::Sell, 5.0, 101.5);
+
+        engine.on_market_event(&snapshot(2, &[(103.0, 5.0)], &[(104.0, 5.0)]));
+        let trades = engine.user_trades("USDT", None);
+        assert_eq!(trades.len(), 1);
+        assert_eq!(trades[0].order_id, better);
+        let open = engine.open_orders(Market::Linear, "USDT", None);
+        assert_eq!(open.len(), 1);
+        assert_eq!(open[0].order_id, older);
+    }
+
+    #[test]
+    fn taker_walks_levels_up_to_its_limit() {
+        let mut engine = engine();
+        engine.on_market_event(&snapshot(
+            2,
+            &[(99.0, 10.0)],
+            &[(101.0, 2.0), (102.0, 3.0), (103.0, 10.0)],
+        ));
+        let req = OrderRequest::limit(INST, Side::Buy, 10.0, 102.0);
+        let (order, _) = engine.new_order(Market::Linear, &req).unwrap();
+        assert_eq!(order.filled_qty, 5.0);
+        assert_eq!(order.avg_price, (2.0 * 101.0 + 3.0 * 102.0) / 5.0);
+        assert_eq!(engine.book(INST).unwrap().best_ask(), Some((103.0, 10.0)));
+    }
+
+    #[test]
+    fn post_only_is_rejected_through_the_touch() {
+        let mut engine = engine();
+        let req = OrderRequest::limit(INST, Side::Buy, 1.0, 101.0).post_only();
+        assert!(engine.new_order(Market::Linear, &req).is_err());
+
+        let order_id = rest(&mut engine, Side::Buy, 1.0, 100.0);
+        let mut req = OrderRequest::limit(INST, Side::Buy, 1.0, 100.0).post_only();
+        req.qty = 2.0;
+        let (post_only, _) = engine.new_order(Market::Linear, &req).unwrap();
+        let amend = |price| AmendRequest {
+            instrument_id: INST.to_string(),
+            order_id: Some(post_only.order_id.clone()),
+            price: Some(price),
+            ..Default::default()
+        };
+        assert!(engine.amend_order(&amend(101.0)).is_err());
+        assert_eq!(engine.amend_order(&amend(100.5)).unwrap().0.price, 100.5);
+        assert_eq!(filled(&engine), 0.0);
+
+        // orders without post only may still cross on amend
+        let req = AmendRequest {
+            instrument_id: INST.to_string(),
+            order_id: Some(order_id),
+            price: Some(101.0),
+            ..Default::default()
+        };
+        assert_eq!(engine.amend_order(&req).unwrap().0.filled_qty, 1.0);
+    }
+
+    #[test]
+    fn book_after_a_gap_is_not_traded_until_the_next_snapshot() {
+        let mut engine = engine();
+        rest(&mut engine, Side::Buy, 1.0, 100.0);
+        let update = |sequence, prev_sequence, asks: &[(f64, f64)]| {
+            WsEvent::Depth(Depth {
+                instrument_id: INST.to_string(),
+                kind: "update".to_string(),
+                sequence,
+                prev_sequence,
+                asks: asks.to_vec(),
+                ..Default::default()
+            })
+        };
+
+        engine.on_market_event(&update(3, 2, &[(99.0, 5.0)]));
+        assert!(!engine.book(INST).unwrap().is_valid());
+        engine.on_market_event(&update(4, 3, &[(99.5, 5.0)]));
+        assert_eq!(filled(&engine), 0.0);
+        let req = OrderRequest::market(INST, Side::Buy, 1.0);
+        assert_eq!(
+            engine.new_order(Market::Linear, &req).unwrap().0.filled_qty,
+            0.0
+        );
+
+        engine.on_market_event(&snapshot(5, &[(98.0, 10.0)], &[(99.5, 5.0)]));
+        assert!(engine.book(INST).unwrap().is_valid());
+        assert_eq!(filled(&engine), 1.0);
+    }
+}
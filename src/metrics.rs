@@ -0,0 +1,276 @@
+// Prometheus metrics for REST and WebSocket activity, with the "metrics" feature.
+//
+// Everything is registered in one `prometheus::Registry`, returned by `registry()` and served
+// as text on `/metrics` by `serve`. Without the feature the recording functions are no-ops, so
+// the instrumented code needs no cfg of its own.
+//
+// Metrics:
+// - `bitsdk_rest_requests_total{endpoint, method}`
+// - `bitsdk_rest_request_duration_seconds{endpoint}`, histogram
+// - `bitsdk_rest_errors_total{endpoint, code}`, the exchange error code, "http" when no
+//   JSON response came back, or "decode" when a successful response did not decode
+// - `bitsdk_rate_limit_wait_seconds`, histogram of the time spent waiting for a token
+// - `bitsdk_ws_reconnects_total{url}`
+// - `bitsdk_ws_messages_total{channel}`, take `rate()` for the message rate
+// - `bitsdk_order_book_resyncs_total{instrument_id}`, books invalidated by a depth update that
+//   did not follow on the book's sequence, each waits for a new snapshot
+// - `bitsdk_ws_clock_skew_ms`, local receive time minus the exchange timestamp of the last
+//   message, so one-way latency plus clock offset
+
+use serde_json::Value;
+use std::time::Duration;
+
+#[cfg(feature = "metrics")]
+mod imp {
+    use crate::error::{BitError, BitResult};
+    use prometheus::{
+        Encoder, Histogram, HistogramOpts, HistogramVec, IntCounterVec, IntGauge, Opts, Registry,
+        TextEncoder,
+    };
+    use std::sync::OnceLock;
+    use std::time::Duration;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    pub(super) struct Metrics {
+        registry: Registry,
+        pub(super) rest_requests: IntCounterVec,
+        pub(super) rest_duration: HistogramVec,
+        pub(super) rest_errors: IntCounterVec,
+        pub(super) rate_limit_wait: Histogram,
+        pub(super) ws_reconnects: IntCounterVec,
+        pub(super) ws_messages: IntCounterVec,
+        pub(super) book_resyncs: IntCounterVec,
+        pub(super) clock_skew: IntGauge,
+    }
+
+    // registration only fails on duplicate names, which would be a bug here
+    fn register<T: prometheus::core::Collector + Clone + 'static>(
+        registry: &Registry,
+        metric: T,
+    ) -> T {
+        registry.register(Box::new(metric.clone())).unwrap();
+        metric
+    }
+
+    fn counter(registry: &Registry, name: &str, help: &str, labels: &[&str]) -> IntCounterVec {
+        register(
+            registry,
+            IntCounterVec::new(Opts::new(name, help), labels).unwrap(),
+        )
+    }
+
+    fn histogram(
+        registry: &Registry,
+        name: &str,
+        help: &str,
+        labels: &[&str],
+        buckets: Vec<f64>,
+    ) -> HistogramVec {
+        let opts = HistogramOpts::new(name, help).buckets(buckets);
+        register(registry, HistogramVec::new(opts, labels).unwrap())
+    }
+
+    pub(super) fn metrics() -> &'static Metrics {
+        static METRICS: OnceLock<Metrics> = OnceLock::new();
+        METRICS.get_or_init(|| {
+            let r = Registry::new();
+            let latency = vec![
+                0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0,
+            ];
+            Metrics {
+                rest_requests: counter(
+                    &r,
+                    "bitsdk_rest_requests_total",
+                    "REST requests sent",
+                    &["endpoint", "method"],
+                ),
+                rest_duration: histogram(
+                    &r,
+                    "bitsdk_rest_request_duration_seconds",
+                    "REST request latency, from sending to the response body",
+                    &["endpoint"],
+                    latency.clone(),
+                ),
+                rest_errors: counter(
+                    &r,
+                    "bitsdk_rest_errors_total",
+                    "REST requests that failed, by exchange error code",
+                    &["endpoint", "code"],
+                ),
+                rate_limit_wait: register(
+                    &r,
+                    Histogram::with_opts(
+                        HistogramOpts::new(
+                            "bitsdk_rate_limit_wait_seconds",
+                            "Time spent waiting for a rate limiter token",
+                        )
+                        .buckets(latency),
+                    )
+                    .unwrap(),
+                ),
+                ws_reconnects: counter(
+                    &r,
+                    "bitsdk_ws_reconnects_total",
+                    "WebSocket reconnect attempts",
+                    &["url"],
+                ),
+                ws_messages: counter(
+                    &r,
+                    "bitsdk_ws_messages_total",
+                    "WebSocket messages received",
+                    &["channel"],
+                ),
+                book_resyncs: counter(
+                    &r,
+                    "bitsdk_order_book_resyncs_total",
+                    "Order books invalidated by a depth sequence gap",
+                    &["instrument_id"],
+                ),
+                clock_skew: register(
+                    &r,
+                    IntGauge::new(
+                        "bitsdk_ws_clock_skew_ms",
+                        "Local receive time minus exchange timestamp of the last message",
+                    )
+                    .unwrap(),
+                ),
+                registry: r,
+            }
+        })
+    }
+
+    pub fn registry() -> &'static Registry {
+        &metrics().registry
+    }
+
+    /// All metrics in the Prometheus text format.
+    pub fn render() -> String {
+        let mut buf = vec![];
+        // encoding into a Vec cannot fail
+        let _ = TextEncoder::new().encode(&registry().gather(), &mut buf);
+        String::from_utf8(buf).unwrap_or_default()
+    }
+
+    /// Serve `render()` on `GET /metrics` at `addr`, e.g. "0.0.0.0:9100", until the task is
+    /// dropped. Other paths get a 404.
+    pub async fn serve(addr: &str) -> BitResult<()> {
+        let listener = TcpListener::bind(addr)
+            .await
+            .map_err(|e| BitError::Config(format!("{}: {}", addr, e)))?;
+        loop {
+            let Ok((mut stream, _)) = listener.accept().await else {
+                continue;
+            };
+            tokio::spawn(async move {
+                let mut buf = [0u8; 1024];
+                let n = match tokio::time::timeout(Duration::from_secs(5), stream.read(&mut buf))
+                    .await
+                {
+                    Ok(Ok(n)) => n,
+                    _ => return,
+                };
+                let request = String::from_utf8_lossy(&buf[..n]);
+                let path = request.split_whitespace().nth(1).unwrap_or("");
+                let (status, body) = if path == "/metrics" {
+                    ("200 OK", render())
+                } else {
+                    ("404 Not Found", String::new())
+                };
+                let head = format!(
+                    "HTTP/1.1 {}\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                    status,
+                    body.len()
+                );
+                let _ = stream.write_all(head.as_bytes()).await;
+                let _ = stream.write_all(body.as_bytes()).await;
+            });
+        }
+    }
+}
+
+#[cfg(feature = "metrics")]
+pub use imp::{registry, render, serve};
+
+/// How a REST request ended, the `code` label of its error.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum RestOutcome {
+    /// The `code` of the JSON response, 0 on success.
+    Code(i64),
+    /// No JSON came back: a body that is not JSON is an error page from something in front
+    /// of the API.
+    Http,
+    /// The response reported success but its `data` did not decode.
+    Decode,
+}
+
+/// The outcome of a raw REST response, code 0 when it has none.
+pub(crate) fn response_code(rsp: &Result<Value, reqwest::Error>) -> RestOutcome {
+    match rsp {
+        Ok(Value::Object(map)) => {
+            RestOutcome::Code(map.get("code").and_then(Value::as_i64).unwrap_or(0))
+        }
+        Ok(_) | Err(_) => RestOutcome::Http,
+    }
+}
+
+pub(crate) fn rest_request(endpoint: &str, method: &str, elapsed: Duration, outcome: RestOutcome) {
+    #[cfg(feature = "metrics")]
+    {
+        let m = imp::metrics();
+        m.rest_requests.with_label_values(&[endpoint, method]).inc();
+        m.rest_duration
+            .with_label_values(&[endpoint])
+            .observe(elapsed.as_secs_f64());
+        let code = match outcome {
+            RestOutcome::Code(0) => None,
+            RestOutcome::Code(code) => Some(code.to_string()),
+            RestOutcome::Http => Some("http".to_string()),
+            RestOutcome::Decode => Some("decode".to_string()),
+        };
+        if let Some(code) = code {
+            m.rest_errors.with_label_values(&[endpoint, &code]).inc();
+        }
+    }
+    #[cfg(not(feature = "metrics"))]
+    let _ = (endpoint, method, elapsed, outcome);
+}
+
+pub(crate) fn rate_limit_wait(waited: Duration) {
+    #[cfg(feature = "metrics")]
+    imp::metrics().rate_limit_wait.observe(waited.as_secs_f64());
+    #[cfg(not(feature = "metrics"))]
+    let _ = waited;
+}
+
+pub(crate) fn ws_reconnect(url: &str) {
+    #[cfg(feature = "metrics")]
+    imp::metrics().ws_reconnects.with_label_values(&[url]).inc();
+    #[cfg(not(feature = "metrics"))]
+    let _ = url;
+}
+
+pub(crate) fn ws_message(channel: Option<&str>, timestamp: Option<i64>, recv_ms: i64) {
+    #[cfg(feature = "metrics")]
+    {
+        let m = imp::metrics();
+        if let Some(channel) = channel {
+            m.ws_messages.with_label_values(&[channel]).inc();
+        }
+        if let Some(ts) = timestamp {
+            m.clock_skew.set(recv_ms - ts);
+        }
+    }
+    #[cfg(not(feature = "metrics"))]
+    let _ = (channel, timestamp, recv_ms);
+}
+
+pub(crate) fn order_book_resync(instrument_id: &str) {
+    #[cfg(feature = "metrics")]
+    imp::metrics()
+        .book_resyncs
+        .with_label_values(&[instrument_id])
+        .inc();
+    #[cfg(not(feature = "metrics"))]
+    let _ = instrument_id;
+}
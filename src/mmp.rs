@@ -0,0 +1,396 @@
+// Market-maker protection: typed models on top of the mmp_state / update_mmp_config / reset_mmp
+// endpoints, and a supervisor that watches for freezes and resets them according to a policy.
+
+use crate::bit_rest_client::BitRestClient;
+use crate::error::{BitError, BitResult, parse_data};
+use crate::models::{Market, base_currency};
+use crate::util::{de_f64, now_ms};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{HashMap, VecDeque};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::broadcast;
+
+/// Error code bit.com returns from mmp_state when the pair has no MMP config yet.
+pub const MMP_NOT_CONFIGURED: i64 = 18_300_004;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MmpConfig {
+    #[serde(alias = "currency")]
+    pub pair: String,
+    pub window_ms: i64,
+    pub frozen_ms: i64,
+    #[serde(deserialize_with = "de_f64")]
+    pub qty_limit: f64,
+    #[serde(deserialize_with = "de_f64")]
+    pub delta_limit: f64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MmpState {
+    pub mmp_frozen: bool,
+    pub mmp_frozen_until_ms: i64,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct MmpStatus {
+    pub mmp_config: MmpConfig,
+    pub mmp_state: MmpState,
+}
+
+/// MMP configs declared in a TOML file:
+///
+/// ```toml
+/// [[spot]]
+/// pair = "BTC-USDT"
+/// window_ms = 5000
+/// frozen_ms = 1000
+/// qty_limit = 10.0
+/// delta_limit = 10.0
+/// ```
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct MmpConfigFile {
+    #[serde(default)]
+    pub spot: Vec<MmpConfig>,
+    #[serde(default)]
+    pub linear: Vec<MmpConfig>,
+    /// Option MMP is per currency, put the currency ("BTC") in `pair`.
+    #[serde(default)]
+    pub option: Vec<MmpConfig>,
+}
+
+impl MmpConfigFile {
+    pub fn load(path: impl AsRef<Path>) -> BitResult<Self> {
+        let text = std::fs::read_to_string(path.as_ref())
+            .map_err(|e| BitError::Config(format!("{}: {}", path.as_ref().display(), e)))?;
+        toml::from_str(&text).map_err(|e| BitError::Config(e.to_string()))
+    }
+
+    pub fn configs(&self, market: Market) -> &[MmpConfig] {
+        match market {
+            Market::Spot => &self.spot,
+            Market::Linear => &self.linear,
+            Market::Option => &self.option,
+        }
+    }
+}
+
+/// A config that differs from what is live on the exchange.
+#[derive(Debug, Clone)]
+pub struct MmpConfigChange {
+    pub current: Option<MmpConfig>,
+    pub desired: MmpConfig,
+}
+
+/// The MMP key an instrument falls under: "BTC-USDT" for "BTC-USDT" and
+/// "BTC-USDT-PERPETUAL", the currency "BTC" for BTC options.
+pub fn mmp_pair(market: Market, instrument_id: &str) -> String {
+    match market {
+        Market::Spot | Market::Linear => instrument_id
+            .splitn(3, '-')
+            .take(2)
+            .collect::<Vec<_>>()
+            .join("-"),
+        Market::Option => base_currency(instrument_id).to_string(),
+    }
+}
+
+/////////////////////////////////////////
+// typed endpoints
+/////////////////////////////////////////
+// spot and linear MMP is keyed by pair, option MMP by currency
+fn mmp_params(market: Market, pair: &str) -> Value {
+    match market {
+        Market::Spot | Market::Linear => serde_json::json!({ "pair": pair }),
+        Market::Option => serde_json::json!({ "currency": pair }),
+    }
+}
+
+pub async fn get_mmp_status(
+    client: &BitRestClient,
+    market: Market,
+    pair: &str,
+) -> BitResult<MmpStatus> {
+    let mut params = mmp_params(market, pair);
+    let rsp = match market {
+        Market::Spot => client.spot_get_mmp_state(&mut params).await?,
+        Market::Linear => client.linear_get_mmp_state(&mut params).await?,
+        Market::Option => client.option_get_mmp_state(&mut params).await?,
+    };
+    parse_data(rsp)
+}
+
+pub async fn update_mmp_config(
+    client: &BitRestClient,
+    market: Market,
+    config: &MmpConfig,
+) -> BitResult<()> {
+    let mut params = mmp_params(market, &config.pair);
+    params["window_ms"] = Value::from(config.window_ms);
+    params["frozen_ms"] = Value::from(config.frozen_ms);
+    params["qty_limit"] = Value::String(config.qty_limit.to_string());
+    params["delta_limit"] = Value::String(config.delta_limit.to_string());
+    let rsp = match market {
+        Market::Spot => client.spot_update_mmp_config(&mut params).await?,
+        Market::Linear => client.linear_update_mmp_config(&mut params).await?,
+        Market::Option => client.option_update_mmp_config(&mut params).await?,
+    };
+    parse_data::<Value>(rsp).map(|_| ())
+}
+
+pub async fn reset_mmp(client: &BitRestClient, market: Market, pair: &str) -> BitResult<()> {
+    let mut params = mmp_params(market, pair);
+    let rsp = match market {
+        Market::Spot => client.spot_reset_mmp(&mut params).await?,
+        Market::Linear => client.linear_reset_mmp(&mut params).await?,
+        Market::Option => client.option_reset_mmp(&mut params).await?,
+    };
+    parse_data::<Value>(rsp).map(|_| ())
+}
+
+/// Compare declared configs with the live ones and return those that need an update.
+pub async fn plan_mmp_configs(
+    client: &BitRestClient,
+    market: Market,
+    desired: &[MmpConfig],
+) -> BitResult<Vec<MmpConfigChange>> {
+    let mut changes = Vec::new();
+    for config in desired {
+        let current = match get_mmp_status(client, market, &config.pair).await {
+            Ok(status) => Some(status.mmp_config),
+            Err(BitError::Api {
+                code: MMP_NOT_CONFIGURED,
+                ..
+            }) => None,
+            Err(e) => return Err(e),
+        };
+        if current.as_ref() != Some(config) {
+            changes.push(MmpConfigChange {
+                current,
+                desired: config.clone(),
+            });
+        }
+    }
+    Ok(changes)
+}
+
+pub async fn apply_mmp_configs(
+    client: &BitRestClient,
+    market: Market,
+    changes: &[MmpConfigChange],
+) -> BitResult<()> {
+    for change in changes {
+        update_mmp_config(client, market, &change.desired).await?;
+    }
+    Ok(())
+}
+
+/////////////////////////////////////////
+// supervisor
+/////////////////////////////////////////
+
+/// What the supervisor does once a pair is frozen.
+#[derive(Debug, Clone)]
+pub enum ResetPolicy {
+    /// Never reset automatically, strategies or operators call `MmpSupervisor::reset`.
+    ManualOnly,
+    /// Reset after `cool_down`, at most `max_resets_per_hour` times in any rolling hour.
+    CoolDown {
+        cool_down: Duration,
+        max_resets_per_hour: usize,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum MmpEvent {
+    Frozen { pair: String, frozen_until_ms: i64 },
+    Unfrozen { pair: String },
+    Reset { pair: String },
+    ResetSuppressed { pair: String, reason: String },
+    Error { pair: String, message: String },
+}
+
+#[derive(Default)]
+struct PairState {
+    frozen: bool,
+    frozen_since_ms: i64,
+    resets: VecDeque<i64>,
+    suppressed: bool,
+}
+
+pub struct MmpSupervisor {
+    client: Arc<BitRestClient>,
+    market: Market,
+    pairs: Vec<String>,
+    policy: ResetPolicy,
+    states: Mutex<HashMap<String, PairState>>,
+    sender: broadcast::Sender<MmpEvent>,
+}
+
+impl MmpSupervisor {
+    pub fn new(
+        client: Arc<BitRestClient>,
+        market: Market,
+        pairs: Vec<String>,
+        policy: ResetPolicy,
+    ) -> Self {
+        let (sender, _) = broadcast::channel(256);
+        Self {
+            client,
+            market,
+            pairs,
+            policy,
+            states: Mutex::new(HashMap::new()),
+            sender,
+        }
+    }
+
+    /// Strategies subscribe here to learn when quoting must stop.
+    pub fn subscribe(&self) -> broadcast::Receiver<MmpEvent> {
+        self.sender.subscribe()
+    }
+
+    pub fn is_frozen(&self, pair: &str) -> bool {
+        self.states
+            .lock()
+            .unwrap()
+            .get(pair)
+            .is_some_and(|s| s.frozen)
+    }
+
+    pub fn any_frozen(&self) -> bool {
+        self.states.lock().unwrap().values().any(|s| s.frozen)
+    }
+
+    /// Poll every pair once and apply the reset policy.
+    pub async fn poll_once(&self) {
+        for pair in &self.pairs {
+            match get_mmp_status(&self.client, self.market, pair).await {
+                Ok(status) => {
+                    self.on_state(
+                        pair,
+                        status.mmp_state.mmp_frozen,
+                        status.mmp_state.mmp_frozen_until_ms,
+                    );
+                }
+                Err(e) => self.emit(MmpEvent::Error {
+                    pair: pair.clone(),
+                    message: e.to_string(),
+                }),
+            }
+        }
+        self.apply_policy().await;
+    }
+
+    /// Poll forever at `interval`.
+    pub async fn run_polling(&self, interval: Duration) {
+        let mut ticker = tokio::time::interval(interval);
+        loop {
+            ticker.tick().await;
+            self.poll_once().await;
+        }
+    }
+
+    /// Feed a raw private WebSocket message. Messages from other channels are ignored,
+    /// so the whole private stream can be passed through.
+    pub async fn on_ws_message(&self, msg: &Value) {
+        if msg["channel"].as_str() != Some("mmp_frozen") {
+            return;
+        }
+        let data = &msg["data"];
+        let Some(pair) = data["pair"].as_str() else {
+            return;
+        };
+        let frozen_until_ms = data["frozen_until_ms"].as_i64().unwrap_or(-1);
+        self.on_state(pair, frozen_until_ms != -1, frozen_until_ms);
+        self.apply_policy().await;
+    }
+
+    /// Reset a pair regardless of policy. Still counted against the hourly budget.
+    pub async fn reset(&self, pair: &str) -> BitResult<()> {
+        reset_mmp(&self.client, self.market, pair).await?;
+        {
+            let mut states = self.states.lock().unwrap();
+            let state = states.entry(pair.to_string()).or_default();
+            state.frozen = false;
+            state.resets.push_back(now_ms());
+        }
+        self.emit(MmpEvent::Reset {
+            pair: pair.to_string(),
+        });
+        Ok(())
+    }
+
+    fn on_state(&self, pair: &str, frozen: bool, frozen_until_ms: i64) {
+        let event = {
+            let mut states = self.states.lock().unwrap();
+            let state = states.entry(pair.to_string()).or_default();
+            if frozen == state.frozen {
+                return;
+            }
+            state.frozen = frozen;
+            state.suppressed = false;
+            if frozen {
+                state.frozen_since_ms = now_ms();
+                MmpEvent::Frozen {
+                    pair: pair.to_string(),
+                    frozen_until_ms,
+                }
+            } else {
+                MmpEvent::Unfrozen {
+                    pair: pair.to_string(),
+                }
+            }
+        };
+        self.emit(event);
+    }
+
+    async fn apply_policy(&self) {
+        let ResetPolicy::CoolDown {
+            cool_down,
+            max_resets_per_hour,
+        } = &self.policy
+        else {
+            return;
+        };
+
+        let now = now_ms();
+        let mut due = Vec::new();
+        {
+            let mut states = self.states.lock().unwrap();
+            for (pair, state) in states.iter_mut() {
+                if !state.frozen || now - state.frozen_since_ms < cool_down.as_millis() as i64 {
+                    continue;
+                }
+                while state.resets.front().is_some_and(|t| now - t > 3_600_000) {
+                    state.resets.pop_front();
+                }
+                if state.resets.len() < *max_resets_per_hour {
+                    due.push(pair.clone());
+                } else if !state.suppressed {
+                    state.suppressed = true;
+                    let _ = self.sender.send(MmpEvent::ResetSuppressed {
+                        pair: pair.clone(),
+                        reason: format!("{} resets in the last hour", state.resets.len()),
+                    });
+                }
+            }
+        }
+
+        for pair in due {
+            if let Err(e) = self.reset(&pair).await {
+                self.emit(MmpEvent::Error {
+                    pair,
+                    message: e.to_string(),
+                });
+            }
+        }
+    }
+
+    fn emit(&self, event: MmpEvent) {
+        // no subscribers is not an error
+        let _ = self.sender.send(event);
+    }
+}
@@ -0,0 +1,171 @@
+// A stand-in for the REST API on localhost, for benchmarks and load tests.
+//
+// `MockExchange` speaks just enough HTTP/1.1 (keep-alive, Content-Length bodies) for
+// `BitRestClient`. It does not check signatures and keeps no state: order requests are
+// acknowledged as open orders echoing the request, cancels report one cancelled order and
+// every GET returns an empty list.
+
+use crate::error::{BitError, BitResult};
+use crate::util::now_ms;
+use serde_json::Value;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::task::JoinHandle;
+
+pub struct MockExchange {
+    addr: SocketAddr,
+    requests: Arc<AtomicU64>,
+    task: JoinHandle<()>,
+}
+
+impl MockExchange {
+    /// Listen on `addr`, e.g. "127.0.0.1:0" for any free port.
+    pub async fn start(addr: &str) -> BitResult<Self> {
+        let listener = TcpListener::bind(addr)
+            .await
+            .map_err(|e| BitError::Config(format!("{}: {}", addr, e)))?;
+        let addr = listener
+            .local_addr()
+            .map_err(|e| BitError::Config(e.to_string()))?;
+        let requests = Arc::new(AtomicU64::new(0));
+        let counter = requests.clone();
+        let task = tokio::spawn(async move {
+            let next_id = Arc::new(AtomicU64::new(1));
+            loop {
+                let Ok((stream, _)) = listener.accept().await else {
+                    continue;
+                };
+                let _ = stream.set_nodelay(true);
+                tokio::spawn(serve(stream, next_id.clone(), counter.clone()));
+            }
+        });
+        Ok(Self {
+            addr,
+            requests,
+            task,
+        })
+    }
+
+    /// Base url to hand to `BitRestClient::new`.
+    pub fn url(&self) -> String {
+        format!("http://{}", self.addr)
+    }
+
+    /// Requests answered so far.
+    pub fn requests(&self) -> u64 {
+        self.requests.load(Ordering::Relaxed)
+    }
+}
+
+impl Drop for MockExchange {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|w| w == needle)
+}
+
+fn content_length(head: &str) -> usize {
+    head.lines()
+        .filter_map(|line| line.split_once(':'))
+        .find(|(name, _)| name.trim().eq_ignore_ascii_case("content-length"))
+        .and_then(|(_, value)| value.trim().parse().ok())
+        .unwrap_or(0)
+}
+
+async fn serve(mut stream: TcpStream, next_id: Arc<AtomicU64>, requests: Arc<AtomicU64>) {
+    let mut buf: Vec<u8> = Vec::with_capacity(8192);
+    let mut chunk = [0u8; 8192];
+    loop {
+        // read until a full request is buffered, pipelined requests stay in `buf`
+        let (head_end, body_len) = loop {
+            if let Some(i) = find(&buf, b"\r\n\r\n") {
+                let head = String::from_utf8_lossy(&buf[..i]);
+                break (i + 4, content_length(&head));
+            }
+            match stream.read(&mut chunk).await {
+                Ok(0) | Err(_) => return,
+                Ok(n) => buf.extend_from_slice(&chunk[..n]),
+            }
+        };
+        while buf.len() < head_end + body_len {
+            match stream.read(&mut chunk).await {
+                Ok(0) | Err(_) => return,
+                Ok(n) => buf.extend_from_slice(&chunk[..n]),
+            }
+        }
+
+        let head = String::from_utf8_lossy(&buf[..head_end]).into_owned();
+        let body: Value =
+            serde_json::from_slice(&buf[head_end..head_end + body_len]).unwrap_or(Value::Null);
+        buf.drain(..head_end + body_len);
+
+        let mut request_line = head.split_whitespace();
+        let method = request_line.next().unwrap_or("");
+        let path = request_line.next().unwrap_or("");
+        let path = path.split('?').next().unwrap_or("");
+        let data = respond(method, path, &body, &next_id);
+        let rsp = serde_json::json!({ "code": 0, "message": "", "data": data }).to_string();
+        let out = format!(
+            "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n{}",
+            rsp.len(),
+            rsp
+        );
+        requests.fetch_add(1, Ordering::Relaxed);
+        if stream.write_all(out.as_bytes()).await.is_err() {
+            return;
+        }
+    }
+}
+
+fn respond(method: &str, path: &str, body: &Value, next_id: &AtomicU64) -> Value {
+    if method != "POST" {
+        return serde_json::json!([]);
+    }
+    if path.contains("cancel") {
+        return serde_json::json!({ "num_cancelled": 1 });
+    }
+    if let Some(orders) = body["orders_data"].as_array() {
+        let orders: Vec<Value> = orders.iter().map(|o| ack(o, next_id)).collect();
+        return serde_json::json!({ "orders": orders });
+    }
+    if path.contains("orders") || path.contains("amend") {
+        return ack(body, next_id);
+    }
+    serde_json::json!({})
+}
+
+fn ack(req: &Value, next_id: &AtomicU64) -> Value {
+    let now = now_ms();
+    let field = |key: &str, default: &str| match &req[key] {
+        Value::Null => Value::String(default.to_string()),
+        v => v.clone(),
+    };
+    let instrument_id = match &req["instrument_id"] {
+        Value::Null => field("pair", ""),
+        v => v.clone(),
+    };
+    let order_id = match &req["order_id"] {
+        Value::Null => Value::String(next_id.fetch_add(1, Ordering::Relaxed).to_string()),
+        v => v.clone(),
+    };
+    serde_json::json!({
+        "order_id": order_id,
+        "instrument_id": instrument_id,
+        "side": field("side", "buy"),
+        "price": field("price", "0"),
+        "qty": field("qty", "0"),
+        "filled_qty": "0",
+        "avg_price": "0",
+        "status": "open",
+        "order_type": field("order_type", "limit"),
+        "label": field("label", ""),
+        "created_at": now,
+        "updated_at": now,
+    })
+}
@@ -0,0 +1,525 @@
+// Typed request/response models shared by the REST, WebSocket and simulated backends.
+
+use crate::util::{de_f64, de_levels, de_string};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::fmt;
+
+/// Which product family an endpoint or setting belongs to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Market {
+    Spot,
+    Linear,
+    Option,
+}
+
+impl Market {
+    /// Spot pairs have two parts ("BTC-USDT"), linear instruments three ("BTC-USDT-PERPETUAL")
+    /// and options four, ending in C or P ("BTC-27DEC24-60000-C").
+    pub fn of_instrument(instrument_id: &str) -> Market {
+        let parts: Vec<&str> = instrument_id.split('-').collect();
+        match parts.len() {
+            4.. if matches!(parts[parts.len() - 1], "C" | "P") => Market::Option,
+            3.. => Market::Linear,
+            _ => Market::Spot,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Side {
+    Buy,
+    Sell,
+}
+
+impl Side {
+    pub fn sign(&self) -> f64 {
+        match self {
+            Side::Buy => 1.0,
+            Side::Sell => -1.0,
+        }
+    }
+
+    pub fn opposite(&self) -> Side {
+        match self {
+            Side::Buy => Side::Sell,
+            Side::Sell => Side::Buy,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Side::Buy => "buy",
+            Side::Sell => "sell",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OrderType {
+    Limit,
+    Market,
+}
+
+impl OrderType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            OrderType::Limit => "limit",
+            OrderType::Market => "market",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TimeInForce {
+    Gtc,
+    Ioc,
+    Fok,
+}
+
+impl TimeInForce {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TimeInForce::Gtc => "gtc",
+            TimeInForce::Ioc => "ioc",
+            TimeInForce::Fok => "fok",
+        }
+    }
+}
+
+/// Quote currency of an instrument, e.g. "USDT" for "BTC-USDT-PERPETUAL" or "BTC-USDT".
+pub fn quote_currency(instrument_id: &str) -> &str {
+    instrument_id.split('-').nth(1).unwrap_or(instrument_id)
+}
+
+/// Base currency of an instrument, e.g. "BTC" for "BTC-USDT-PERPETUAL".
+pub fn base_currency(instrument_id: &str) -> &str {
+    instrument_id.split('-').next().unwrap_or(instrument_id)
+}
+
+/// Currency an instrument is margined and settled in, and the `currency` the endpoints
+/// expect: the quote for spot and linear, the underlying for coin-margined options.
+pub fn settle_currency(instrument_id: &str) -> &str {
+    match Market::of_instrument(instrument_id) {
+        Market::Option => base_currency(instrument_id),
+        _ => quote_currency(instrument_id),
+    }
+}
+
+/////////////////////////////////////////
+// order entry
+/////////////////////////////////////////
+
+/// A request field borrowed from a typed request, see `OrderRequest::params`. Decimals go out
+/// as JSON strings, like the ones `to_params` builds.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Param<'a> {
+    Str(&'a str),
+    Decimal(f64),
+    Bool(bool),
+}
+
+// the canonical form that gets signed
+impl fmt::Display for Param<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Param::Str(s) => f.write_str(s),
+            Param::Decimal(d) => write!(f, "{}", d),
+            Param::Bool(b) => write!(f, "{}", b),
+        }
+    }
+}
+
+impl Serialize for Param<'_> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            Param::Str(s) => serializer.serialize_str(s),
+            Param::Decimal(d) => serializer.collect_str(d),
+            Param::Bool(b) => serializer.serialize_bool(*b),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OrderRequest {
+    pub instrument_id: String,
+    pub side: Side,
+    pub qty: f64,
+    pub price: Option<f64>,
+    pub order_type: OrderType,
+    pub time_in_force: Option<TimeInForce>,
+    pub post_only: bool,
+    pub reduce_only: bool,
+    pub label: Option<String>,
+}
+
+impl OrderRequest {
+    pub fn limit(instrument_id: &str, side: Side, qty: f64, price: f64) -> Self {
+        Self {
+            instrument_id: instrument_id.to_string(),
+            side,
+            qty,
+            price: Some(price),
+            order_type: OrderType::Limit,
+            time_in_force: None,
+            post_only: false,
+            reduce_only: false,
+            label: None,
+        }
+    }
+
+    pub fn market(instrument_id: &str, side: Side, qty: f64) -> Self {
+        Self {
+            instrument_id: instrument_id.to_string(),
+            side,
+            qty,
+            price: None,
+            order_type: OrderType::Market,
+            time_in_force: None,
+            post_only: false,
+            reduce_only: false,
+            label: None,
+        }
+    }
+
+    pub fn with_label(mut self, label: &str) -> Self {
+        self.label = Some(label.to_string());
+        self
+    }
+
+    pub fn with_time_in_force(mut self, time_in_force: TimeInForce) -> Self {
+        self.time_in_force = Some(time_in_force);
+        self
+    }
+
+    pub fn post_only(mut self) -> Self {
+        self.post_only = true;
+        self
+    }
+
+    pub fn reduce_only(mut self) -> Self {
+        self.reduce_only = true;
+        self
+    }
+
+    /// Build the request body expected by the spot, linear or option order endpoints.
+    pub fn to_params(&self, market: Market) -> Value {
+        let mut params = match market {
+            Market::Spot => serde_json::json!({ "pair": self.instrument_id }),
+            Market::Linear | Market::Option => serde_json::json!({
+                "currency": settle_currency(&self.instrument_id),
+                "instrument_id": self.instrument_id,
+            }),
+        };
+        params["side"] = serde_json::json!(self.side);
+        params["qty"] = Value::String(self.qty.to_string());
+        params["order_type"] = serde_json::json!(self.order_type);
+        if let Some(price) = self.price {
+            params["price"] = Value::String(price.to_string());
+        }
+        if let Some(tif) = self.time_in_force {
+            params["time_in_force"] = serde_json::json!(tif);
+        }
+        if self.post_only {
+            params["post_only"] = Value::Bool(true);
+        }
+        if self.reduce_only {
+            params["reduce_only"] = Value::Bool(true);
+        }
+        if let Some(label) = &self.label {
+            params["label"] = Value::String(label.clone());
+        }
+        params
+    }
+
+    /// The fields of `to_params` sorted by key, `None` where it leaves a key out. Sent without
+    /// building a `Value` by `BitRestClient::call_private_typed`.
+    pub fn params(&self, market: Market) -> [Option<(&'static str, Param<'_>)>; 11] {
+        let instrument_id = self.instrument_id.as_str();
+        let (currency, pair) = match market {
+            Market::Spot => (None, Some(instrument_id)),
+            Market::Linear | Market::Option => (Some(settle_currency(instrument_id)), None),
+        };
+        [
+            currency.map(|c| ("currency", Param::Str(c))),
+            pair.is_none()
+                .then_some(("instrument_id", Param::Str(instrument_id))),
+            self.label.as_deref().map(|l| ("label", Param::Str(l))),
+            Some(("order_type", Param::Str(self.order_type.as_str()))),
+            pair.map(|p| ("pair", Param::Str(p))),
+            self.post_only.then_some(("post_only", Param::Bool(true))),
+            self.price.map(|p| ("price", Param::Decimal(p))),
+            Some(("qty", Param::Decimal(self.qty))),
+            self.reduce_only
+                .then_some(("reduce_only", Param::Bool(true))),
+            Some(("side", Param::Str(self.side.as_str()))),
+            self.time_in_force
+                .map(|t| ("time_in_force", Param::Str(t.as_str()))),
+        ]
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct AmendRequest {
+    pub instrument_id: String,
+    pub order_id: Option<String>,
+    pub label: Option<String>,
+    pub price: Option<f64>,
+    pub qty: Option<f64>,
+}
+
+impl AmendRequest {
+    pub fn to_params(&self, market: Market) -> Value {
+        let mut params = match market {
+            Market::Spot => serde_json::json!({ "pair": self.instrument_id }),
+            Market::Linear | Market::Option => serde_json::json!({
+                "currency": settle_currency(&self.instrument_id),
+            }),
+        };
+        if let Some(order_id) = &self.order_id {
+            params["order_id"] = Value::String(order_id.clone());
+        }
+        if let Some(label) = &self.label {
+            params["label"] = Value::String(label.clone());
+        }
+        if let Some(price) = self.price {
+            params["price"] = Value::String(price.to_string());
+        }
+        if let Some(qty) = self.qty {
+            params["qty"] = Value::String(qty.to_string());
+        }
+        params
+    }
+
+    /// The fields of `to_params` sorted by key, see `OrderRequest::params`.
+    pub fn params(&self, market: Market) -> [Option<(&'static str, Param<'_>)>; 6] {
+        let instrument_id = self.instrument_id.as_str();
+        let (currency, pair) = match market {
+            Market::Spot => (None, Some(instrument_id)),
+            Market::Linear | Market::Option => (Some(settle_currency(instrument_id)), None),
+        };
+        [
+            currency.map(|c| ("currency", Param::Str(c))),
+            self.label.as_deref().map(|l| ("label", Param::Str(l))),
+            self.order_id
+                .as_deref()
+                .map(|o| ("order_id", Param::Str(o))),
+            pair.map(|p| ("pair", Param::Str(p))),
+            self.price.map(|p| ("price", Param::Decimal(p))),
+            self.qty.map(|q| ("qty", Param::Decimal(q))),
+        ]
+    }
+}
+
+/// Cancel by order id, by label, every order of an instrument, or everything in a currency.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct CancelRequest {
+    pub currency: String,
+    pub instrument_id: Option<String>,
+    pub order_id: Option<String>,
+    pub label: Option<String>,
+}
+
+impl CancelRequest {
+    pub fn all(currency: &str) -> Self {
+        Self {
+            currency: currency.to_string(),
+            ..Default::default()
+        }
+    }
+
+    pub fn by_order_id(instrument_id: &str, order_id: &str) -> Self {
+        Self {
+            currency: settle_currency(instrument_id).to_string(),
+            instrument_id: Some(instrument_id.to_string()),
+            order_id: Some(order_id.to_string()),
+            label: None,
+        }
+    }
+
+    pub fn to_params(&self, market: Market) -> Value {
+        let mut params = serde_json::json!({ "currency": self.currency });
+        if let Some(instrument_id) = &self.instrument_id {
+            let key = match market {
+                Market::Spot => "pair",
+                Market::Linear | Market::Option => "instrument_id",
+            };
+            params[key] = Value::String(instrument_id.clone());
+        }
+        if let Some(order_id) = &self.order_id {
+            params["order_id"] = Value::String(order_id.clone());
+        }
+        if let Some(label) = &self.label {
+            params["label"] = Value::String(label.clone());
+        }
+        params
+    }
+}
+
+/////////////////////////////////////////
+// order / trade / position
+/////////////////////////////////////////
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Order {
+    #[serde(deserialize_with = "de_string")]
+    pub order_id: String,
+    #[serde(alias = "pair")]
+    pub instrument_id: String,
+    pub side: Side,
+    #[serde(default, deserialize_with = "de_f64")]
+    pub price: f64,
+    #[serde(deserialize_with = "de_f64")]
+    pub qty: f64,
+    #[serde(default, deserialize_with = "de_f64")]
+    pub filled_qty: f64,
+    #[serde(default, deserialize_with = "de_f64")]
+    pub avg_price: f64,
+    #[serde(default)]
+    pub status: String,
+    pub order_type: OrderType,
+    #[serde(default)]
+    pub label: String,
+    #[serde(default)]
+    pub created_at: i64,
+    #[serde(default)]
+    pub updated_at: i64,
+}
+
+impl Order {
+    pub fn remaining_qty(&self) -> f64 {
+        self.qty - self.filled_qty
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.status == "open" || self.status == "pending"
+    }
+}
+
+/// One of our own fills.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Trade {
+    #[serde(deserialize_with = "de_string")]
+    pub trade_id: String,
+    #[serde(deserialize_with = "de_string")]
+    pub order_id: String,
+    #[serde(alias = "pair")]
+    pub instrument_id: String,
+    pub side: Side,
+    #[serde(deserialize_with = "de_f64")]
+    pub price: f64,
+    #[serde(deserialize_with = "de_f64")]
+    pub qty: f64,
+    #[serde(default, deserialize_with = "de_f64")]
+    pub fee: f64,
+    #[serde(default)]
+    pub fee_ccy: String,
+    #[serde(default)]
+    pub is_taker: bool,
+    #[serde(default)]
+    pub label: String,
+    #[serde(default)]
+    pub created_at: i64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Position {
+    pub instrument_id: String,
+    /// Signed, negative for short.
+    #[serde(deserialize_with = "de_f64")]
+    pub qty: f64,
+    #[serde(default, deserialize_with = "de_f64")]
+    pub avg_price: f64,
+    #[serde(default, deserialize_with = "de_f64")]
+    pub mark_price: f64,
+    #[serde(default, deserialize_with = "de_f64")]
+    pub position_pnl: f64,
+    #[serde(default, deserialize_with = "de_f64")]
+    pub liq_price: f64,
+    #[serde(default, deserialize_with = "de_f64")]
+    pub leverage: f64,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct UmAccount {
+    #[serde(default, deserialize_with = "de_f64")]
+    pub total_collateral: f64,
+    #[serde(default, deserialize_with = "de_f64")]
+    pub total_margin_balance: f64,
+    #[serde(default, deserialize_with = "de_f64")]
+    pub total_available: f64,
+    #[serde(default, deserialize_with = "de_f64")]
+    pub total_initial_margin: f64,
+    #[serde(default, deserialize_with = "de_f64")]
+    pub total_maintenance_margin: f64,
+    #[serde(default, deserialize_with = "de_f64")]
+    pub total_position_pnl: f64,
+    #[serde(default)]
+    pub details: Vec<Value>,
+}
+
+/////////////////////////////////////////
+// public market data
+/////////////////////////////////////////
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Ticker {
+    #[serde(alias = "pair")]
+    pub instrument_id: String,
+    #[serde(default)]
+    pub time: i64,
+    #[serde(default, deserialize_with = "de_f64")]
+    pub best_bid: f64,
+    #[serde(default, deserialize_with = "de_f64")]
+    pub best_ask: f64,
+    #[serde(default, deserialize_with = "de_f64")]
+    pub best_bid_qty: f64,
+    #[serde(default, deserialize_with = "de_f64")]
+    pub best_ask_qty: f64,
+    #[serde(default, deserialize_with = "de_f64")]
+    pub last_price: f64,
+    #[serde(default, deserialize_with = "de_f64")]
+    pub mark_price: f64,
+    #[serde(default, deserialize_with = "de_f64")]
+    pub index_price: f64,
+    #[serde(default, deserialize_with = "de_f64")]
+    pub volume24h: f64,
+    #[serde(default, deserialize_with = "de_f64")]
+    pub funding_rate: f64,
+}
+
+/// A depth snapshot or incremental update. Levels are `(price, qty)`, qty 0 removes the level.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Depth {
+    #[serde(alias = "pair")]
+    pub instrument_id: String,
+    #[serde(default, rename = "type")]
+    pub kind: String,
+    #[serde(default)]
+    pub sequence: i64,
+    #[serde(default)]
+    pub prev_sequence: i64,
+    #[serde(default)]
+    pub timestamp: i64,
+    #[serde(default, deserialize_with = "de_levels")]
+    pub bids: Vec<(f64, f64)>,
+    #[serde(default, deserialize_with = "de_levels")]
+    pub asks: Vec<(f64, f64)>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PublicTrade {
+    #[serde(deserialize_with = "de_string")]
+    pub trade_id: String,
+    #[serde(alias = "pair")]
+    pub instrument_id: String,
+    pub side: Side,
+    #[serde(deserialize_with = "de_f64")]
+    pub price: f64,
+    #[serde(deserialize_with = "de_f64")]
+    pub qty: f64,
+    #[serde(default)]
+    pub created_at: i64,
+}
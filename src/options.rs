@@ -0,0 +1,263 @@
+// Coin-margined options: instruments, greeks, positions and settlement history.
+//
+// Order entry goes through `TradingApi` with `Market::Option`, these are the option-only calls.
+
+use crate::bit_rest_client::BitRestClient;
+use crate::error::{BitResult, parse_data};
+use crate::models::Ticker;
+use crate::util::{de_f64, de_string};
+use chrono::{NaiveDate, NaiveTime};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OptionKind {
+    Call,
+    Put,
+}
+
+/// The parts of an option name such as "BTC-27DEC24-60000-C".
+#[derive(Debug, Clone, PartialEq)]
+pub struct OptionSymbol {
+    pub underlying: String,
+    pub expiry: NaiveDate,
+    pub strike: f64,
+    pub kind: OptionKind,
+}
+
+impl OptionSymbol {
+    pub fn parse(instrument_id: &str) -> Option<OptionSymbol> {
+        let mut parts = instrument_id.split('-');
+        let underlying = parts.next()?.to_string();
+        let expiry = NaiveDate::parse_from_str(parts.next()?, "%d%b%y").ok()?;
+        let strike = parts.next()?.parse().ok()?;
+        let kind = match parts.next()? {
+            "C" => OptionKind::Call,
+            "P" => OptionKind::Put,
+            _ => return None,
+        };
+        if parts.next().is_some() {
+            return None;
+        }
+        Some(OptionSymbol {
+            underlying,
+            expiry,
+            strike,
+            kind,
+        })
+    }
+
+    /// Options expire at 08:00 UTC on the expiry date.
+    pub fn expiry_ms(&self) -> i64 {
+        self.expiry
+            .and_time(NaiveTime::from_hms_opt(8, 0, 0).unwrap())
+            .and_utc()
+            .timestamp_millis()
+    }
+}
+
+impl fmt::Display for OptionSymbol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let kind = match self.kind {
+            OptionKind::Call => "C",
+            OptionKind::Put => "P",
+        };
+        write!(
+            f,
+            "{}-{}-{}-{}",
+            self.underlying,
+            self.expiry.format("%-d%b%y").to_string().to_uppercase(),
+            self.strike,
+            kind
+        )
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OptionInstrument {
+    pub instrument_id: String,
+    #[serde(default)]
+    pub base_currency: String,
+    #[serde(deserialize_with = "de_f64")]
+    pub strike_price: f64,
+    pub expiration_at: i64,
+    pub option_type: OptionKind,
+    #[serde(default, deserialize_with = "de_f64")]
+    pub price_step: f64,
+    #[serde(default, deserialize_with = "de_f64")]
+    pub size_step: f64,
+    #[serde(default, deserialize_with = "de_f64")]
+    pub min_size: f64,
+    #[serde(default)]
+    pub is_active: bool,
+}
+
+/// Sensitivities in the settlement currency. For a position they are already scaled by qty.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct Greeks {
+    #[serde(default, deserialize_with = "de_f64")]
+    pub delta: f64,
+    #[serde(default, deserialize_with = "de_f64")]
+    pub gamma: f64,
+    #[serde(default, deserialize_with = "de_f64")]
+    pub vega: f64,
+    #[serde(default, deserialize_with = "de_f64")]
+    pub theta: f64,
+}
+
+impl std::ops::Add for Greeks {
+    type Output = Greeks;
+
+    fn add(self, other: Greeks) -> Greeks {
+        Greeks {
+            delta: self.delta + other.delta,
+            gamma: self.gamma + other.gamma,
+            vega: self.vega + other.vega,
+            theta: self.theta + other.theta,
+        }
+    }
+}
+
+/// Ticker of an option, on REST `/v1/tickers` and the WebSocket `ticker` channel.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct OptionTicker {
+    #[serde(flatten)]
+    pub ticker: Ticker,
+    #[serde(default, alias = "sigma", deserialize_with = "de_f64")]
+    pub mark_iv: f64,
+    #[serde(default, alias = "bid_sigma", deserialize_with = "de_f64")]
+    pub bid_iv: f64,
+    #[serde(default, alias = "ask_sigma", deserialize_with = "de_f64")]
+    pub ask_iv: f64,
+    #[serde(default, deserialize_with = "de_f64")]
+    pub underlying_price: f64,
+    #[serde(flatten)]
+    pub greeks: Greeks,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OptionPosition {
+    pub instrument_id: String,
+    #[serde(deserialize_with = "de_f64")]
+    pub qty: f64,
+    #[serde(default, deserialize_with = "de_f64")]
+    pub avg_price: f64,
+    #[serde(default, deserialize_with = "de_f64")]
+    pub mark_price: f64,
+    #[serde(default, deserialize_with = "de_f64")]
+    pub index_price: f64,
+    #[serde(default, deserialize_with = "de_f64")]
+    pub position_pnl: f64,
+    #[serde(default)]
+    pub expiration_at: i64,
+    #[serde(flatten)]
+    pub greeks: Greeks,
+}
+
+/// Book-level greeks, the sum over positions.
+pub fn net_greeks(positions: &[OptionPosition]) -> Greeks {
+    positions
+        .iter()
+        .fold(Greeks::default(), |acc, p| acc + p.greeks)
+}
+
+/// One expired position, from the delivery or settlement history.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SettlementRecord {
+    #[serde(default, deserialize_with = "de_string")]
+    pub id: String,
+    pub instrument_id: String,
+    #[serde(deserialize_with = "de_f64")]
+    pub qty: f64,
+    #[serde(
+        alias = "delivery_price",
+        alias = "settlement_price",
+        deserialize_with = "de_f64"
+    )]
+    pub price: f64,
+    #[serde(
+        default,
+        alias = "position_pnl",
+        alias = "settlement_pnl",
+        deserialize_with = "de_f64"
+    )]
+    pub pnl: f64,
+    #[serde(default, deserialize_with = "de_f64")]
+    pub fee: f64,
+    #[serde(default)]
+    pub created_at: i64,
+}
+
+fn history_params(currency: &str, start_time: Option<i64>, end_time: Option<i64>) -> Value {
+    let mut params = serde_json::json!({ "currency": currency });
+    if let Some(start_time) = start_time {
+        params["start_time"] = Value::from(start_time);
+    }
+    if let Some(end_time) = end_time {
+        params["end_time"] = Value::from(end_time);
+    }
+    params
+}
+
+/////////////////////////////////////////
+// typed endpoints
+/////////////////////////////////////////
+pub async fn instruments(
+    client: &BitRestClient,
+    currency: &str,
+) -> BitResult<Vec<OptionInstrument>> {
+    let params = serde_json::json!({ "currency": currency, "category": "option" });
+    parse_data(client.option_get_instruments(&params).await?)
+}
+
+pub async fn ticker(client: &BitRestClient, instrument_id: &str) -> BitResult<OptionTicker> {
+    let params = serde_json::json!({ "instrument_id": instrument_id });
+    parse_data(client.option_get_tickers(&params).await?)
+}
+
+pub async fn positions(client: &BitRestClient, currency: &str) -> BitResult<Vec<OptionPosition>> {
+    let mut params = serde_json::json!({ "currency": currency, "category": "option" });
+    parse_data(client.option_get_positions(&mut params).await?)
+}
+
+pub async fn deliveries(
+    client: &BitRestClient,
+    currency: &str,
+    start_time: Option<i64>,
+    end_time: Option<i64>,
+) -> BitResult<Vec<SettlementRecord>> {
+    let mut params = history_params(currency, start_time, end_time);
+    parse_data(client.option_get_deliveries(&mut params).await?)
+}
+
+pub async fn settlements(
+    client: &BitRestClient,
+    currency: &str,
+    start_time: Option<i64>,
+    end_time: Option<i64>,
+) -> BitResult<Vec<SettlementRecord>> {
+    let mut params = history_params(currency, start_time, end_time);
+    parse_data(client.option_get_settlements(&mut params).await?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn symbol_round_trips_with_a_single_digit_day() {
+        let symbol = OptionSymbol::parse("BTC-5JAN24-45000-P").unwrap();
+        assert_eq!(symbol.expiry, NaiveDate::from_ymd_opt(2024, 1, 5).unwrap());
+        assert_eq!(symbol.strike, 45000.0);
+        assert_eq!(symbol.kind, OptionKind::Put);
+        assert_eq!(symbol.to_string(), "BTC-5JAN24-45000-P");
+    }
+
+    #[test]
+    fn symbol_round_trips_with_a_two_digit_day() {
+        let id = "ETH-27DEC24-3500-C";
+        assert_eq!(OptionSymbol::parse(id).unwrap().to_string(), id);
+    }
+}
@@ -0,0 +1,138 @@
+// Local order book maintained from depth snapshots and updates.
+
+use crate::metrics;
+use crate::models::{Depth, Side};
+use std::cmp::Reverse;
+use std::collections::BTreeMap;
+
+/// Prices are kept as integer ticks of 1e-8 so they can be used as map keys.
+const PRICE_SCALE: f64 = 1e8;
+
+fn to_key(price: f64) -> i64 {
+    (price * PRICE_SCALE).round() as i64
+}
+
+fn from_key(key: i64) -> f64 {
+    key as f64 / PRICE_SCALE
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct OrderBook {
+    pub instrument_id: String,
+    pub sequence: i64,
+    pub timestamp: i64,
+    bids: BTreeMap<Reverse<i64>, f64>,
+    asks: BTreeMap<i64, f64>,
+    /// Set by a sequence gap, cleared by the next snapshot.
+    stale: bool,
+}
+
+impl OrderBook {
+    pub fn new(instrument_id: &str) -> Self {
+        Self {
+            instrument_id: instrument_id.to_string(),
+            ..Default::default()
+        }
+    }
+
+    /// Apply a depth message. Anything that is not an "update" is treated as a snapshot.
+    ///
+    /// An update that does not follow on the book's sequence marks the book invalid, and
+    /// updates are ignored until the next snapshot.
+    pub fn apply(&mut self, depth: &Depth) {
+        if depth.kind == "update" {
+            if self.stale {
+                return;
+            }
+            if self.sequence != 0
+                && depth.prev_sequence != 0
+                && depth.prev_sequence != self.sequence
+            {
+                metrics::order_book_resync(&self.instrument_id);
+                self.stale = true;
+                return;
+            }
+        } else {
+            self.bids.clear();
+            self.asks.clear();
+            self.stale = false;
+        }
+        for &(price, qty) in &depth.bids {
+            if qty == 0.0 {
+                self.bids.remove(&Reverse(to_key(price)));
+            } else {
+                self.bids.insert(Reverse(to_key(price)), qty);
+            }
+        }
+        for &(price, qty) in &depth.asks {
+            if qty == 0.0 {
+                self.asks.remove(&to_key(price));
+            } else {
+                self.asks.insert(to_key(price), qty);
+            }
+        }
+        self.sequence = depth.sequence;
+        self.timestamp = depth.timestamp;
+    }
+
+    /// False between a sequence gap and the next snapshot. The levels are then out of date
+    /// and must not be traded against.
+    pub fn is_valid(&self) -> bool {
+        !self.stale
+    }
+
+    pub fn best_bid(&self) -> Option<(f64, f64)> {
+        self.bids.iter().next().map(|(k, q)| (from_key(k.0), *q))
+    }
+
+    pub fn best_ask(&self) -> Option<(f64, f64)> {
+        self.asks.iter().next().map(|(k, q)| (from_key(*k), *q))
+    }
+
+    pub fn mid(&self) -> Option<f64> {
+        match (self.best_bid(), self.best_ask()) {
+            (Some((b, _)), Some((a, _))) => Some((a + b) / 2.0),
+            _ => None,
+        }
+    }
+
+    /// Best levels first.
+    pub fn bids(&self) -> impl Iterator<Item = (f64, f64)> + '_ {
+        self.bids.iter().map(|(k, q)| (from_key(k.0), *q))
+    }
+
+    /// Best levels first.
+    pub fn asks(&self) -> impl Iterator<Item = (f64, f64)> + '_ {
+        self.asks.iter().map(|(k, q)| (from_key(*k), *q))
+    }
+
+    /// Levels a taker on `side` would trade against, best first.
+    pub fn opposite_levels(&self, side: Side) -> Box<dyn Iterator<Item = (f64, f64)> + '_> {
+        match side {
+            Side::Buy => Box::new(self.asks()),
+            Side::Sell => Box::new(self.bids()),
+        }
+    }
+
+    /// Remove liquidity consumed by a local taker on `side` at `price`, so the same level is
+    /// not filled twice before the next update arrives.
+    pub fn consume(&mut self, side: Side, price: f64, qty: f64) {
+        let remaining = match side {
+            Side::Buy => self.asks.get_mut(&to_key(price)),
+            Side::Sell => self.bids.get_mut(&Reverse(to_key(price))),
+        };
+        if let Some(level_qty) = remaining {
+            *level_qty -= qty;
+            if *level_qty <= 1e-12 {
+                match side {
+                    Side::Buy => self.asks.remove(&to_key(price)),
+                    Side::Sell => self.bids.remove(&Reverse(to_key(price))),
+                };
+            }
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bids.is_empty() && self.asks.is_empty()
+    }
+}
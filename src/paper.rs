@@ -0,0 +1,196 @@
+// Paper trading: the `TradingApi` surface backed by the local matching engine and fed by the
+// live public WebSocket. Fills, fees and positions are computed locally and published as the
+// same `WsEvent`s the private WebSocket produces.
+
+use crate::bit_ws_client::{BitWsClient, WsEvent, WsSubscription};
+use crate::error::BitResult;
+use crate::matching::{FeeSchedule, MatchingEngine};
+use crate::models::{
+    AmendRequest, CancelRequest, Market, Order, OrderRequest, Position, Trade, UmAccount,
+};
+use crate::trading::TradingApi;
+use crate::util::now_ms;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex};
+use tokio::sync::broadcast;
+use tokio::task::JoinHandle;
+
+pub struct PaperExchange {
+    engine: Mutex<MatchingEngine>,
+    sender: broadcast::Sender<WsEvent>,
+}
+
+impl PaperExchange {
+    /// `balances` are the starting balances per currency, e.g. `{"USDT": 100000.0}`.
+    pub fn new(fees: FeeSchedule, balances: HashMap<String, f64>) -> Self {
+        Self::with_engine(MatchingEngine::new(fees, balances))
+    }
+
+    pub fn with_engine(engine: MatchingEngine) -> Self {
+        let (sender, _) = broadcast::channel(4096);
+        Self {
+            engine: Mutex::new(engine),
+            sender,
+        }
+    }
+
+    /// Private events (orders, user trades, positions), same types as the private WebSocket.
+    pub fn subscribe(&self) -> broadcast::Receiver<WsEvent> {
+        self.sender.subscribe()
+    }
+
+    /// Subscribe to depth, trades and ticker for `instruments` on the public WebSocket at
+    /// `ws_url` and match against them until the returned task is aborted.
+    pub fn connect(self: &Arc<Self>, ws_url: &str, instruments: &[&str]) -> JoinHandle<()> {
+        let mut events = BitWsClient::new(ws_url)
+            .subscribe(WsSubscription::public(
+                &["depth", "trade", "ticker"],
+                instruments,
+            ))
+            .spawn();
+        let exchange = Arc::clone(self);
+        tokio::spawn(async move {
+            while let Some(event) = events.recv().await {
+                exchange.on_market_event(&event);
+            }
+        })
+    }
+
+    /// Feed a public market data event by hand, e.g. from an existing WebSocket connection.
+    pub fn on_market_event(&self, event: &WsEvent) {
+        let events = {
+            let mut engine = self.engine.lock().unwrap();
+            engine.set_time(now_ms());
+            engine.on_market_event(event)
+        };
+        self.publish(events);
+    }
+
+    pub fn balances(&self) -> BTreeMap<String, f64> {
+        self.engine.lock().unwrap().balances().clone()
+    }
+
+    fn publish(&self, events: Vec<WsEvent>) {
+        for event in events {
+            // no subscribers is not an error
+            let _ = self.sender.send(event);
+        }
+    }
+
+    fn with_engine_mut<T>(
+        &self,
+        f: impl FnOnce(&mut MatchingEngine) -> BitResult<(T, Vec<WsEvent>)>,
+    ) -> BitResult<T> {
+        let (result, events) = {
+            let mut engine = self.engine.lock().unwrap();
+            engine.set_time(now_ms());
+            f(&mut engine)?
+        };
+        self.publish(events);
+        Ok(result)
+    }
+}
+
+impl TradingApi for PaperExchange {
+    async fn new_order(&self, market: Market, req: &OrderRequest) -> BitResult<Order> {
+        self.with_engine_mut(|e| e.new_order(market, req))
+    }
+
+    async fn amend_order(&self, _market: Market, req: &AmendRequest) -> BitResult<Order> {
+        self.with_engine_mut(|e| e.amend_order(req))
+    }
+
+    async fn cancel_order(&self, market: Market, req: &CancelRequest) -> BitResult<i64> {
+        self.with_engine_mut(|e| Ok(e.cancel(market, req)))
+    }
+
+    async fn batch_new_orders(
+        &self,
+        market: Market,
+        reqs: &[OrderRequest],
+    ) -> BitResult<Vec<Order>> {
+        let mut orders = Vec::with_capacity(reqs.len());
+        for req in reqs {
+            orders.push(self.new_order(market, req).await?);
+        }
+        Ok(orders)
+    }
+
+    async fn batch_amend_orders(
+        &self,
+        market: Market,
+        reqs: &[AmendRequest],
+    ) -> BitResult<Vec<Order>> {
+        let mut orders = Vec::with_capacity(reqs.len());
+        for req in reqs {
+            orders.push(self.amend_order(market, req).await?);
+        }
+        Ok(orders)
+    }
+
+    async fn open_orders(
+        &self,
+        market: Market,
+        currency: &str,
+        instrument_id: Option<&str>,
+    ) -> BitResult<Vec<Order>> {
+        Ok(self
+            .engine
+            .lock()
+            .unwrap()
+            .open_orders(market, currency, instrument_id))
+    }
+
+    /// Only open orders are found, the simulator forgets orders once they leave the book.
+    async fn order_by_label(
+        &self,
+        market: Market,
+        currency: &str,
+        label: &str,
+    ) -> BitResult<Option<Order>> {
+        Ok(self
+            .engine
+            .lock()
+            .unwrap()
+            .open_orders(market, currency, None)
+            .into_iter()
+            .rfind(|o| o.label == label))
+    }
+
+    async fn user_trades(
+        &self,
+        market: Market,
+        currency: &str,
+        instrument_id: Option<&str>,
+    ) -> BitResult<Vec<Trade>> {
+        let mut trades = self
+            .engine
+            .lock()
+            .unwrap()
+            .user_trades(currency, instrument_id);
+        trades.retain(|t| Market::of_instrument(&t.instrument_id) == market);
+        Ok(trades)
+    }
+
+    async fn user_trades_since(
+        &self,
+        market: Market,
+        currency: &str,
+        instrument_id: Option<&str>,
+        start_ms: i64,
+    ) -> BitResult<Vec<Trade>> {
+        let trades = self.user_trades(market, currency, instrument_id).await?;
+        Ok(trades
+            .into_iter()
+            .filter(|t| t.created_at >= start_ms)
+            .collect())
+    }
+
+    async fn positions(&self, currency: &str) -> BitResult<Vec<Position>> {
+        Ok(self.engine.lock().unwrap().positions(currency))
+    }
+
+    async fn um_account(&self) -> BitResult<UmAccount> {
+        Ok(self.engine.lock().unwrap().um_account())
+    }
+}
@@ -0,0 +1,531 @@
+// Daily PnL and fee report per instrument.
+//
+// Fills are replayed in time order through a FIFO or average-cost book. Each UTC day gets the
+// realized PnL of the fills that closed exposure, the end-of-day unrealized PnL against the
+// mark, funding from the UM transaction log and fees. Amounts are in the instrument's settle
+// currency and converted to USD with caller-supplied rates.
+
+use crate::bit_rest_client::BitRestClient;
+use crate::error::{BitError, BitResult, parse_page};
+use crate::ledger::{self, LedgerQuery, LedgerRecord, LedgerSource};
+use crate::models::{Market, Position, Trade, base_currency, settle_currency};
+use crate::trading::TradingApi;
+use chrono::{DateTime, NaiveDate};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::fmt::Write as _;
+use std::io::{self, Write};
+
+const DAY_MS: i64 = 24 * 3600 * 1000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CostBasis {
+    Fifo,
+    Average,
+}
+
+/// UTC date of a millisecond timestamp.
+pub fn utc_day(ms: i64) -> NaiveDate {
+    DateTime::from_timestamp_millis(ms)
+        .map(|t| t.date_naive())
+        .unwrap_or_default()
+}
+
+/////////////////////////////////////////
+// position book
+/////////////////////////////////////////
+/// Open exposure of one instrument. Lots are signed, all with the same sign.
+#[derive(Debug, Clone, Default)]
+struct Book {
+    lots: VecDeque<(f64, f64)>,
+}
+
+impl Book {
+    fn qty(&self) -> f64 {
+        self.lots.iter().map(|(q, _)| q).sum()
+    }
+
+    fn unrealized(&self, mark: f64) -> f64 {
+        self.lots.iter().map(|(q, p)| q * (mark - p)).sum()
+    }
+
+    /// Apply a signed fill and return the realized PnL.
+    fn fill(&mut self, basis: CostBasis, mut qty: f64, price: f64) -> f64 {
+        let mut realized = 0.0;
+        while qty != 0.0 {
+            let Some(front) = self.lots.front_mut() else {
+                break;
+            };
+            if front.0.signum() == qty.signum() {
+                break;
+            }
+            let closed = qty.abs().min(front.0.abs()) * front.0.signum();
+            realized += closed * (price - front.1);
+            front.0 -= closed;
+            qty += closed;
+            if front.0.abs() < 1e-12 {
+                self.lots.pop_front();
+            }
+        }
+        if qty.abs() > 1e-12 {
+            self.lots.push_back((qty, price));
+        }
+        if basis == CostBasis::Average && self.lots.len() > 1 {
+            let total = self.qty();
+            let cost: f64 = self.lots.iter().map(|(q, p)| q * p).sum();
+            self.lots = VecDeque::from([(total, cost / total)]);
+        }
+        realized
+    }
+}
+
+/////////////////////////////////////////
+// report
+/////////////////////////////////////////
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct PnlAmounts {
+    pub realized: f64,
+    /// End-of-day mark-to-market of the open position.
+    pub unrealized: f64,
+    /// `unrealized` minus the previous day's.
+    pub unrealized_change: f64,
+    /// Received is positive.
+    pub funding: f64,
+    /// Paid is positive, rebates are negative.
+    pub fees: f64,
+}
+
+impl PnlAmounts {
+    /// The day's result: realized, plus the move in unrealized, plus funding, minus fees.
+    pub fn net(&self) -> f64 {
+        self.realized + self.unrealized_change + self.funding - self.fees
+    }
+
+    fn scaled(&self, rate: f64) -> PnlAmounts {
+        PnlAmounts {
+            realized: self.realized * rate,
+            unrealized: self.unrealized * rate,
+            unrealized_change: self.unrealized_change * rate,
+            funding: self.funding * rate,
+            fees: self.fees * rate,
+        }
+    }
+}
+
+impl std::ops::AddAssign for PnlAmounts {
+    fn add_assign(&mut self, other: PnlAmounts) {
+        self.realized += other.realized;
+        self.unrealized += other.unrealized;
+        self.unrealized_change += other.unrealized_change;
+        self.funding += other.funding;
+        self.fees += other.fees;
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DailyPnl {
+    pub date: NaiveDate,
+    pub instrument_id: String,
+    /// Settle currency of the instrument, the unit of `amounts`.
+    pub currency: String,
+    pub amounts: PnlAmounts,
+    pub usd: PnlAmounts,
+    /// Position at the end of the day.
+    pub position: f64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PnlReport {
+    pub basis: CostBasis,
+    /// Ordered by date, then instrument.
+    pub rows: Vec<DailyPnl>,
+}
+
+/// Collects fills, funding and marks, then computes the report with `finish`.
+#[derive(Debug, Clone)]
+pub struct PnlReportBuilder {
+    basis: CostBasis,
+    start: NaiveDate,
+    end: NaiveDate,
+    opening: Vec<Position>,
+    trades: Vec<Trade>,
+    funding: Vec<LedgerRecord>,
+    marks: BTreeMap<(String, NaiveDate), f64>,
+    usd_rates: HashMap<String, f64>,
+}
+
+impl PnlReportBuilder {
+    /// Report the days `start..=end`. Stablecoins are priced at 1 USD unless overridden.
+    pub fn new(basis: CostBasis, start: NaiveDate, end: NaiveDate) -> Self {
+        let usd_rates = ["USD", "USDT", "USDC"]
+            .iter()
+            .map(|c| (c.to_string(), 1.0))
+            .collect();
+        Self {
+            basis,
+            start,
+            end,
+            opening: vec![],
+            trades: vec![],
+            funding: vec![],
+            marks: BTreeMap::new(),
+            usd_rates,
+        }
+    }
+
+    /// Positions held at the start of the first day, opened at `avg_price`.
+    pub fn opening_positions(mut self, positions: Vec<Position>) -> Self {
+        self.opening = positions;
+        self
+    }
+
+    pub fn trades(mut self, trades: Vec<Trade>) -> Self {
+        self.trades.extend(trades);
+        self
+    }
+
+    /// UM transaction log entries. Only funding entries are used.
+    pub fn funding(mut self, records: Vec<LedgerRecord>) -> Self {
+        self.funding.extend(
+            records
+                .into_iter()
+                .filter(|r| r.tx_type.contains("funding")),
+        );
+        self
+    }
+
+    /// End-of-day mark. Days without one reuse the last known mark, or the last fill price.
+    pub fn mark(mut self, instrument_id: &str, date: NaiveDate, price: f64) -> Self {
+        self.marks.insert((instrument_id.to_string(), date), price);
+        self
+    }
+
+    pub fn usd_rate(mut self, currency: &str, rate: f64) -> Self {
+        self.usd_rates.insert(currency.to_string(), rate);
+        self
+    }
+
+    fn rate(&self, currency: &str) -> BitResult<f64> {
+        self.usd_rates
+            .get(currency)
+            .copied()
+            .ok_or_else(|| BitError::Invalid(format!("no USD rate for {}", currency)))
+    }
+
+    /// Fee of a fill in the instrument's settle currency. Spot fees charged in the base
+    /// currency are converted at the fill price.
+    fn fee_in_settle(&self, trade: &Trade) -> BitResult<f64> {
+        let settle = settle_currency(&trade.instrument_id);
+        if trade.fee_ccy.is_empty() || trade.fee_ccy == settle {
+            return Ok(trade.fee);
+        }
+        if trade.fee_ccy == base_currency(&trade.instrument_id)
+            && Market::of_instrument(&trade.instrument_id) == Market::Spot
+        {
+            return Ok(trade.fee * trade.price);
+        }
+        Ok(trade.fee * self.rate(&trade.fee_ccy)? / self.rate(settle)?)
+    }
+
+    pub fn finish(mut self) -> BitResult<PnlReport> {
+        if self.end < self.start {
+            return Err(BitError::Invalid(
+                "report ends before it starts".to_string(),
+            ));
+        }
+        self.trades.sort_by_key(|t| t.created_at);
+
+        let mut books: BTreeMap<String, Book> = BTreeMap::new();
+        let mut last_price: HashMap<String, f64> = HashMap::new();
+        let mut last_unrealized: HashMap<String, f64> = HashMap::new();
+        for p in &self.opening {
+            books.entry(p.instrument_id.clone()).or_default().lots =
+                VecDeque::from([(p.qty, p.avg_price)]);
+            last_price.insert(p.instrument_id.clone(), p.avg_price);
+        }
+
+        // flows per (instrument, day)
+        let mut flows: BTreeMap<(String, NaiveDate), PnlAmounts> = BTreeMap::new();
+        let mut trades = self.trades.iter().peekable();
+        let mut day = self.start;
+        let mut rows = vec![];
+        while day <= self.end {
+            while let Some(t) = trades.peek()
+                && utc_day(t.created_at) <= day
+            {
+                let book = books.entry(t.instrument_id.clone()).or_default();
+                let realized = book.fill(self.basis, t.side.sign() * t.qty, t.price);
+                last_price.insert(t.instrument_id.clone(), t.price);
+                // fills before the first day only build up the opening book
+                if utc_day(t.created_at) == day {
+                    let flow = flows.entry((t.instrument_id.clone(), day)).or_default();
+                    flow.realized += realized;
+                    flow.fees += self.fee_in_settle(t)?;
+                }
+                trades.next();
+            }
+            for r in self.funding.iter().filter(|r| utc_day(r.time) == day) {
+                books.entry(r.instrument_id.clone()).or_default();
+                flows
+                    .entry((r.instrument_id.clone(), day))
+                    .or_default()
+                    .funding += r.amount;
+            }
+
+            for (instrument_id, book) in &books {
+                let key = (instrument_id.clone(), day);
+                if let Some(mark) = self.marks.get(&key) {
+                    last_price.insert(instrument_id.clone(), *mark);
+                }
+                let qty = book.qty();
+                let flow = flows.remove(&key);
+                let prev = last_unrealized.get(instrument_id).copied().unwrap_or(0.0);
+                if flow.is_none() && qty == 0.0 && prev == 0.0 {
+                    continue;
+                }
+
+                let mut amounts = flow.unwrap_or_default();
+                let mark = last_price.get(instrument_id).copied().unwrap_or(0.0);
+                amounts.unrealized = book.unrealized(mark);
+                amounts.unrealized_change = amounts.unrealized - prev;
+                last_unrealized.insert(instrument_id.clone(), amounts.unrealized);
+
+                let currency = settle_currency(instrument_id).to_string();
+                let usd = amounts.scaled(self.rate(&currency)?);
+                rows.push(DailyPnl {
+                    date: day,
+                    instrument_id: instrument_id.clone(),
+                    currency,
+                    amounts,
+                    usd,
+                    position: qty,
+                });
+            }
+            let Some(next) = day.succ_opt() else {
+                break;
+            };
+            day = next;
+        }
+
+        Ok(PnlReport {
+            basis: self.basis,
+            rows,
+        })
+    }
+}
+
+impl PnlReport {
+    /// USD totals over all instruments, per day.
+    pub fn daily_usd(&self) -> BTreeMap<NaiveDate, PnlAmounts> {
+        let mut totals: BTreeMap<NaiveDate, PnlAmounts> = BTreeMap::new();
+        for row in &self.rows {
+            *totals.entry(row.date).or_default() += row.usd;
+        }
+        totals
+    }
+
+    pub fn write_csv<W: Write>(&self, mut out: W) -> io::Result<()> {
+        writeln!(
+            out,
+            "date,instrument_id,currency,position,realized,unrealized,unrealized_change,funding,fees,net,\
+             realized_usd,unrealized_usd,unrealized_change_usd,funding_usd,fees_usd,net_usd"
+        )?;
+        for r in &self.rows {
+            let (a, u) = (&r.amounts, &r.usd);
+            writeln!(
+                out,
+                "{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{}",
+                r.date,
+                r.instrument_id,
+                r.currency,
+                r.position,
+                a.realized,
+                a.unrealized,
+                a.unrealized_change,
+                a.funding,
+                a.fees,
+                a.net(),
+                u.realized,
+                u.unrealized,
+                u.unrealized_change,
+                u.funding,
+                u.fees,
+                u.net(),
+            )?;
+        }
+        out.flush()
+    }
+
+    /// One table per day with a USD total line.
+    pub fn to_markdown(&self) -> String {
+        let mut md = String::new();
+        let totals = self.daily_usd();
+        let mut rows = self.rows.iter().peekable();
+        for (date, total) in &totals {
+            let _ = writeln!(md, "## {}\n", date);
+            md.push_str(
+                "| instrument | ccy | position | realized | unrealized Δ | funding | fees | net | net USD |\n",
+            );
+            md.push_str("|---|---|--:|--:|--:|--:|--:|--:|--:|\n");
+            while let Some(r) = rows.next_if(|r| r.date == *date) {
+                let a = &r.amounts;
+                let _ = writeln!(
+                    md,
+                    "| {} | {} | {} | {:.4} | {:.4} | {:.4} | {:.4} | {:.4} | {:.2} |",
+                    r.instrument_id,
+                    r.currency,
+                    r.position,
+                    a.realized,
+                    a.unrealized_change,
+                    a.funding,
+                    a.fees,
+                    a.net(),
+                    r.usd.net()
+                );
+            }
+            let _ = writeln!(
+                md,
+                "| **total** | USD | | {:.2} | {:.2} | {:.2} | {:.2} | {:.2} | {:.2} |\n",
+                total.realized,
+                total.unrealized_change,
+                total.funding,
+                total.fees,
+                total.net(),
+                total.net()
+            );
+        }
+        md
+    }
+}
+
+/////////////////////////////////////////
+// fetching
+/////////////////////////////////////////
+/// Our fills on `market` in `[start_ms, end_ms)`, a day at a time.
+pub async fn fetch_trades(
+    client: &BitRestClient,
+    market: Market,
+    currency: &str,
+    start_ms: i64,
+    end_ms: i64,
+) -> BitResult<Vec<Trade>> {
+    const LIMIT: u32 = 100;
+    let mut trades: BTreeMap<String, Trade> = BTreeMap::new();
+    let mut start = start_ms;
+    while start < end_ms {
+        let end = (start + DAY_MS).min(end_ms);
+        let mut offset = 1;
+        loop {
+            let mut params = serde_json::json!({
+                "start_time": start,
+                "end_time": end,
+                "offset": offset,
+                "limit": LIMIT,
+            });
+            if market != Market::Spot {
+                params["currency"] = Value::String(currency.to_string());
+            }
+            let rsp = match market {
+                Market::Spot => client.spot_get_user_trades(&mut params).await?,
+                Market::Linear => client.linear_get_user_trades(&mut params).await?,
+                Market::Option => client.option_get_user_trades(&mut params).await?,
+            };
+            let page = parse_page::<Trade>(rsp, LIMIT)?;
+            let done = !page.has_more || page.items.is_empty();
+            for t in page.items {
+                trades.entry(t.trade_id.clone()).or_insert(t);
+            }
+            if done {
+                break;
+            }
+            offset += 1;
+        }
+        start = end;
+    }
+    Ok(trades.into_values().collect())
+}
+
+/// Fetch spot and linear fills and UM funding for the days `start..=end` and build the report.
+/// `builder` carries the basis, opening positions, marks and USD rates. Linear instruments
+/// without a mark on the last day get the current mark from the positions endpoint.
+pub async fn daily_report(
+    client: &BitRestClient,
+    mut builder: PnlReportBuilder,
+    currency: &str,
+) -> BitResult<PnlReport> {
+    let start_ms = builder
+        .start
+        .and_time(chrono::NaiveTime::MIN)
+        .and_utc()
+        .timestamp_millis();
+    let end_ms = start_ms + (builder.end - builder.start).num_days().max(0) * DAY_MS + DAY_MS;
+
+    let spot = fetch_trades(client, Market::Spot, currency, start_ms, end_ms).await?;
+    let linear = fetch_trades(client, Market::Linear, currency, start_ms, end_ms).await?;
+    let query = LedgerQuery::new(LedgerSource::Um, start_ms, end_ms).with_currency(currency);
+    let funding = ledger::fetch_ledger(client, &query).await?;
+    for p in client.positions(currency).await? {
+        let key = (p.instrument_id.clone(), builder.end);
+        if p.mark_price > 0.0 && !builder.marks.contains_key(&key) {
+            builder.marks.insert(key, p.mark_price);
+        }
+    }
+
+    builder
+        .trades(spot)
+        .trades(linear)
+        .funding(funding)
+        .finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(a: f64, b: f64) -> bool {
+        (a - b).abs() < 1e-9
+    }
+
+    #[test]
+    fn fifo_closes_oldest_lot_first() {
+        let mut book = Book::default();
+        assert_eq!(book.fill(CostBasis::Fifo, 1.0, 100.0), 0.0);
+        assert_eq!(book.fill(CostBasis::Fifo, 1.0, 110.0), 0.0);
+        assert!(close(book.fill(CostBasis::Fifo, -1.0, 120.0), 20.0));
+        assert_eq!(book.lots, VecDeque::from([(1.0, 110.0)]));
+        assert!(close(book.fill(CostBasis::Fifo, -1.0, 120.0), 10.0));
+        assert!(book.lots.is_empty());
+    }
+
+    #[test]
+    fn average_cost_realizes_against_the_mean() {
+        let mut book = Book::default();
+        book.fill(CostBasis::Average, 1.0, 100.0);
+        book.fill(CostBasis::Average, 1.0, 110.0);
+        assert!(close(book.fill(CostBasis::Average, -1.0, 120.0), 15.0));
+        assert_eq!(book.lots.len(), 1);
+        assert!(close(book.lots[0].0, 1.0));
+        assert!(close(book.lots[0].1, 105.0));
+    }
+
+    #[test]
+    fn fill_through_zero_opens_the_other_side() {
+        for basis in [CostBasis::Fifo, CostBasis::Average] {
+            let mut book = Book::default();
+            book.fill(basis, 2.0, 100.0);
+            assert!(close(book.fill(basis, -3.0, 90.0), -20.0));
+            assert_eq!(book.lots, VecDeque::from([(-1.0, 90.0)]));
+            assert!(close(book.unrealized(80.0), 10.0));
+        }
+    }
+
+    #[test]
+    fn short_is_realized_with_the_sign_flipped() {
+        let mut book = Book::default();
+        book.fill(CostBasis::Fifo, -2.0, 100.0);
+        assert!(close(book.fill(CostBasis::Fifo, 1.0, 90.0), 10.0));
+        assert!(close(book.qty(), -1.0));
+    }
+}
@@ -0,0 +1,478 @@
+// Two-sided quoting: keep a ladder of bids and asks per instrument on the book with as few
+// requests as possible.
+//
+// `QuoteManager::update` diffs the desired ladder against the live quotes. Levels already on
+// the book are kept, remaining live orders are amended onto remaining levels best-first, and
+// whatever is left over is cancelled or placed new. Amends and new orders go out through the
+// batch endpoints in chunks of `batch_size`. Cancels have no batch endpoint, the exchange
+// cancels one order id per request, so they go out one by one unless every live quote is being
+// pulled and quotes carry a label, then a single cancel by instrument and label does it. When
+// an `MmpSupervisor` reports the instrument's pair frozen, quoting halts until it thaws.
+
+use crate::error::BitResult;
+use crate::mmp::{MmpSupervisor, mmp_pair};
+use crate::models::{
+    AmendRequest, CancelRequest, Market, Order, OrderRequest, Side, settle_currency,
+};
+use crate::rate_limit::RateLimiter;
+use crate::trading::TradingApi;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct QuoteLevel {
+    pub price: f64,
+    pub qty: f64,
+}
+
+/// Desired quotes of one instrument. Order within a side does not matter.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Ladder {
+    pub bids: Vec<QuoteLevel>,
+    pub asks: Vec<QuoteLevel>,
+}
+
+impl Ladder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn bid(mut self, price: f64, qty: f64) -> Self {
+        self.bids.push(QuoteLevel { price, qty });
+        self
+    }
+
+    pub fn ask(mut self, price: f64, qty: f64) -> Self {
+        self.asks.push(QuoteLevel { price, qty });
+        self
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct QuoteConfig {
+    /// Orders per batch request, the exchange accepts at most 10.
+    pub batch_size: usize,
+    /// Price and qty differences below these leave an order alone.
+    pub price_tolerance: f64,
+    pub qty_tolerance: f64,
+    pub post_only: bool,
+    /// Set on every quote, so quotes can be told apart from other orders.
+    pub label: Option<String>,
+}
+
+impl Default for QuoteConfig {
+    fn default() -> Self {
+        Self {
+            batch_size: 10,
+            price_tolerance: 1e-9,
+            qty_tolerance: 1e-9,
+            post_only: true,
+            label: None,
+        }
+    }
+}
+
+/// Requests that turn the live quotes into the desired ladder.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct QuotePlan {
+    pub new: Vec<OrderRequest>,
+    pub amend: Vec<AmendRequest>,
+    pub cancel: Vec<String>,
+}
+
+impl QuotePlan {
+    pub fn is_empty(&self) -> bool {
+        self.new.is_empty() && self.amend.is_empty() && self.cancel.is_empty()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum QuoteOutcome {
+    Applied(QuotePlan),
+    /// MMP is frozen for the instrument's pair, nothing was sent.
+    Halted,
+}
+
+fn diff_side(
+    instrument_id: &str,
+    side: Side,
+    desired: &[QuoteLevel],
+    live: &[&Order],
+    config: &QuoteConfig,
+    plan: &mut QuotePlan,
+) {
+    let same_price = |a: f64, b: f64| (a - b).abs() <= config.price_tolerance;
+    let same_qty = |a: f64, b: f64| (a - b).abs() <= config.qty_tolerance;
+
+    // a live order already at a desired price stays, amended if only its size is off
+    let mut desired_left: Vec<QuoteLevel> = vec![];
+    let mut live_left: Vec<&Order> = live.to_vec();
+    for level in desired {
+        let Some(i) = live_left
+            .iter()
+            .position(|o| same_price(o.price, level.price))
+        else {
+            desired_left.push(*level);
+            continue;
+        };
+        let order = live_left.swap_remove(i);
+        if !same_qty(order.remaining_qty(), level.qty) {
+            plan.amend.push(AmendRequest {
+                instrument_id: instrument_id.to_string(),
+                order_id: Some(order.order_id.clone()),
+                qty: Some(order.filled_qty + level.qty),
+                ..Default::default()
+            });
+        }
+    }
+
+    // pair the rest best price first, so the top of the book moves before the tail
+    let better = |a: f64, b: f64| match side {
+        Side::Buy => b.total_cmp(&a),
+        Side::Sell => a.total_cmp(&b),
+    };
+    desired_left.sort_by(|a, b| better(a.price, b.price));
+    live_left.sort_by(|a, b| better(a.price, b.price));
+    for (i, level) in desired_left.iter().enumerate() {
+        match live_left.get(i) {
+            Some(order) => plan.amend.push(AmendRequest {
+                instrument_id: instrument_id.to_string(),
+                order_id: Some(order.order_id.clone()),
+                price: Some(level.price),
+                qty: (!same_qty(order.remaining_qty(), level.qty))
+                    .then_some(order.filled_qty + level.qty),
+                ..Default::default()
+            }),
+            None => {
+                let mut req = OrderRequest::limit(instrument_id, side, level.qty, level.price);
+                req.post_only = config.post_only;
+                req.label = config.label.clone();
+                plan.new.push(req);
+            }
+        }
+    }
+    for order in live_left.iter().skip(desired_left.len()) {
+        plan.cancel.push(order.order_id.clone());
+    }
+}
+
+/// The requests that turn `live` (open orders of `instrument_id`) into `ladder`.
+pub fn diff(
+    instrument_id: &str,
+    ladder: &Ladder,
+    live: &[Order],
+    config: &QuoteConfig,
+) -> QuotePlan {
+    let mut plan = QuotePlan::default();
+    for (side, desired) in [(Side::Buy, &ladder.bids), (Side::Sell, &ladder.asks)] {
+        let live: Vec<&Order> = live
+            .iter()
+            .filter(|o| o.instrument_id == instrument_id && o.side == side)
+            .collect();
+        diff_side(instrument_id, side, desired, &live, config, &mut plan);
+    }
+    plan
+}
+
+pub struct QuoteManager<T> {
+    api: Arc<T>,
+    market: Market,
+    config: QuoteConfig,
+    rate_limiter: Option<Arc<RateLimiter>>,
+    mmp: Option<Arc<MmpSupervisor>>,
+    /// Live quotes per instrument, no entry until the instrument is synced or first updated.
+    live: Mutex<HashMap<String, Vec<Order>>>,
+    /// Instruments whose live quotes are unknown after a failed request.
+    stale: Mutex<HashSet<String>>,
+}
+
+impl<T: TradingApi> QuoteManager<T> {
+    pub fn new(api: Arc<T>, market: Market, config: QuoteConfig) -> Self {
+        Self {
+            api,
+            market,
+            config,
+            rate_limiter: None,
+            mmp: None,
+            live: Mutex::new(HashMap::new()),
+            stale: Mutex::new(HashSet::new()),
+        }
+    }
+
+    /// Every request waits for a token from `limiter`, on top of any limiter on the client.
+    pub fn with_rate_limiter(mut self, limiter: Arc<RateLimiter>) -> Self {
+        self.rate_limiter = Some(limiter);
+        self
+    }
+
+    /// Halt quoting while `mmp` reports the instrument's pair frozen.
+    pub fn with_mmp(mut self, mmp: Arc<MmpSupervisor>) -> Self {
+        self.mmp = Some(mmp);
+        self
+    }
+
+    /// Live quotes of an instrument as last seen.
+    pub fn live_orders(&self, instrument_id: &str) -> Vec<Order> {
+        self.live
+            .lock()
+            .unwrap()
+            .get(instrument_id)
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    /// Feed order updates from the private WebSocket so fills and exchange-side cancels are
+    /// seen before the next `update`.
+    pub fn on_order_update(&self, order: &Order) {
+        let mut live = self.live.lock().unwrap();
+        let Some(orders) = live.get_mut(&order.instrument_id) else {
+            return;
+        };
+        let Some(i) = orders.iter().position(|o| o.order_id == order.order_id) else {
+            return;
+        };
+        if order.is_open() {
+            orders[i] = order.clone();
+        } else {
+            orders.swap_remove(i);
+        }
+    }
+
+    /// Reload the live quotes of an instrument from the exchange. Only orders with our label
+    /// count as quotes when one is configured.
+    pub async fn sync(&self, instrument_id: &str) -> BitResult<()> {
+        self.throttle().await;
+        let currency = settle_currency(instrument_id);
+        let orders = self
+            .api
+            .open_orders(self.market, currency, Some(instrument_id))
+            .await?;
+        let quotes = orders
+            .into_iter()
+            .filter(|o| self.config.label.as_ref().is_none_or(|l| &o.label == l))
+            .collect();
+        self.live
+            .lock()
+            .unwrap()
+            .insert(instrument_id.to_string(), quotes);
+        self.stale.lock().unwrap().remove(instrument_id);
+        Ok(())
+    }
+
+    fn is_frozen(&self, instrument_id: &str) -> bool {
+        self.mmp
+            .as_ref()
+            .is_some_and(|mmp| mmp.is_frozen(&mmp_pair(self.market, instrument_id)))
+    }
+
+    async fn throttle(&self) {
+        if let Some(limiter) = &self.rate_limiter {
+            limiter.acquire().await;
+        }
+    }
+
+    /// Move the live quotes of `instrument_id` to `ladder`. The first update of an instrument
+    /// syncs it first, so quotes left from an earlier run are reused or cancelled.
+    pub async fn update(&self, instrument_id: &str, ladder: &Ladder) -> BitResult<QuoteOutcome> {
+        if self.is_frozen(instrument_id) {
+            // a freeze cancels our quotes on the exchange side
+            self.live.lock().unwrap().remove(instrument_id);
+            return Ok(QuoteOutcome::Halted);
+        }
+        let stale = self.stale.lock().unwrap().contains(instrument_id)
+            || !self.live.lock().unwrap().contains_key(instrument_id);
+        if stale {
+            self.sync(instrument_id).await?;
+        }
+
+        let plan = diff(
+            instrument_id,
+            ladder,
+            &self.live_orders(instrument_id),
+            &self.config,
+        );
+        if let Err(e) = self.apply(instrument_id, &plan).await {
+            self.stale.lock().unwrap().insert(instrument_id.to_string());
+            return Err(e);
+        }
+        Ok(QuoteOutcome::Applied(plan))
+    }
+
+    /// Pull every quote of an instrument.
+    pub async fn cancel_all(&self, instrument_id: &str) -> BitResult<()> {
+        let plan = QuotePlan {
+            cancel: self
+                .live_orders(instrument_id)
+                .into_iter()
+                .map(|o| o.order_id)
+                .collect(),
+            ..Default::default()
+        };
+        self.apply(instrument_id, &plan).await
+    }
+
+    // cancels first to free margin, then amends, then new orders
+    async fn apply(&self, instrument_id: &str, plan: &QuotePlan) -> BitResult<()> {
+        match &self.config.label {
+            Some(label)
+                if plan.cancel.len() > 1 && self.cancels_all(instrument_id, &plan.cancel) =>
+            {
+                self.throttle().await;
+                let req = CancelRequest {
+                    currency: settle_currency(instrument_id).to_string(),
+                    instrument_id: Some(instrument_id.to_string()),
+                    order_id: None,
+                    label: Some(label.clone()),
+                };
+                self.api.cancel_order(self.market, &req).await?;
+                self.live
+                    .lock()
+                    .unwrap()
+                    .insert(instrument_id.to_string(), vec![]);
+            }
+            _ => {
+                for order_id in &plan.cancel {
+                    self.throttle().await;
+                    let req = CancelRequest::by_order_id(instrument_id, order_id);
+                    self.api.cancel_order(self.market, &req).await?;
+                    self.remove(instrument_id, order_id);
+                }
+            }
+        }
+
+        let batch_size = self.config.batch_size.max(1);
+        for chunk in plan.amend.chunks(batch_size) {
+            self.throttle().await;
+            let orders = self.api.batch_amend_orders(self.market, chunk).await?;
+            self.store(instrument_id, orders);
+        }
+        for chunk in plan.new.chunks(batch_size) {
+            self.throttle().await;
+            let orders = self.api.batch_new_orders(self.market, chunk).await?;
+            self.store(instrument_id, orders);
+        }
+        Ok(())
+    }
+
+    // whether `order_ids` are all of the live quotes of the instrument
+    fn cancels_all(&self, instrument_id: &str, order_ids: &[String]) -> bool {
+        let live = self.live.lock().unwrap();
+        let quotes = live.get(instrument_id).map_or(&[][..], Vec::as_slice);
+        quotes.len() == order_ids.len() && quotes.iter().all(|o| order_ids.contains(&o.order_id))
+    }
+
+    fn remove(&self, instrument_id: &str, order_id: &str) {
+        if let Some(orders) = self.live.lock().unwrap().get_mut(instrument_id) {
+            orders.retain(|o| o.order_id != order_id);
+        }
+    }
+
+    fn store(&self, instrument_id: &str, updated: Vec<Order>) {
+        let mut live = self.live.lock().unwrap();
+        let orders = live.entry(instrument_id.to_string()).or_default();
+        for order in updated {
+            orders.retain(|o| o.order_id != order.order_id);
+            // batch responses may leave out the status of an accepted order
+            if order.is_open() || order.status.is_empty() {
+                orders.push(order);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::OrderType;
+
+    const INST: &str = "BTC-USD-PERPETUAL";
+
+    fn order(id: &str, side: Side, price: f64, qty: f64, filled_qty: f64) -> Order {
+        Order {
+            order_id: id.to_string(),
+            instrument_id: INST.to_string(),
+            side,
+            price,
+            qty,
+            filled_qty,
+            avg_price: 0.0,
+            status: "open".to_string(),
+            order_type: OrderType::Limit,
+            label: String::new(),
+            created_at: 0,
+            updated_at: 0,
+        }
+    }
+
+    #[test]
+    fn matching_ladder_needs_no_requests() {
+        let ladder = Ladder::new().bid(99.0, 1.0).ask(101.0, 1.0);
+        let live = [
+            order("1", Side::Buy, 99.0, 1.0, 0.0),
+            order("2", Side::Sell, 101.0, 1.0, 0.0),
+        ];
+        assert!(diff(INST, &ladder, &live, &QuoteConfig::default()).is_empty());
+    }
+
+    #[test]
+    fn size_change_amends_total_qty_including_fills() {
+        let ladder = Ladder::new().bid(99.0, 2.0);
+        let live = [order("1", Side::Buy, 99.0, 1.0, 0.5)];
+        let plan = diff(INST, &ladder, &live, &QuoteConfig::default());
+        assert!(plan.new.is_empty() && plan.cancel.is_empty());
+        assert_eq!(plan.amend.len(), 1);
+        assert_eq!(plan.amend[0].order_id.as_deref(), Some("1"));
+        assert_eq!(plan.amend[0].price, None);
+        assert_eq!(plan.amend[0].qty, Some(2.5));
+    }
+
+    #[test]
+    fn moved_levels_amend_best_first_and_place_the_rest() {
+        let ladder = Ladder::new().bid(97.0, 1.0).bid(98.0, 1.0).bid(96.0, 1.0);
+        let live = [
+            order("a", Side::Buy, 95.0, 1.0, 0.0),
+            order("b", Side::Buy, 94.0, 1.0, 0.0),
+        ];
+        let plan = diff(INST, &ladder, &live, &QuoteConfig::default());
+        let amended: Vec<_> = plan
+            .amend
+            .iter()
+            .map(|a| (a.order_id.as_deref().unwrap(), a.price.unwrap(), a.qty))
+            .collect();
+        assert_eq!(amended, [("a", 98.0, None), ("b", 97.0, None)]);
+        assert_eq!(plan.new.len(), 1);
+        assert_eq!(plan.new[0].price, Some(96.0));
+        assert!(plan.new[0].post_only);
+        assert!(plan.cancel.is_empty());
+    }
+
+    #[test]
+    fn surplus_orders_are_cancelled_and_other_instruments_ignored() {
+        let ladder = Ladder::new().ask(101.0, 1.0);
+        let mut other = order("x", Side::Sell, 150.0, 1.0, 0.0);
+        other.instrument_id = "ETH-USD-PERPETUAL".to_string();
+        let live = [
+            order("1", Side::Sell, 101.0, 1.0, 0.0),
+            order("2", Side::Sell, 102.0, 1.0, 0.0),
+            order("3", Side::Buy, 99.0, 1.0, 0.0),
+            other,
+        ];
+        let mut plan = diff(INST, &ladder, &live, &QuoteConfig::default());
+        plan.cancel.sort();
+        assert!(plan.new.is_empty() && plan.amend.is_empty());
+        assert_eq!(plan.cancel, ["2", "3"]);
+    }
+
+    #[test]
+    fn differences_within_tolerance_are_ignored() {
+        let config = QuoteConfig {
+            price_tolerance: 0.01,
+            qty_tolerance: 0.01,
+            ..Default::default()
+        };
+        let ladder = Ladder::new().bid(99.005, 1.005);
+        let live = [order("1", Side::Buy, 99.0, 1.0, 0.0)];
+        assert!(diff(INST, &ladder, &live, &config).is_empty());
+    }
+}
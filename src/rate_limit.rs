@@ -0,0 +1,85 @@
+// Token bucket rate limiter for outgoing REST requests.
+
+use crate::error::{BitError, BitResult};
+use crate::metrics;
+use serde::{Deserialize, Serialize};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct RateLimitConfig {
+    /// Sustained requests per second.
+    pub rate_per_sec: f64,
+    /// Requests that may be sent back to back after an idle period.
+    pub burst: f64,
+}
+
+impl RateLimitConfig {
+    /// A rate that is not positive would never refill, a burst below one never lets a
+    /// request through.
+    pub fn validate(&self) -> BitResult<()> {
+        if !(self.rate_per_sec.is_finite() && self.rate_per_sec > 0.0) {
+            return Err(BitError::Config(format!(
+                "rate_per_sec must be positive, got {}",
+                self.rate_per_sec
+            )));
+        }
+        if !(self.burst.is_finite() && self.burst >= 1.0) {
+            return Err(BitError::Config(format!(
+                "burst must be at least 1, got {}",
+                self.burst
+            )));
+        }
+        Ok(())
+    }
+}
+
+struct Bucket {
+    tokens: f64,
+    last_refill: Instant,
+}
+
+pub struct RateLimiter {
+    config: RateLimitConfig,
+    bucket: Mutex<Bucket>,
+}
+
+impl RateLimiter {
+    pub fn new(config: RateLimitConfig) -> BitResult<Self> {
+        config.validate()?;
+        Ok(Self {
+            config,
+            bucket: Mutex::new(Bucket {
+                tokens: config.burst,
+                last_refill: Instant::now(),
+            }),
+        })
+    }
+
+    pub fn config(&self) -> RateLimitConfig {
+        self.config
+    }
+
+    /// Take a token, waiting until one is available. Returns how long we waited.
+    pub async fn acquire(&self) -> Duration {
+        let start = Instant::now();
+        loop {
+            let wait = {
+                let mut bucket = self.bucket.lock().unwrap();
+                let now = Instant::now();
+                let refill =
+                    now.duration_since(bucket.last_refill).as_secs_f64() * self.config.rate_per_sec;
+                bucket.tokens = (bucket.tokens + refill).min(self.config.burst);
+                bucket.last_refill = now;
+                if bucket.tokens >= 1.0 {
+                    bucket.tokens -= 1.0;
+                    let waited = start.elapsed();
+                    metrics::rate_limit_wait(waited);
+                    return waited;
+                }
+                Duration::from_secs_f64((1.0 - bucket.tokens) / self.config.rate_per_sec)
+            };
+            tokio::time::sleep(wait).await;
+        }
+    }
+}
@@ -0,0 +1,84 @@
+// On-disk format for recorded WebSocket traffic: one JSON object per line holding the local
+// receive time and the raw message as it came off the wire. Files may be gzip compressed.
+// Missing data is marked with frames on the pseudo channel "gap".
+
+use crate::bit_ws_client::WsEvent;
+use crate::error::{BitError, BitResult};
+use flate2::read::MultiGzDecoder;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Read};
+use std::path::Path;
+
+pub const GAP_CHANNEL: &str = "gap";
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RecordedFrame {
+    /// Local wall clock time the frame was received, in milliseconds.
+    pub recv_ms: i64,
+    /// The raw `{"channel": ..., "data": ...}` message.
+    pub message: Value,
+}
+
+impl RecordedFrame {
+    pub fn event(&self) -> Option<WsEvent> {
+        WsEvent::parse(&self.message)
+    }
+
+    /// Data between `from_ms` and `to_ms` is missing, for every instrument or just one.
+    pub fn gap(from_ms: i64, to_ms: i64, reason: &str, instrument_id: Option<&str>) -> Self {
+        Self {
+            recv_ms: to_ms,
+            message: serde_json::json!({
+                "channel": GAP_CHANNEL,
+                "data": {
+                    "from_ms": from_ms,
+                    "to_ms": to_ms,
+                    "reason": reason,
+                    "instrument_id": instrument_id,
+                },
+            }),
+        }
+    }
+
+    pub fn channel(&self) -> &str {
+        self.message["channel"].as_str().unwrap_or("")
+    }
+
+    pub fn is_gap(&self) -> bool {
+        self.channel() == GAP_CHANNEL
+    }
+}
+
+/// Read a `.jsonl` or `.jsonl.gz` recording. Blank lines are skipped, malformed lines are an
+/// error.
+pub fn read_jsonl(path: impl AsRef<Path>) -> BitResult<Vec<RecordedFrame>> {
+    let path = path.as_ref();
+    let file =
+        File::open(path).map_err(|e| BitError::Config(format!("{}: {}", path.display(), e)))?;
+    let reader: Box<dyn Read> = if path.extension().is_some_and(|e| e == "gz") {
+        Box::new(MultiGzDecoder::new(file))
+    } else {
+        Box::new(file)
+    };
+    let mut frames = Vec::new();
+    for (n, line) in BufReader::new(reader).lines().enumerate() {
+        let line = line.map_err(|e| BitError::Decode(format!("{}: {}", path.display(), e)))?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let frame = serde_json::from_str(&line)
+            .map_err(|e| BitError::Decode(format!("{}:{}: {}", path.display(), n + 1, e)))?;
+        frames.push(frame);
+    }
+    Ok(frames)
+}
+
+/// Merge several recordings into a single stream ordered by receive time.
+/// Frames with equal timestamps keep the order of `recordings`.
+pub fn merge(recordings: Vec<Vec<RecordedFrame>>) -> Vec<RecordedFrame> {
+    let mut frames: Vec<RecordedFrame> = recordings.into_iter().flatten().collect();
+    frames.sort_by_key(|f| f.recv_ms);
+    frames
+}
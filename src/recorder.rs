@@ -0,0 +1,405 @@
+// Market data recorder: writes raw public WebSocket frames to rolling hourly files, as gzip
+// compressed JSONL and optionally Parquet (cargo feature "parquet"). Disconnects and depth
+// sequence breaks are written as explicit gap frames so consumers know data is missing.
+
+use crate::bit_ws_client::{BitWsClient, RawWsFrame, WsSubscription};
+use crate::error::{BitError, BitResult};
+use crate::record::RecordedFrame;
+use chrono::{DateTime, Utc};
+use flate2::Compression;
+use flate2::write::GzEncoder;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+const HOUR_MS: i64 = 3_600_000;
+const FLUSH_INTERVAL_MS: i64 = 5_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OutputFormat {
+    JsonlGz,
+    Parquet,
+}
+
+/// ```toml
+/// ws_url = "wss://ws.bit.com"
+/// output_dir = "data"
+/// formats = ["jsonl_gz", "parquet"]
+///
+/// [[subscriptions]]
+/// channels = ["ticker", "depth", "trade"]
+/// instruments = ["BTC-USDT-PERPETUAL", "ETH-USDT-PERPETUAL"]
+///
+/// [[subscriptions]]
+/// channels = ["index_price"]
+/// pairs = ["BTC-USDT"]
+/// ```
+#[derive(Debug, Clone, Deserialize)]
+pub struct RecorderConfig {
+    pub ws_url: String,
+    pub output_dir: PathBuf,
+    #[serde(default = "default_formats")]
+    pub formats: Vec<OutputFormat>,
+    pub subscriptions: Vec<RecorderSubscription>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct RecorderSubscription {
+    pub channels: Vec<String>,
+    #[serde(default)]
+    pub instruments: Vec<String>,
+    #[serde(default)]
+    pub currencies: Vec<String>,
+    #[serde(default)]
+    pub pairs: Vec<String>,
+    #[serde(default = "default_interval")]
+    pub interval: String,
+}
+
+fn default_formats() -> Vec<OutputFormat> {
+    vec![OutputFormat::JsonlGz]
+}
+
+fn default_interval() -> String {
+    "raw".to_string()
+}
+
+impl RecorderConfig {
+    pub fn load(path: impl AsRef<Path>) -> BitResult<Self> {
+        let path = path.as_ref();
+        let text = fs::read_to_string(path)
+            .map_err(|e| BitError::Config(format!("{}: {}", path.display(), e)))?;
+        toml::from_str(&text).map_err(|e| BitError::Config(e.to_string()))
+    }
+
+    pub fn ws_client(&self) -> BitWsClient {
+        self.subscriptions
+            .iter()
+            .fold(BitWsClient::new(&self.ws_url), |client, sub| {
+                client.subscribe(WsSubscription {
+                    channels: sub.channels.clone(),
+                    instruments: sub.instruments.clone(),
+                    currencies: sub.currencies.clone(),
+                    pairs: sub.pairs.clone(),
+                    interval: sub.interval.clone(),
+                    private: false,
+                })
+            })
+    }
+}
+
+/////////////////////////////////////////
+// file sinks
+/////////////////////////////////////////
+trait FrameSink {
+    fn write(&mut self, frame: &RecordedFrame) -> io::Result<()>;
+    fn flush(&mut self) -> io::Result<()>;
+    fn close(self: Box<Self>) -> io::Result<()>;
+}
+
+struct JsonlGzSink {
+    encoder: GzEncoder<BufWriter<File>>,
+}
+
+impl JsonlGzSink {
+    fn create(path: &Path) -> io::Result<Self> {
+        // append: a restart within the same hour adds a new gzip member to the file
+        let file = fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)?;
+        Ok(Self {
+            encoder: GzEncoder::new(BufWriter::new(file), Compression::default()),
+        })
+    }
+}
+
+impl FrameSink for JsonlGzSink {
+    fn write(&mut self, frame: &RecordedFrame) -> io::Result<()> {
+        serde_json::to_writer(&mut self.encoder, frame)?;
+        self.encoder.write_all(b"\n")
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.encoder.flush()
+    }
+
+    fn close(self: Box<Self>) -> io::Result<()> {
+        self.encoder.finish()?.flush()
+    }
+}
+
+#[cfg(feature = "parquet")]
+mod parquet_sink {
+    use super::FrameSink;
+    use crate::record::RecordedFrame;
+    use arrow_array::{ArrayRef, Int64Array, RecordBatch, StringArray};
+    use arrow_schema::{DataType, Field, Schema};
+    use parquet::arrow::ArrowWriter;
+    use parquet::basic::Compression;
+    use parquet::file::properties::WriterProperties;
+    use std::fs::File;
+    use std::io;
+    use std::path::Path;
+    use std::sync::Arc;
+
+    const BATCH_ROWS: usize = 10_000;
+
+    pub(super) struct ParquetSink {
+        writer: ArrowWriter<File>,
+        schema: Arc<Schema>,
+        recv_ms: Vec<i64>,
+        channel: Vec<String>,
+        instrument_id: Vec<Option<String>>,
+        exchange_ms: Vec<Option<i64>>,
+        payload: Vec<String>,
+    }
+
+    impl ParquetSink {
+        pub(super) fn create(path: &Path) -> io::Result<Self> {
+            let schema = Arc::new(Schema::new(vec![
+                Field::new("recv_ms", DataType::Int64, false),
+                Field::new("channel", DataType::Utf8, false),
+                Field::new("instrument_id", DataType::Utf8, true),
+                Field::new("exchange_ms", DataType::Int64, true),
+                Field::new("payload", DataType::Utf8, false),
+            ]));
+            let props = WriterProperties::builder()
+                .set_compression(Compression::SNAPPY)
+                .build();
+            // parquet files cannot be appended to, a restart within the hour gets a new file
+            let path = unique_path(path);
+            let writer = ArrowWriter::try_new(File::create(path)?, schema.clone(), Some(props))
+                .map_err(io::Error::other)?;
+            Ok(Self {
+                writer,
+                schema,
+                recv_ms: Vec::new(),
+                channel: Vec::new(),
+                instrument_id: Vec::new(),
+                exchange_ms: Vec::new(),
+                payload: Vec::new(),
+            })
+        }
+
+        fn write_batch(&mut self) -> io::Result<()> {
+            if self.recv_ms.is_empty() {
+                return Ok(());
+            }
+            let columns: Vec<ArrayRef> = vec![
+                Arc::new(Int64Array::from(std::mem::take(&mut self.recv_ms))),
+                Arc::new(StringArray::from(std::mem::take(&mut self.channel))),
+                Arc::new(StringArray::from(std::mem::take(&mut self.instrument_id))),
+                Arc::new(Int64Array::from(std::mem::take(&mut self.exchange_ms))),
+                Arc::new(StringArray::from(std::mem::take(&mut self.payload))),
+            ];
+            let batch =
+                RecordBatch::try_new(self.schema.clone(), columns).map_err(io::Error::other)?;
+            self.writer.write(&batch).map_err(io::Error::other)
+        }
+    }
+
+    fn unique_path(path: &Path) -> std::path::PathBuf {
+        let mut candidate = path.to_path_buf();
+        let mut n = 1;
+        while candidate.exists() {
+            candidate = path.with_extension(format!("{}.parquet", n));
+            n += 1;
+        }
+        candidate
+    }
+
+    impl FrameSink for ParquetSink {
+        fn write(&mut self, frame: &RecordedFrame) -> io::Result<()> {
+            let data = &frame.message["data"];
+            // list payloads (trades) carry the instrument on each element
+            let first = data.get(0).unwrap_or(data);
+            self.recv_ms.push(frame.recv_ms);
+            self.channel.push(frame.channel().to_string());
+            self.instrument_id.push(
+                first["instrument_id"]
+                    .as_str()
+                    .or(first["pair"].as_str())
+                    .map(str::to_string),
+            );
+            self.exchange_ms.push(frame.message["timestamp"].as_i64());
+            self.payload.push(data.to_string());
+            if self.recv_ms.len() >= BATCH_ROWS {
+                self.write_batch()?;
+            }
+            Ok(())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            // row groups are written per full batch and on close, small row groups written
+            // every few seconds would bloat the file and slow down reads
+            Ok(())
+        }
+
+        fn close(mut self: Box<Self>) -> io::Result<()> {
+            self.write_batch()?;
+            self.writer.close().map_err(io::Error::other)?;
+            Ok(())
+        }
+    }
+}
+
+/// Writes frames to `{output_dir}/{YYYYMMDD}/{YYYYMMDD-HH}.{ext}`, rolling every hour.
+struct RollingWriter {
+    output_dir: PathBuf,
+    format: OutputFormat,
+    hour: Option<i64>,
+    sink: Option<Box<dyn FrameSink>>,
+}
+
+impl RollingWriter {
+    fn write(&mut self, frame: &RecordedFrame) -> io::Result<()> {
+        let hour = frame.recv_ms.div_euclid(HOUR_MS);
+        if self.hour != Some(hour) {
+            self.close()?;
+            self.sink = Some(self.open(hour)?);
+            self.hour = Some(hour);
+        }
+        self.sink.as_mut().unwrap().write(frame)
+    }
+
+    fn open(&self, hour: i64) -> io::Result<Box<dyn FrameSink>> {
+        let start = DateTime::<Utc>::from_timestamp_millis(hour * HOUR_MS).unwrap_or_default();
+        let dir = self.output_dir.join(start.format("%Y%m%d").to_string());
+        fs::create_dir_all(&dir)?;
+        let stem = start.format("%Y%m%d-%H").to_string();
+        match self.format {
+            OutputFormat::JsonlGz => Ok(Box::new(JsonlGzSink::create(
+                &dir.join(format!("{}.jsonl.gz", stem)),
+            )?)),
+            #[cfg(feature = "parquet")]
+            OutputFormat::Parquet => Ok(Box::new(parquet_sink::ParquetSink::create(
+                &dir.join(format!("{}.parquet", stem)),
+            )?)),
+            #[cfg(not(feature = "parquet"))]
+            OutputFormat::Parquet => Err(io::Error::other(
+                "parquet output requires the \"parquet\" cargo feature",
+            )),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self.sink.as_mut() {
+            Some(sink) => sink.flush(),
+            None => Ok(()),
+        }
+    }
+
+    fn close(&mut self) -> io::Result<()> {
+        self.hour = None;
+        match self.sink.take() {
+            Some(sink) => sink.close(),
+            None => Ok(()),
+        }
+    }
+}
+
+/////////////////////////////////////////
+// recorder
+/////////////////////////////////////////
+pub struct Recorder {
+    writers: Vec<RollingWriter>,
+    last_recv_ms: Option<i64>,
+    last_flush_ms: i64,
+    disconnected: Option<(i64, String)>,
+    depth_sequences: HashMap<String, i64>,
+}
+
+impl Recorder {
+    pub fn new(output_dir: &Path, formats: &[OutputFormat]) -> Self {
+        Self {
+            writers: formats
+                .iter()
+                .map(|format| RollingWriter {
+                    output_dir: output_dir.to_path_buf(),
+                    format: *format,
+                    hour: None,
+                    sink: None,
+                })
+                .collect(),
+            last_recv_ms: None,
+            last_flush_ms: 0,
+            disconnected: None,
+            depth_sequences: HashMap::new(),
+        }
+    }
+
+    pub fn handle(&mut self, frame: RawWsFrame) -> io::Result<()> {
+        match frame {
+            RawWsFrame::Message(frame) => {
+                if let Some(gap) = self.check_sequence(&frame) {
+                    self.write(&gap)?;
+                }
+                self.last_recv_ms = Some(frame.recv_ms);
+                self.write(&frame)?;
+                if frame.recv_ms - self.last_flush_ms >= FLUSH_INTERVAL_MS {
+                    self.last_flush_ms = frame.recv_ms;
+                    for writer in &mut self.writers {
+                        writer.flush()?;
+                    }
+                }
+                Ok(())
+            }
+            RawWsFrame::Disconnected { at_ms, reason } => {
+                // keep the first disconnect if reconnect attempts keep failing
+                self.disconnected.get_or_insert((at_ms, reason));
+                Ok(())
+            }
+            RawWsFrame::Connected { at_ms } => {
+                // depth restarts with a snapshot after resubscribing
+                self.depth_sequences.clear();
+                match (self.disconnected.take(), self.last_recv_ms) {
+                    (Some((_, reason)), Some(from_ms)) => {
+                        self.write(&RecordedFrame::gap(from_ms, at_ms, &reason, None))
+                    }
+                    _ => Ok(()),
+                }
+            }
+        }
+    }
+
+    pub fn close(&mut self) -> io::Result<()> {
+        for writer in &mut self.writers {
+            writer.close()?;
+        }
+        Ok(())
+    }
+
+    fn write(&mut self, frame: &RecordedFrame) -> io::Result<()> {
+        for writer in &mut self.writers {
+            writer.write(frame)?;
+        }
+        Ok(())
+    }
+
+    /// Depth updates carry `prev_sequence`; a mismatch means updates were dropped.
+    fn check_sequence(&mut self, frame: &RecordedFrame) -> Option<RecordedFrame> {
+        if !frame.channel().starts_with("depth") {
+            return None;
+        }
+        let data = &frame.message["data"];
+        let instrument_id = data["instrument_id"].as_str()?;
+        let sequence = data["sequence"].as_i64()?;
+        let last = self
+            .depth_sequences
+            .insert(instrument_id.to_string(), sequence);
+        let prev = data["prev_sequence"].as_i64();
+        match (last, prev, data["type"].as_str()) {
+            (Some(last), Some(prev), Some("update")) if prev != last => Some(RecordedFrame::gap(
+                self.last_recv_ms.unwrap_or(frame.recv_ms),
+                frame.recv_ms,
+                &format!("depth sequence {} after {}", prev, last),
+                Some(instrument_id),
+            )),
+            _ => None,
+        }
+    }
+}
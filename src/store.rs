@@ -0,0 +1,856 @@
+// Embedded SQLite store of everything that happened to our orders.
+//
+// `OrderStore` keeps an append-only `events` table (every request, ack, reject, cancel and
+// fill, as the audit trail) next to the latest state of each order, every fill and the
+// WebSocket subscriptions in use. `RecordingApi` wraps a `TradingApi` and writes to the store
+// around each call; private WebSocket events go in through `on_ws_event`. After a restart,
+// `open_orders` and `net_positions` give back the last known state and `resync` fetches what
+// changed while we were away.
+
+use crate::bit_ws_client::{WsEvent, WsSubscription};
+use crate::error::{BitError, BitResult};
+use crate::models::{
+    AmendRequest, CancelRequest, Market, Order, OrderRequest, Position, Side, Trade, UmAccount,
+    settle_currency,
+};
+use crate::trading::TradingApi;
+use crate::util::now_ms;
+use rusqlite::{Connection, OptionalExtension, params};
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+use std::collections::{BTreeMap, HashSet};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS events (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    ts INTEGER NOT NULL,
+    kind TEXT NOT NULL,
+    market TEXT NOT NULL,
+    instrument_id TEXT NOT NULL,
+    order_id TEXT NOT NULL,
+    label TEXT NOT NULL,
+    payload TEXT NOT NULL
+);
+CREATE INDEX IF NOT EXISTS events_order_id ON events(order_id);
+CREATE INDEX IF NOT EXISTS events_label ON events(label);
+CREATE TABLE IF NOT EXISTS orders (
+    order_id TEXT PRIMARY KEY,
+    market TEXT NOT NULL,
+    instrument_id TEXT NOT NULL,
+    label TEXT NOT NULL,
+    status TEXT NOT NULL,
+    updated_at INTEGER NOT NULL,
+    payload TEXT NOT NULL
+);
+CREATE INDEX IF NOT EXISTS orders_label ON orders(label);
+CREATE TABLE IF NOT EXISTS fills (
+    trade_id TEXT PRIMARY KEY,
+    order_id TEXT NOT NULL,
+    market TEXT NOT NULL,
+    instrument_id TEXT NOT NULL,
+    side TEXT NOT NULL,
+    qty REAL NOT NULL,
+    label TEXT NOT NULL,
+    created_at INTEGER NOT NULL,
+    payload TEXT NOT NULL
+);
+CREATE INDEX IF NOT EXISTS fills_created_at ON fills(created_at);
+CREATE TABLE IF NOT EXISTS subscriptions (
+    name TEXT PRIMARY KEY,
+    payload TEXT NOT NULL
+);
+";
+
+fn db_err(e: rusqlite::Error) -> BitError {
+    BitError::Config(format!("sqlite: {}", e))
+}
+
+fn market_name(market: Market) -> &'static str {
+    match market {
+        Market::Spot => "spot",
+        Market::Linear => "linear",
+        Market::Option => "option",
+    }
+}
+
+fn decode_rows<T: DeserializeOwned>(payloads: Vec<String>) -> BitResult<Vec<T>> {
+    payloads
+        .iter()
+        .map(|p| Ok(serde_json::from_str(p)?))
+        .collect()
+}
+
+/// One row of the audit trail.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct AuditEntry {
+    pub id: i64,
+    pub ts: i64,
+    /// `new_request`, `ack`, `reject`, `amend_request`, `amend_ack`, `amend_reject`,
+    /// `cancel_request`, `cancelled`, `cancel_reject`, `order_update` or `fill`.
+    pub kind: String,
+    pub market: String,
+    pub instrument_id: String,
+    pub order_id: String,
+    pub label: String,
+    pub payload: Value,
+}
+
+/// What `resync` changed.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ResyncReport {
+    /// Fills the store had not seen.
+    pub new_fills: Vec<Trade>,
+    /// Orders open on the exchange.
+    pub open_orders: Vec<Order>,
+    /// Orders the store had open that are gone from the exchange, by order id.
+    pub closed: Vec<String>,
+}
+
+pub struct OrderStore {
+    conn: Mutex<Connection>,
+}
+
+impl OrderStore {
+    /// Open or create the database at `path`.
+    pub fn open(path: impl AsRef<Path>) -> BitResult<Self> {
+        let conn = Connection::open(path).map_err(db_err)?;
+        Self::init(conn)
+    }
+
+    /// A store that lives as long as the process, for tests and paper trading.
+    pub fn in_memory() -> BitResult<Self> {
+        Self::init(Connection::open_in_memory().map_err(db_err)?)
+    }
+
+    fn init(conn: Connection) -> BitResult<Self> {
+        // WAL keeps readers from blocking the writer, a no-op for in-memory databases
+        conn.pragma_update(None, "journal_mode", "WAL")
+            .map_err(db_err)?;
+        conn.execute_batch(SCHEMA).map_err(db_err)?;
+        Ok(Self {
+            conn: Mutex::new(conn),
+        })
+    }
+
+    fn append(
+        conn: &Connection,
+        kind: &str,
+        market: Market,
+        instrument_id: &str,
+        order_id: &str,
+        label: &str,
+        payload: &impl Serialize,
+    ) -> BitResult<()> {
+        conn.execute(
+            "INSERT INTO events (ts, kind, market, instrument_id, order_id, label, payload)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            params![
+                now_ms(),
+                kind,
+                market_name(market),
+                instrument_id,
+                order_id,
+                label,
+                serde_json::to_string(payload)?
+            ],
+        )
+        .map_err(db_err)?;
+        Ok(())
+    }
+
+    // an older update never overwrites a newer one, REST acks and WebSocket updates race
+    fn upsert_order(conn: &Connection, market: Market, order: &Order) -> BitResult<()> {
+        conn.execute(
+            "INSERT INTO orders (order_id, market, instrument_id, label, status, updated_at, payload)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)
+             ON CONFLICT(order_id) DO UPDATE SET
+                 label = excluded.label, status = excluded.status,
+                 updated_at = excluded.updated_at, payload = excluded.payload
+             WHERE excluded.updated_at >= orders.updated_at",
+            params![
+                order.order_id,
+                market_name(market),
+                order.instrument_id,
+                order.label,
+                order.status,
+                order.updated_at,
+                serde_json::to_string(order)?
+            ],
+        )
+        .map_err(db_err)?;
+        Ok(())
+    }
+
+    // Mark a stored open order cancelled, in its payload as well as the status column, so
+    // `order` and `open_orders` agree with the `orders` table.
+    fn close_cancelled(conn: &Connection, order_id: &str) -> BitResult<()> {
+        let payload: Option<String> = conn
+            .query_row(
+                "SELECT payload FROM orders WHERE order_id = ?1 AND status IN ('open', 'pending')",
+                params![order_id],
+                |row| row.get(0),
+            )
+            .optional()
+            .map_err(db_err)?;
+        let Some(payload) = payload else {
+            return Ok(());
+        };
+        let mut order: Order = serde_json::from_str(&payload)?;
+        order.status = "cancelled".to_string();
+        order.updated_at = order.updated_at.max(now_ms());
+        conn.execute(
+            "UPDATE orders SET status = ?2, updated_at = ?3, payload = ?4 WHERE order_id = ?1",
+            params![
+                order_id,
+                order.status,
+                order.updated_at,
+                serde_json::to_string(&order)?
+            ],
+        )
+        .map_err(db_err)?;
+        Ok(())
+    }
+
+    /// Returns false when the fill was already stored.
+    fn insert_fill(conn: &Connection, market: Market, trade: &Trade) -> BitResult<bool> {
+        let inserted = conn
+            .execute(
+                "INSERT OR IGNORE INTO fills
+                 (trade_id, order_id, market, instrument_id, side, qty, label, created_at, payload)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+                params![
+                    trade.trade_id,
+                    trade.order_id,
+                    market_name(market),
+                    trade.instrument_id,
+                    match trade.side {
+                        Side::Buy => "buy",
+                        Side::Sell => "sell",
+                    },
+                    trade.qty,
+                    trade.label,
+                    trade.created_at,
+                    serde_json::to_string(trade)?
+                ],
+            )
+            .map_err(db_err)?;
+        if inserted > 0 {
+            Self::append(
+                conn,
+                "fill",
+                market,
+                &trade.instrument_id,
+                &trade.order_id,
+                &trade.label,
+                trade,
+            )?;
+        }
+        Ok(inserted > 0)
+    }
+
+    /// Record an order request before it is sent.
+    pub fn record_request(&self, market: Market, req: &OrderRequest) -> BitResult<()> {
+        let conn = self.conn.lock().unwrap();
+        let label = req.label.as_deref().unwrap_or("");
+        Self::append(
+            &conn,
+            "new_request",
+            market,
+            &req.instrument_id,
+            "",
+            label,
+            req,
+        )
+    }
+
+    /// Record an order as returned by the exchange. `kind` is the audit event, e.g. `ack`.
+    pub fn record_order(&self, kind: &str, market: Market, order: &Order) -> BitResult<()> {
+        let conn = self.conn.lock().unwrap();
+        Self::append(
+            &conn,
+            kind,
+            market,
+            &order.instrument_id,
+            &order.order_id,
+            &order.label,
+            order,
+        )?;
+        Self::upsert_order(&conn, market, order)
+    }
+
+    /// Record a request the exchange refused. `kind` is the audit event, e.g. `reject`.
+    pub fn record_reject(
+        &self,
+        kind: &str,
+        market: Market,
+        instrument_id: &str,
+        order_id: Option<&str>,
+        label: Option<&str>,
+        error: &BitError,
+    ) -> BitResult<()> {
+        let conn = self.conn.lock().unwrap();
+        Self::append(
+            &conn,
+            kind,
+            market,
+            instrument_id,
+            order_id.unwrap_or(""),
+            label.unwrap_or(""),
+            &error.to_string(),
+        )
+    }
+
+    pub fn record_amend(&self, market: Market, req: &AmendRequest) -> BitResult<()> {
+        let conn = self.conn.lock().unwrap();
+        Self::append(
+            &conn,
+            "amend_request",
+            market,
+            &req.instrument_id,
+            req.order_id.as_deref().unwrap_or(""),
+            req.label.as_deref().unwrap_or(""),
+            req,
+        )
+    }
+
+    /// Record a cancel request, and with `cancelled` the number of orders it cancelled.
+    /// A cancel by order id that cancelled something also closes that order.
+    pub fn record_cancel(
+        &self,
+        market: Market,
+        req: &CancelRequest,
+        cancelled: Option<i64>,
+    ) -> BitResult<()> {
+        let conn = self.conn.lock().unwrap();
+        let instrument_id = req.instrument_id.as_deref().unwrap_or("");
+        let order_id = req.order_id.as_deref().unwrap_or("");
+        let label = req.label.as_deref().unwrap_or("");
+        match cancelled {
+            None => Self::append(
+                &conn,
+                "cancel_request",
+                market,
+                instrument_id,
+                order_id,
+                label,
+                req,
+            ),
+            Some(count) => {
+                Self::append(
+                    &conn,
+                    "cancelled",
+                    market,
+                    instrument_id,
+                    order_id,
+                    label,
+                    &count,
+                )?;
+                if count > 0 && !order_id.is_empty() {
+                    Self::close_cancelled(&conn, order_id)?;
+                }
+                Ok(())
+            }
+        }
+    }
+
+    /// Store our fills, returning the ones not seen before.
+    pub fn record_fills(&self, market: Market, trades: &[Trade]) -> BitResult<Vec<Trade>> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction().map_err(db_err)?;
+        let mut new = vec![];
+        for trade in trades {
+            if Self::insert_fill(&tx, market, trade)? {
+                new.push(trade.clone());
+            }
+        }
+        tx.commit().map_err(db_err)?;
+        Ok(new)
+    }
+
+    /// Feed events from the private WebSocket. Order updates and fills are stored, other
+    /// events are ignored.
+    pub fn on_ws_event(&self, event: &WsEvent) -> BitResult<()> {
+        match event {
+            WsEvent::Orders(orders) => {
+                let mut conn = self.conn.lock().unwrap();
+                let tx = conn.transaction().map_err(db_err)?;
+                for order in orders {
+                    let market = Market::of_instrument(&order.instrument_id);
+                    Self::append(
+                        &tx,
+                        "order_update",
+                        market,
+                        &order.instrument_id,
+                        &order.order_id,
+                        &order.label,
+                        order,
+                    )?;
+                    Self::upsert_order(&tx, market, order)?;
+                }
+                tx.commit().map_err(db_err)
+            }
+            WsEvent::UserTrades(trades) => {
+                let mut conn = self.conn.lock().unwrap();
+                let tx = conn.transaction().map_err(db_err)?;
+                for trade in trades {
+                    Self::insert_fill(&tx, Market::of_instrument(&trade.instrument_id), trade)?;
+                }
+                tx.commit().map_err(db_err)
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn query_payloads(&self, sql: &str, args: &[&dyn rusqlite::ToSql]) -> BitResult<Vec<String>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(sql).map_err(db_err)?;
+        let rows = stmt
+            .query_map(args, |row| row.get(0))
+            .map_err(db_err)?
+            .collect::<Result<Vec<String>, _>>()
+            .map_err(db_err)?;
+        Ok(rows)
+    }
+
+    pub fn order(&self, order_id: &str) -> BitResult<Option<Order>> {
+        let conn = self.conn.lock().unwrap();
+        let payload: Option<String> = conn
+            .query_row(
+                "SELECT payload FROM orders WHERE order_id = ?1",
+                params![order_id],
+                |row| row.get(0),
+            )
+            .optional()
+            .map_err(db_err)?;
+        Ok(payload.map(|p| serde_json::from_str(&p)).transpose()?)
+    }
+
+    /// Orders carrying `label`, oldest first.
+    pub fn orders_by_label(&self, label: &str) -> BitResult<Vec<Order>> {
+        decode_rows(self.query_payloads(
+            "SELECT payload FROM orders WHERE label = ?1 ORDER BY updated_at",
+            &[&label],
+        )?)
+    }
+
+    /// Orders last seen open in `market`.
+    pub fn open_orders(&self, market: Market) -> BitResult<Vec<Order>> {
+        decode_rows(self.query_payloads(
+            "SELECT payload FROM orders
+             WHERE market = ?1 AND status IN ('open', 'pending') ORDER BY updated_at",
+            &[&market_name(market)],
+        )?)
+    }
+
+    /// Fills created at or after `since_ms`, oldest first.
+    pub fn fills_since(&self, since_ms: i64) -> BitResult<Vec<Trade>> {
+        decode_rows(self.query_payloads(
+            "SELECT payload FROM fills WHERE created_at >= ?1 ORDER BY created_at",
+            &[&since_ms],
+        )?)
+    }
+
+    /// Time of the newest stored fill of `market` in `currency`, 0 when there is none.
+    /// Spot fills count whatever their currency, as in `resync`.
+    pub fn last_fill_time_of(&self, market: Market, currency: &str) -> BitResult<i64> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn
+            .prepare("SELECT instrument_id, MAX(created_at) FROM fills WHERE market = ?1 GROUP BY instrument_id")
+            .map_err(db_err)?;
+        let rows = stmt
+            .query_map(params![market_name(market)], |row| {
+                Ok((row.get::<_, String>(0)?, row.get::<_, i64>(1)?))
+            })
+            .map_err(db_err)?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(db_err)?;
+        Ok(rows
+            .into_iter()
+            .filter(|(inst, _)| market == Market::Spot || settle_currency(inst) == currency)
+            .map(|(_, ts)| ts)
+            .max()
+            .unwrap_or(0))
+    }
+
+    /// Time of the newest stored fill, 0 when there is none.
+    pub fn last_fill_time(&self) -> BitResult<i64> {
+        let conn = self.conn.lock().unwrap();
+        conn.query_row(
+            "SELECT COALESCE(MAX(created_at), 0) FROM fills",
+            [],
+            |row| row.get(0),
+        )
+        .map_err(db_err)
+    }
+
+    /// Signed quantity traded per instrument over every stored fill. Matches the positions
+    /// only when the store has seen every fill since they were flat.
+    pub fn net_positions(&self) -> BitResult<BTreeMap<String, f64>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn
+            .prepare(
+                "SELECT instrument_id, SUM(CASE side WHEN 'buy' THEN qty ELSE -qty END)
+                 FROM fills GROUP BY instrument_id",
+            )
+            .map_err(db_err)?;
+        let rows = stmt
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+            .map_err(db_err)?
+            .collect::<Result<BTreeMap<String, f64>, _>>()
+            .map_err(db_err)?;
+        Ok(rows)
+    }
+
+    /// Every event of an order, oldest first. The request that created it has no order id
+    /// yet and is found through its label with `audit_by_label`.
+    pub fn audit(&self, order_id: &str) -> BitResult<Vec<AuditEntry>> {
+        self.audit_where("order_id = ?1", &order_id)
+    }
+
+    pub fn audit_by_label(&self, label: &str) -> BitResult<Vec<AuditEntry>> {
+        self.audit_where("label = ?1", &label)
+    }
+
+    /// Events recorded at or after `since_ms`.
+    pub fn audit_since(&self, since_ms: i64) -> BitResult<Vec<AuditEntry>> {
+        self.audit_where("ts >= ?1", &since_ms)
+    }
+
+    fn audit_where(&self, filter: &str, arg: &dyn rusqlite::ToSql) -> BitResult<Vec<AuditEntry>> {
+        let conn = self.conn.lock().unwrap();
+        let sql = format!(
+            "SELECT id, ts, kind, market, instrument_id, order_id, label, payload
+             FROM events WHERE {} ORDER BY id",
+            filter
+        );
+        let mut stmt = conn.prepare(&sql).map_err(db_err)?;
+        let rows = stmt
+            .query_map([arg], |row| {
+                Ok((
+                    AuditEntry {
+                        id: row.get(0)?,
+                        ts: row.get(1)?,
+                        kind: row.get(2)?,
+                        market: row.get(3)?,
+                        instrument_id: row.get(4)?,
+                        order_id: row.get(5)?,
+                        label: row.get(6)?,
+                        payload: Value::Null,
+                    },
+                    row.get::<_, String>(7)?,
+                ))
+            })
+            .map_err(db_err)?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(db_err)?;
+        rows.into_iter()
+            .map(|(mut entry, payload)| {
+                entry.payload = serde_json::from_str(&payload)?;
+                Ok(entry)
+            })
+            .collect()
+    }
+
+    /// Remember a WebSocket subscription under `name`, replacing any previous one.
+    pub fn save_subscription(&self, name: &str, subscription: &WsSubscription) -> BitResult<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "INSERT OR REPLACE INTO subscriptions (name, payload) VALUES (?1, ?2)",
+            params![name, serde_json::to_string(subscription)?],
+        )
+        .map_err(db_err)?;
+        Ok(())
+    }
+
+    pub fn remove_subscription(&self, name: &str) -> BitResult<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute("DELETE FROM subscriptions WHERE name = ?1", params![name])
+            .map_err(db_err)?;
+        Ok(())
+    }
+
+    pub fn subscriptions(&self) -> BitResult<BTreeMap<String, WsSubscription>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn
+            .prepare("SELECT name, payload FROM subscriptions")
+            .map_err(db_err)?;
+        let rows = stmt
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+            .map_err(db_err)?
+            .collect::<Result<Vec<(String, String)>, _>>()
+            .map_err(db_err)?;
+        rows.into_iter()
+            .map(|(name, payload)| Ok((name, serde_json::from_str(&payload)?)))
+            .collect()
+    }
+
+    /// Catch up with the exchange after a restart: store the fills of `market` in `currency`
+    /// since the newest stored one, and its open orders, and close stored orders that are no
+    /// longer open. A closed order counts as filled when its stored fills cover its qty, as
+    /// cancelled otherwise. An empty store only gets the exchange's most recent fills.
+    pub async fn resync<T: TradingApi>(
+        &self,
+        api: &T,
+        market: Market,
+        currency: &str,
+    ) -> BitResult<ResyncReport> {
+        let trades = match self.last_fill_time_of(market, currency)? {
+            0 => api.user_trades(market, currency, None).await?,
+            // from the newest stored fill itself, fills in the same ms may not all be stored
+            since => api.user_trades_since(market, currency, None, since).await?,
+        };
+        let new_fills = self.record_fills(market, &trades)?;
+        let open_orders = api.open_orders(market, currency, None).await?;
+
+        let live: HashSet<&str> = open_orders.iter().map(|o| o.order_id.as_str()).collect();
+        // spot open orders are not queried per currency
+        let stale: Vec<Order> = self
+            .open_orders(market)?
+            .into_iter()
+            .filter(|o| market == Market::Spot || settle_currency(&o.instrument_id) == currency)
+            .filter(|o| !live.contains(o.order_id.as_str()))
+            .collect();
+
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction().map_err(db_err)?;
+        for order in &open_orders {
+            Self::upsert_order(&tx, market, order)?;
+        }
+        let mut closed = vec![];
+        for mut order in stale {
+            let filled: f64 = tx
+                .query_row(
+                    "SELECT COALESCE(SUM(qty), 0) FROM fills WHERE order_id = ?1",
+                    params![order.order_id],
+                    |row| row.get(0),
+                )
+                .map_err(db_err)?;
+            order.filled_qty = order.filled_qty.max(filled);
+            order.status = if order.filled_qty >= order.qty {
+                "filled".to_string()
+            } else {
+                "cancelled".to_string()
+            };
+            order.updated_at = order.updated_at.max(now_ms());
+            Self::append(
+                &tx,
+                "order_update",
+                market,
+                &order.instrument_id,
+                &order.order_id,
+                &order.label,
+                &order,
+            )?;
+            Self::upsert_order(&tx, market, &order)?;
+            closed.push(order.order_id);
+        }
+        tx.commit().map_err(db_err)?;
+
+        Ok(ResyncReport {
+            new_fills,
+            open_orders,
+            closed,
+        })
+    }
+}
+
+/// A `TradingApi` that records every order-entry call and its outcome in an `OrderStore`.
+///
+/// Requests are stored before they are sent and a failed write fails the call, so nothing
+/// reaches the exchange without a trace. Writes after the exchange answered cannot undo the
+/// call and are best effort; `resync` fills any gap.
+pub struct RecordingApi<T> {
+    inner: Arc<T>,
+    store: Arc<OrderStore>,
+}
+
+impl<T: TradingApi> RecordingApi<T> {
+    pub fn new(inner: Arc<T>, store: Arc<OrderStore>) -> Self {
+        Self { inner, store }
+    }
+
+    pub fn inner(&self) -> &Arc<T> {
+        &self.inner
+    }
+
+    pub fn store(&self) -> &Arc<OrderStore> {
+        &self.store
+    }
+}
+
+impl<T: TradingApi> TradingApi for RecordingApi<T> {
+    async fn new_order(&self, market: Market, req: &OrderRequest) -> BitResult<Order> {
+        self.store.record_request(market, req)?;
+        let result = self.inner.new_order(market, req).await;
+        // best effort, see above
+        let _ = match &result {
+            Ok(order) => self.store.record_order("ack", market, order),
+            Err(e) => self.store.record_reject(
+                "reject",
+                market,
+                &req.instrument_id,
+                None,
+                req.label.as_deref(),
+                e,
+            ),
+        };
+        result
+    }
+
+    async fn amend_order(&self, market: Market, req: &AmendRequest) -> BitResult<Order> {
+        self.store.record_amend(market, req)?;
+        let result = self.inner.amend_order(market, req).await;
+        let _ = match &result {
+            Ok(order) => self.store.record_order("amend_ack", market, order),
+            Err(e) => self.store.record_reject(
+                "amend_reject",
+                market,
+                &req.instrument_id,
+                req.order_id.as_deref(),
+                req.label.as_deref(),
+                e,
+            ),
+        };
+        result
+    }
+
+    async fn cancel_order(&self, market: Market, req: &CancelRequest) -> BitResult<i64> {
+        self.store.record_cancel(market, req, None)?;
+        let result = self.inner.cancel_order(market, req).await;
+        let _ = match &result {
+            Ok(count) => self.store.record_cancel(market, req, Some(*count)),
+            Err(e) => self.store.record_reject(
+                "cancel_reject",
+                market,
+                req.instrument_id.as_deref().unwrap_or(""),
+                req.order_id.as_deref(),
+                req.label.as_deref(),
+                e,
+            ),
+        };
+        result
+    }
+
+    async fn batch_new_orders(
+        &self,
+        market: Market,
+        reqs: &[OrderRequest],
+    ) -> BitResult<Vec<Order>> {
+        for req in reqs {
+            self.store.record_request(market, req)?;
+        }
+        let result = self.inner.batch_new_orders(market, reqs).await;
+        match &result {
+            Ok(orders) => {
+                for order in orders {
+                    let _ = self.store.record_order("ack", market, order);
+                }
+            }
+            Err(e) => {
+                for req in reqs {
+                    let _ = self.store.record_reject(
+                        "reject",
+                        market,
+                        &req.instrument_id,
+                        None,
+                        req.label.as_deref(),
+                        e,
+                    );
+                }
+            }
+        }
+        result
+    }
+
+    async fn batch_amend_orders(
+        &self,
+        market: Market,
+        reqs: &[AmendRequest],
+    ) -> BitResult<Vec<Order>> {
+        for req in reqs {
+            self.store.record_amend(market, req)?;
+        }
+        let result = self.inner.batch_amend_orders(market, reqs).await;
+        match &result {
+            Ok(orders) => {
+                for order in orders {
+                    let _ = self.store.record_order("amend_ack", market, order);
+                }
+            }
+            Err(e) => {
+                for req in reqs {
+                    let _ = self.store.record_reject(
+                        "amend_reject",
+                        market,
+                        &req.instrument_id,
+                        req.order_id.as_deref(),
+                        req.label.as_deref(),
+                        e,
+                    );
+                }
+            }
+        }
+        result
+    }
+
+    async fn open_orders(
+        &self,
+        market: Market,
+        currency: &str,
+        instrument_id: Option<&str>,
+    ) -> BitResult<Vec<Order>> {
+        self.inner
+            .open_orders(market, currency, instrument_id)
+            .await
+    }
+
+    async fn order_by_label(
+        &self,
+        market: Market,
+        currency: &str,
+        label: &str,
+    ) -> BitResult<Option<Order>> {
+        self.inner.order_by_label(market, currency, label).await
+    }
+
+    /// Also stores any fills the store has not seen.
+    async fn user_trades(
+        &self,
+        market: Market,
+        currency: &str,
+        instrument_id: Option<&str>,
+    ) -> BitResult<Vec<Trade>> {
+        let trades = self
+            .inner
+            .user_trades(market, currency, instrument_id)
+            .await?;
+        let _ = self.store.record_fills(market, &trades);
+        Ok(trades)
+    }
+
+    async fn user_trades_since(
+        &self,
+        market: Market,
+        currency: &str,
+        instrument_id: Option<&str>,
+        start_ms: i64,
+    ) -> BitResult<Vec<Trade>> {
+        let trades = self
+            .inner
+            .user_trades_since(market, currency, instrument_id, start_ms)
+            .await?;
+        let _ = self.store.record_fills(market, &trades);
+        Ok(trades)
+    }
+
+    async fn positions(&self, currency: &str) -> BitResult<Vec<Position>> {
+        self.inner.positions(currency).await
+    }
+
+    async fn um_account(&self) -> BitResult<UmAccount> {
+        self.inner.um_account().await
+    }
+}
@@ -0,0 +1,340 @@
+// The order-entry and query surface shared by the live exchange and the simulated backends.
+// Strategies written against `TradingApi` switch between live and paper trading by changing
+// the constructor of the backend they are handed.
+
+use crate::bit_rest_client::{
+    BitRestClient, V1_LINEAR_AMEND_ORDERS, V1_LINEAR_ORDERS, V1_OPTION_AMEND_ORDERS,
+    V1_OPTION_ORDERS, V1_SPOT_AMEND_ORDERS, V1_SPOT_ORDERS,
+};
+use crate::error::{BitResult, parse_data, parse_page};
+use crate::latency::Timed;
+use crate::models::{
+    AmendRequest, CancelRequest, Market, Order, OrderRequest, Position, Trade, UmAccount,
+    settle_currency,
+};
+use crate::util::now_ms;
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+use std::collections::HashSet;
+use std::future::Future;
+
+pub trait TradingApi: Send + Sync {
+    fn new_order(
+        &self,
+        market: Market,
+        req: &OrderRequest,
+    ) -> impl Future<Output = BitResult<Order>> + Send;
+
+    fn amend_order(
+        &self,
+        market: Market,
+        req: &AmendRequest,
+    ) -> impl Future<Output = BitResult<Order>> + Send;
+
+    /// Returns the number of cancelled orders.
+    fn cancel_order(
+        &self,
+        market: Market,
+        req: &CancelRequest,
+    ) -> impl Future<Output = BitResult<i64>> + Send;
+
+    fn batch_new_orders(
+        &self,
+        market: Market,
+        reqs: &[OrderRequest],
+    ) -> impl Future<Output = BitResult<Vec<Order>>> + Send;
+
+    fn batch_amend_orders(
+        &self,
+        market: Market,
+        reqs: &[AmendRequest],
+    ) -> impl Future<Output = BitResult<Vec<Order>>> + Send;
+
+    fn open_orders(
+        &self,
+        market: Market,
+        currency: &str,
+        instrument_id: Option<&str>,
+    ) -> impl Future<Output = BitResult<Vec<Order>>> + Send;
+
+    /// The order carrying `label`, open or not. The newest one when a label was reused.
+    fn order_by_label(
+        &self,
+        market: Market,
+        currency: &str,
+        label: &str,
+    ) -> impl Future<Output = BitResult<Option<Order>>> + Send;
+
+    fn user_trades(
+        &self,
+        market: Market,
+        currency: &str,
+        instrument_id: Option<&str>,
+    ) -> impl Future<Output = BitResult<Vec<Trade>>> + Send;
+
+    /// Our fills from `start_ms` on, every page of them, oldest first. Use it rather than
+    /// `user_trades`, which returns only the latest page, whenever fills must add up.
+    fn user_trades_since(
+        &self,
+        market: Market,
+        currency: &str,
+        instrument_id: Option<&str>,
+        start_ms: i64,
+    ) -> impl Future<Output = BitResult<Vec<Trade>>> + Send;
+
+    fn positions(&self, currency: &str) -> impl Future<Output = BitResult<Vec<Position>>> + Send;
+
+    fn um_account(&self) -> impl Future<Output = BitResult<UmAccount>> + Send;
+}
+
+/// Batch endpoints return either a bare list or `{"orders": [...]}`.
+fn parse_list<T: DeserializeOwned>(rsp: Value) -> BitResult<Vec<T>> {
+    let data: Value = parse_data(rsp)?;
+    let list = match data {
+        Value::Object(mut map) => map.remove("orders").unwrap_or(Value::Array(vec![])),
+        v => v,
+    };
+    Ok(serde_json::from_value(list)?)
+}
+
+fn query_params(market: Market, currency: &str, instrument_id: Option<&str>) -> Value {
+    let mut params = match market {
+        Market::Spot => serde_json::json!({}),
+        Market::Linear | Market::Option => serde_json::json!({ "currency": currency }),
+    };
+    if let Some(instrument_id) = instrument_id {
+        let key = match market {
+            Market::Spot => "pair",
+            Market::Linear | Market::Option => "instrument_id",
+        };
+        params[key] = Value::String(instrument_id.to_string());
+    }
+    params
+}
+
+fn batch_params<I: Iterator<Item = Value>>(market: Market, currency: &str, items: I) -> Value {
+    let orders_data: Vec<Value> = items
+        .map(|mut item| {
+            if let Some(map) = item.as_object_mut() {
+                map.remove("currency");
+            }
+            item
+        })
+        .collect();
+    match market {
+        Market::Spot => serde_json::json!({ "orders_data": orders_data }),
+        Market::Linear | Market::Option => {
+            serde_json::json!({ "currency": currency, "orders_data": orders_data })
+        }
+    }
+}
+
+fn order_path(market: Market) -> &'static str {
+    match market {
+        Market::Spot => V1_SPOT_ORDERS,
+        Market::Linear => V1_LINEAR_ORDERS,
+        Market::Option => V1_OPTION_ORDERS,
+    }
+}
+
+fn amend_path(market: Market) -> &'static str {
+    match market {
+        Market::Spot => V1_SPOT_AMEND_ORDERS,
+        Market::Linear => V1_LINEAR_AMEND_ORDERS,
+        Market::Option => V1_OPTION_AMEND_ORDERS,
+    }
+}
+
+impl BitRestClient {
+    /// `TradingApi::new_order` with the send and receive timestamps of the request.
+    pub async fn new_order_timed(
+        &self,
+        market: Market,
+        req: &OrderRequest,
+    ) -> BitResult<Timed<Order>> {
+        let params = req.params(market);
+        self.call_private_typed_timed(order_path(market), params.into_iter().flatten())
+            .await
+    }
+
+    /// `TradingApi::amend_order` with the send and receive timestamps of the request.
+    pub async fn amend_order_timed(
+        &self,
+        market: Market,
+        req: &AmendRequest,
+    ) -> BitResult<Timed<Order>> {
+        let params = req.params(market);
+        self.call_private_typed_timed(amend_path(market), params.into_iter().flatten())
+            .await
+    }
+}
+
+impl TradingApi for BitRestClient {
+    // order entry skips `to_params` and the `Value` round trip, see `call_private_typed`
+    async fn new_order(&self, market: Market, req: &OrderRequest) -> BitResult<Order> {
+        let params = req.params(market);
+        self.call_private_typed(order_path(market), params.into_iter().flatten())
+            .await
+    }
+
+    async fn amend_order(&self, market: Market, req: &AmendRequest) -> BitResult<Order> {
+        let params = req.params(market);
+        self.call_private_typed(amend_path(market), params.into_iter().flatten())
+            .await
+    }
+
+    async fn cancel_order(&self, market: Market, req: &CancelRequest) -> BitResult<i64> {
+        let mut params = req.to_params(market);
+        let rsp = match market {
+            Market::Spot => self.spot_cancel_order(&mut params).await?,
+            Market::Linear => self.linear_cancel_order(&mut params).await?,
+            Market::Option => self.option_cancel_order(&mut params).await?,
+        };
+        let data: Value = parse_data(rsp)?;
+        Ok(data["num_cancelled"].as_i64().unwrap_or(0))
+    }
+
+    async fn batch_new_orders(
+        &self,
+        market: Market,
+        reqs: &[OrderRequest],
+    ) -> BitResult<Vec<Order>> {
+        let Some(first) = reqs.first() else {
+            return Ok(vec![]);
+        };
+        let currency = settle_currency(&first.instrument_id).to_string();
+        let mut params = batch_params(market, &currency, reqs.iter().map(|r| r.to_params(market)));
+        let rsp = match market {
+            Market::Spot => self.spot_batch_new_orders(&mut params).await?,
+            Market::Linear => self.linear_batch_new_orders(&mut params).await?,
+            Market::Option => self.option_batch_new_orders(&mut params).await?,
+        };
+        parse_list(rsp)
+    }
+
+    async fn batch_amend_orders(
+        &self,
+        market: Market,
+        reqs: &[AmendRequest],
+    ) -> BitResult<Vec<Order>> {
+        let Some(first) = reqs.first() else {
+            return Ok(vec![]);
+        };
+        let currency = settle_currency(&first.instrument_id).to_string();
+        let mut params = batch_params(market, &currency, reqs.iter().map(|r| r.to_params(market)));
+        let rsp = match market {
+            Market::Spot => self.spot_batch_amend_orders(&mut params).await?,
+            Market::Linear => self.linear_batch_amend_orders(&mut params).await?,
+            Market::Option => self.option_batch_amend_orders(&mut params).await?,
+        };
+        parse_list(rsp)
+    }
+
+    async fn open_orders(
+        &self,
+        market: Market,
+        currency: &str,
+        instrument_id: Option<&str>,
+    ) -> BitResult<Vec<Order>> {
+        let mut params = query_params(market, currency, instrument_id);
+        let rsp = match market {
+            Market::Spot => self.spot_get_open_orders(&mut params).await?,
+            Market::Linear => self.linear_get_open_orders(&mut params).await?,
+            Market::Option => self.option_get_open_orders(&mut params).await?,
+        };
+        parse_data(rsp)
+    }
+
+    async fn order_by_label(
+        &self,
+        market: Market,
+        currency: &str,
+        label: &str,
+    ) -> BitResult<Option<Order>> {
+        let mut params = query_params(market, currency, None);
+        params["label"] = Value::String(label.to_string());
+        let rsp = match market {
+            Market::Spot => self.spot_get_orders(&mut params).await?,
+            Market::Linear => self.linear_get_orders(&mut params).await?,
+            Market::Option => self.option_get_orders(&mut params).await?,
+        };
+        let orders: Vec<Order> = parse_data(rsp)?;
+        Ok(orders
+            .into_iter()
+            .filter(|o| o.label == label)
+            .max_by_key(|o| o.created_at))
+    }
+
+    async fn user_trades(
+        &self,
+        market: Market,
+        currency: &str,
+        instrument_id: Option<&str>,
+    ) -> BitResult<Vec<Trade>> {
+        let mut params = query_params(market, currency, instrument_id);
+        let rsp = match market {
+            Market::Spot => self.spot_get_user_trades(&mut params).await?,
+            Market::Linear => self.linear_get_user_trades(&mut params).await?,
+            Market::Option => self.option_get_user_trades(&mut params).await?,
+        };
+        parse_data(rsp)
+    }
+
+    // a day per window, like `pnl::fetch_trades`, and every page of each
+    async fn user_trades_since(
+        &self,
+        market: Market,
+        currency: &str,
+        instrument_id: Option<&str>,
+        start_ms: i64,
+    ) -> BitResult<Vec<Trade>> {
+        const LIMIT: u32 = 100;
+        const DAY_MS: i64 = 24 * 3600 * 1000;
+        let end_ms = now_ms() + 1;
+        let mut seen = HashSet::new();
+        let mut trades = vec![];
+        let mut start = start_ms;
+        while start < end_ms {
+            let end = (start + DAY_MS).min(end_ms);
+            let mut offset = 1;
+            loop {
+                let mut params = query_params(market, currency, instrument_id);
+                params["start_time"] = Value::from(start);
+                params["end_time"] = Value::from(end);
+                params["offset"] = Value::from(offset);
+                params["limit"] = Value::from(LIMIT);
+                let rsp = match market {
+                    Market::Spot => self.spot_get_user_trades(&mut params).await?,
+                    Market::Linear => self.linear_get_user_trades(&mut params).await?,
+                    Market::Option => self.option_get_user_trades(&mut params).await?,
+                };
+                let page = parse_page::<Trade>(rsp, LIMIT)?;
+                let done = !page.has_more || page.items.is_empty();
+                for t in page.items {
+                    if seen.insert(t.trade_id.clone()) {
+                        trades.push(t);
+                    }
+                }
+                if done {
+                    break;
+                }
+                offset += 1;
+            }
+            start = end;
+        }
+        trades.sort_by_key(|t| t.created_at);
+        Ok(trades)
+    }
+
+    async fn positions(&self, currency: &str) -> BitResult<Vec<Position>> {
+        let rsp = self
+            .linear_get_positions(&mut serde_json::json!({ "currency": currency }))
+            .await?;
+        parse_data(rsp)
+    }
+
+    async fn um_account(&self) -> BitResult<UmAccount> {
+        let rsp = self.get_um_account(&mut serde_json::json!({})).await?;
+        parse_data(rsp)
+    }
+}
@@ -0,0 +1,101 @@
+use chrono::Utc;
+use serde::de::{self, Visitor};
+use serde::{Deserialize, Deserializer};
+use std::fmt;
+
+/// bit.com sends most decimals as strings ("100.00000000"), but not all of them.
+pub fn de_f64<'de, D>(deserializer: D) -> Result<f64, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    // a visitor rather than an untagged enum, which would copy every string before parsing
+    struct F64Visitor;
+
+    impl Visitor<'_> for F64Visitor {
+        type Value = f64;
+
+        fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            f.write_str("a number or a numeric string")
+        }
+
+        fn visit_str<E: de::Error>(self, s: &str) -> Result<f64, E> {
+            if s.is_empty() {
+                return Ok(0.0);
+            }
+            s.parse().map_err(de::Error::custom)
+        }
+
+        fn visit_f64<E: de::Error>(self, n: f64) -> Result<f64, E> {
+            Ok(n)
+        }
+
+        fn visit_i64<E: de::Error>(self, n: i64) -> Result<f64, E> {
+            Ok(n as f64)
+        }
+
+        fn visit_u64<E: de::Error>(self, n: u64) -> Result<f64, E> {
+            Ok(n as f64)
+        }
+    }
+
+    deserializer.deserialize_any(F64Visitor)
+}
+
+pub fn now_ms() -> i64 {
+    Utc::now().timestamp_millis()
+}
+
+/// Ids are strings on some endpoints and integers on others.
+pub fn de_string<'de, D>(deserializer: D) -> Result<String, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    struct StringVisitor;
+
+    impl Visitor<'_> for StringVisitor {
+        type Value = String;
+
+        fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            f.write_str("a string or a number")
+        }
+
+        fn visit_str<E: de::Error>(self, s: &str) -> Result<String, E> {
+            Ok(s.to_string())
+        }
+
+        fn visit_string<E: de::Error>(self, s: String) -> Result<String, E> {
+            Ok(s)
+        }
+
+        fn visit_i64<E: de::Error>(self, n: i64) -> Result<String, E> {
+            Ok(n.to_string())
+        }
+
+        fn visit_u64<E: de::Error>(self, n: u64) -> Result<String, E> {
+            Ok(n.to_string())
+        }
+
+        fn visit_f64<E: de::Error>(self, n: f64) -> Result<String, E> {
+            Ok(serde_json::Number::from_f64(n)
+                .map(|n| n.to_string())
+                .unwrap_or_else(|| n.to_string()))
+        }
+    }
+
+    deserializer.deserialize_any(StringVisitor)
+}
+
+/// Order book levels come as `[["price", "qty"], ...]`.
+pub fn de_levels<'de, D>(deserializer: D) -> Result<Vec<(f64, f64)>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    struct Level(
+        #[serde(deserialize_with = "de_f64")] f64,
+        #[serde(deserialize_with = "de_f64")] f64,
+    );
+
+    let levels = Vec::<Level>::deserialize(deserializer)?;
+    Ok(levels.into_iter().map(|l| (l.0, l.1)).collect())
+}
@@ -0,0 +1,353 @@
+// Typed wallet calls: deposit addresses, deposits, withdrawals and transfers.
+
+use crate::bit_rest_client::BitRestClient;
+use crate::error::{BitError, BitResult, parse_data};
+use crate::util::{de_f64, de_string};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DepositAddress {
+    pub currency: String,
+    #[serde(default)]
+    pub chain: String,
+    pub address: String,
+    /// Tag / memo for chains that need one (XRP, EOS, ...).
+    #[serde(default)]
+    pub memo: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Deposit {
+    #[serde(deserialize_with = "de_string")]
+    pub id: String,
+    pub currency: String,
+    #[serde(default)]
+    pub chain: String,
+    #[serde(deserialize_with = "de_f64")]
+    pub amount: f64,
+    #[serde(default)]
+    pub address: String,
+    #[serde(default)]
+    pub tx_hash: String,
+    pub status: String,
+    #[serde(default)]
+    pub created_at: i64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Withdrawal {
+    #[serde(deserialize_with = "de_string")]
+    pub id: String,
+    pub currency: String,
+    #[serde(default)]
+    pub chain: String,
+    #[serde(deserialize_with = "de_f64")]
+    pub amount: f64,
+    #[serde(default, deserialize_with = "de_f64")]
+    pub fee: f64,
+    #[serde(default)]
+    pub address: String,
+    #[serde(default)]
+    pub memo: Option<String>,
+    #[serde(default)]
+    pub tx_hash: String,
+    #[serde(default)]
+    pub client_id: Option<String>,
+    pub status: String,
+    #[serde(default)]
+    pub created_at: i64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WithdrawFee {
+    pub currency: String,
+    #[serde(default)]
+    pub chain: String,
+    #[serde(deserialize_with = "de_f64")]
+    pub fee: f64,
+    #[serde(default, deserialize_with = "de_f64")]
+    pub min_amount: f64,
+}
+
+// Checks shared by the requests that move funds, run before anything is signed.
+fn check_amount(amount: f64) -> BitResult<()> {
+    if !amount.is_finite() || amount <= 0.0 {
+        return Err(BitError::Invalid(format!(
+            "amount must be positive, got {}",
+            amount
+        )));
+    }
+    Ok(())
+}
+
+fn check_present(field: &str, value: &str) -> BitResult<()> {
+    if value.trim().is_empty() {
+        return Err(BitError::Invalid(format!("{} is empty", field)));
+    }
+    Ok(())
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct WithdrawRequest {
+    pub currency: String,
+    pub chain: String,
+    pub address: String,
+    pub amount: f64,
+    pub memo: Option<String>,
+    /// Idempotency key, a retried request with the same id is not paid out twice.
+    pub client_id: Option<String>,
+}
+
+impl WithdrawRequest {
+    pub fn new(currency: &str, chain: &str, address: &str, amount: f64) -> Self {
+        Self {
+            currency: currency.to_string(),
+            chain: chain.to_string(),
+            address: address.to_string(),
+            amount,
+            memo: None,
+            client_id: None,
+        }
+    }
+
+    pub fn with_memo(mut self, memo: &str) -> Self {
+        self.memo = Some(memo.to_string());
+        self
+    }
+
+    pub fn with_client_id(mut self, client_id: &str) -> Self {
+        self.client_id = Some(client_id.to_string());
+        self
+    }
+
+    pub fn validate(&self) -> BitResult<()> {
+        check_present("currency", &self.currency)?;
+        check_present("chain", &self.chain)?;
+        check_present("address", &self.address)?;
+        check_amount(self.amount)
+    }
+
+    pub fn to_params(&self) -> Value {
+        let mut params = serde_json::json!({
+            "currency": self.currency,
+            "chain": self.chain,
+            "address": self.address,
+            "amount": self.amount.to_string(),
+        });
+        if let Some(memo) = &self.memo {
+            params["memo"] = Value::String(memo.clone());
+        }
+        if let Some(client_id) = &self.client_id {
+            params["client_id"] = Value::String(client_id.clone());
+        }
+        params
+    }
+}
+
+/// Wallets of one user that funds can move between.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum WalletAccount {
+    Spot,
+    Um,
+}
+
+impl WalletAccount {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            WalletAccount::Spot => "spot",
+            WalletAccount::Um => "um",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TransferRequest {
+    pub currency: String,
+    pub amount: f64,
+    pub from: WalletAccount,
+    pub to: WalletAccount,
+}
+
+impl TransferRequest {
+    pub fn new(currency: &str, amount: f64, from: WalletAccount, to: WalletAccount) -> Self {
+        Self {
+            currency: currency.to_string(),
+            amount,
+            from,
+            to,
+        }
+    }
+
+    pub fn validate(&self) -> BitResult<()> {
+        check_present("currency", &self.currency)?;
+        if self.from == self.to {
+            return Err(BitError::Invalid(format!(
+                "cannot transfer from {} to itself",
+                self.from.as_str()
+            )));
+        }
+        check_amount(self.amount)
+    }
+
+    pub fn to_params(&self) -> Value {
+        serde_json::json!({
+            "currency": self.currency,
+            "amount": self.amount.to_string(),
+            "from_account": self.from.as_str(),
+            "to_account": self.to.as_str(),
+        })
+    }
+}
+
+/// Move funds between the master account and its sub-accounts, by user id.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SubAccountTransferRequest {
+    pub currency: String,
+    pub amount: f64,
+    pub from_uid: String,
+    pub to_uid: String,
+}
+
+impl SubAccountTransferRequest {
+    pub fn new(currency: &str, amount: f64, from_uid: &str, to_uid: &str) -> Self {
+        Self {
+            currency: currency.to_string(),
+            amount,
+            from_uid: from_uid.to_string(),
+            to_uid: to_uid.to_string(),
+        }
+    }
+
+    pub fn validate(&self) -> BitResult<()> {
+        check_present("currency", &self.currency)?;
+        check_present("from_uid", &self.from_uid)?;
+        check_present("to_uid", &self.to_uid)?;
+        if self.from_uid.trim() == self.to_uid.trim() {
+            return Err(BitError::Invalid(format!(
+                "cannot transfer from uid {} to itself",
+                self.from_uid
+            )));
+        }
+        check_amount(self.amount)
+    }
+
+    pub fn to_params(&self) -> Value {
+        serde_json::json!({
+            "currency": self.currency,
+            "amount": self.amount.to_string(),
+            "from_uid": self.from_uid,
+            "to_uid": self.to_uid,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Transfer {
+    #[serde(deserialize_with = "de_string")]
+    pub id: String,
+    pub currency: String,
+    #[serde(deserialize_with = "de_f64")]
+    pub amount: f64,
+    #[serde(default)]
+    pub from_account: String,
+    #[serde(default)]
+    pub to_account: String,
+    #[serde(default)]
+    pub status: String,
+    #[serde(default)]
+    pub created_at: i64,
+}
+
+/// Filter for the deposit, withdrawal and transfer history endpoints.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct HistoryQuery {
+    pub currency: Option<String>,
+    pub start_time: Option<i64>,
+    pub end_time: Option<i64>,
+    pub limit: Option<u32>,
+}
+
+impl HistoryQuery {
+    pub fn currency(currency: &str) -> Self {
+        Self {
+            currency: Some(currency.to_string()),
+            ..Default::default()
+        }
+    }
+
+    pub fn between(mut self, start_time: i64, end_time: i64) -> Self {
+        self.start_time = Some(start_time);
+        self.end_time = Some(end_time);
+        self
+    }
+
+    pub fn to_params(&self) -> Value {
+        let mut params = serde_json::json!({});
+        if let Some(currency) = &self.currency {
+            params["currency"] = Value::String(currency.clone());
+        }
+        if let Some(start_time) = self.start_time {
+            params["start_time"] = Value::from(start_time);
+        }
+        if let Some(end_time) = self.end_time {
+            params["end_time"] = Value::from(end_time);
+        }
+        if let Some(limit) = self.limit {
+            params["limit"] = Value::from(limit);
+        }
+        params
+    }
+}
+
+pub async fn deposit_address(
+    client: &BitRestClient,
+    currency: &str,
+    chain: &str,
+) -> BitResult<DepositAddress> {
+    let mut params = serde_json::json!({ "currency": currency, "chain": chain });
+    parse_data(client.get_deposit_address(&mut params).await?)
+}
+
+pub async fn deposits(client: &BitRestClient, query: &HistoryQuery) -> BitResult<Vec<Deposit>> {
+    parse_data(client.get_deposits(&mut query.to_params()).await?)
+}
+
+pub async fn withdraw(client: &BitRestClient, req: &WithdrawRequest) -> BitResult<Withdrawal> {
+    req.validate()?;
+    parse_data(client.withdraw(&mut req.to_params()).await?)
+}
+
+pub async fn withdrawals(
+    client: &BitRestClient,
+    query: &HistoryQuery,
+) -> BitResult<Vec<Withdrawal>> {
+    parse_data(client.get_withdrawals(&mut query.to_params()).await?)
+}
+
+pub async fn withdraw_fee(
+    client: &BitRestClient,
+    currency: &str,
+    chain: &str,
+) -> BitResult<WithdrawFee> {
+    let mut params = serde_json::json!({ "currency": currency, "chain": chain });
+    parse_data(client.get_withdraw_fee(&mut params).await?)
+}
+
+pub async fn transfer(client: &BitRestClient, req: &TransferRequest) -> BitResult<Transfer> {
+    req.validate()?;
+    parse_data(client.transfer(&mut req.to_params()).await?)
+}
+
+pub async fn subaccount_transfer(
+    client: &BitRestClient,
+    req: &SubAccountTransferRequest,
+) -> BitResult<Transfer> {
+    req.validate()?;
+    parse_data(client.subaccount_transfer(&mut req.to_params()).await?)
+}
+
+pub async fn transfers(client: &BitRestClient, query: &HistoryQuery) -> BitResult<Vec<Transfer>> {
+    parse_data(client.get_transfers(&mut query.to_params()).await?)
+}
@@ -0,0 +1,149 @@
+// Decoding WebSocket frames without building a `serde_json::Value` per message.
+//
+// `FrameDecoder` turns the payload of a text or binary frame into JSON text, inflating gzip,
+// zlib and raw deflate payloads into a buffer it reuses across messages. `WsFrame` borrows the
+// channel, the exchange timestamp and the raw `data` from that text, and typed events are
+// deserialized straight from the raw `data`. Implement `FromWsFrame` for a type with borrowed
+// fields to skip the remaining copies.
+
+use flate2::read::{DeflateDecoder, GzDecoder, ZlibDecoder};
+use serde::Deserialize;
+use serde_json::Value;
+use serde_json::value::RawValue;
+use std::borrow::Cow;
+use std::io::Read;
+
+/// Upper bound on an inflated payload, so a corrupt or hostile frame cannot exhaust memory.
+pub const MAX_INFLATED_LEN: u64 = 64 << 20;
+
+#[derive(Debug, Default)]
+pub struct FrameDecoder {
+    buf: Vec<u8>,
+}
+
+impl FrameDecoder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The JSON text of a frame payload: as is when it already is JSON, else inflated as gzip,
+    /// zlib or raw deflate. `None` when it is neither or not UTF-8.
+    pub fn text<'a>(&'a mut self, payload: &'a [u8]) -> Option<&'a str> {
+        let first = payload.iter().find(|b| !b.is_ascii_whitespace())?;
+        if matches!(first, b'{' | b'[') {
+            return std::str::from_utf8(payload).ok();
+        }
+        self.buf.clear();
+        let inflated = match payload {
+            [0x1f, 0x8b, ..] => inflate(GzDecoder::new(payload), &mut self.buf),
+            [cmf, flg, ..]
+                if cmf & 0x0f == 8 && (u16::from(*cmf) << 8 | u16::from(*flg)) % 31 == 0 =>
+            {
+                inflate(ZlibDecoder::new(payload), &mut self.buf)
+            }
+            _ => inflate(DeflateDecoder::new(payload), &mut self.buf),
+        };
+        if !inflated {
+            return None;
+        }
+        std::str::from_utf8(&self.buf).ok()
+    }
+}
+
+fn inflate(decoder: impl Read, buf: &mut Vec<u8>) -> bool {
+    match decoder.take(MAX_INFLATED_LEN + 1).read_to_end(buf) {
+        Ok(n) => n as u64 <= MAX_INFLATED_LEN,
+        Err(_) => false,
+    }
+}
+
+#[derive(Deserialize)]
+struct Envelope<'a> {
+    #[serde(borrow, default)]
+    channel: Option<Cow<'a, str>>,
+    #[serde(borrow, default)]
+    timestamp: Option<&'a RawValue>,
+    #[serde(borrow, default)]
+    data: Option<&'a RawValue>,
+}
+
+/// A `{"channel": ..., "timestamp": ..., "data": ...}` message, borrowed from its text.
+#[derive(Debug, Clone)]
+pub struct WsFrame<'a> {
+    text: &'a str,
+    channel: Option<Cow<'a, str>>,
+    timestamp: Option<i64>,
+    data: Option<&'a RawValue>,
+}
+
+impl<'a> WsFrame<'a> {
+    /// `None` when `text` is not JSON. Other JSON than an object is a frame without channel.
+    pub fn parse(text: &'a str) -> Option<Self> {
+        let envelope = match serde_json::from_str::<Envelope>(text) {
+            Ok(envelope) => envelope,
+            Err(_) => {
+                serde_json::from_str::<serde::de::IgnoredAny>(text).ok()?;
+                Envelope {
+                    channel: None,
+                    timestamp: None,
+                    data: None,
+                }
+            }
+        };
+        Some(Self {
+            text,
+            channel: envelope.channel,
+            // integers only, like `Value::as_i64`
+            timestamp: envelope.timestamp.and_then(|ts| ts.get().parse().ok()),
+            data: envelope.data,
+        })
+    }
+
+    /// The whole message.
+    pub fn text(&self) -> &'a str {
+        self.text
+    }
+
+    pub fn channel(&self) -> Option<&str> {
+        self.channel.as_deref()
+    }
+
+    /// The top-level `timestamp`.
+    pub fn timestamp(&self) -> Option<i64> {
+        self.timestamp
+    }
+
+    /// Top-level `timestamp`, else `data.timestamp`, see `latency::exchange_timestamp`.
+    pub fn exchange_timestamp(&self) -> Option<i64> {
+        #[derive(Deserialize)]
+        struct Data {
+            timestamp: Option<i64>,
+        }
+
+        self.timestamp.or_else(|| {
+            serde_json::from_str::<Data>(self.data?.get())
+                .ok()?
+                .timestamp
+        })
+    }
+
+    /// The raw JSON of `data`.
+    pub fn data(&self) -> Option<&'a RawValue> {
+        self.data
+    }
+
+    /// `data` deserialized as `T`, which may borrow from the message.
+    pub fn decode<T: Deserialize<'a>>(&self) -> Option<T> {
+        serde_json::from_str(self.data?.get()).ok()
+    }
+
+    /// `data` as a `Value`, `Null` when absent.
+    pub fn data_value(&self) -> Value {
+        self.decode().unwrap_or(Value::Null)
+    }
+
+    /// The whole message as a `Value`.
+    pub fn to_value(&self) -> Value {
+        serde_json::from_str(self.text).unwrap_or(Value::Null)
+    }
+}